@@ -0,0 +1,60 @@
+use crate::service::settings::Settings;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Sao lưu database theo mục "Backup" trong settings.json trước khi ghi câu hỏi mới,
+// chỉ giữ lại `Keep` bản gần nhất
+pub fn backup_before_import(settings: &Settings) -> Result<Option<PathBuf>, String> {
+    let db_path = Path::new(&settings.paths.database);
+    if !settings.backup.before_import || !db_path.exists() {
+        return Ok(None);
+    }
+
+    // Flush WAL để bản sao có đủ dữ liệu
+    if let Ok(conn) = duckdb::Connection::open(db_path) {
+        let _ = conn.execute("PRAGMA checkpoint;", []);
+    }
+
+    let backup_dir = Path::new(&settings.backup.directory);
+    fs::create_dir_all(backup_dir)
+        .map_err(|e| format!("Không thể tạo thư mục sao lưu {}: {}", backup_dir.display(), e))?;
+
+    let stem = db_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "data".to_string());
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let backup_path = backup_dir.join(format!("{}_{}.duckdb", stem, timestamp));
+
+    fs::copy(db_path, &backup_path)
+        .map_err(|e| format!("Không thể sao lưu database: {}", e))?;
+
+    prune_backups(backup_dir, &stem, settings.backup.keep);
+
+    Ok(Some(backup_path))
+}
+
+fn prune_backups(backup_dir: &Path, stem: &str, keep: usize) {
+    let prefix = format!("{}_", stem);
+    let mut backups: Vec<PathBuf> = match fs::read_dir(backup_dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".duckdb"))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+
+    // Tên file chứa thời gian dạng YYYYmmdd_HHMMSS nên sắp xếp theo tên là theo thời gian
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep);
+    for old in backups.into_iter().take(excess) {
+        if let Err(e) = fs::remove_file(&old) {
+            println!("Không thể xóa bản sao lưu cũ {}: {}", old.display(), e);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use duckdb::{Connection, Result};
 
-pub fn create_database() -> Result<()> {
-    let conn = Connection::open("data.duckdb")?;
+pub fn create_database(db_path: &str) -> Result<()> {
+    let conn = Connection::open(db_path)?;
 
     // Lấy danh sách tất cả các bảng và xóa từng bảng
     conn.execute(
@@ -20,6 +20,48 @@ pub fn create_database() -> Result<()> {
         [],
     )?;
 
+    ensure_schema(&conn)?;
+
+    Ok(())
+}
+
+// Bổ sung các cột mới cho database tạo từ phiên bản cũ
+pub fn ensure_schema(conn: &Connection) -> Result<()> {
+    // Dữ liệu cũ đều được tạo bằng all-minilm-l6-v2
+    conn.execute(
+        "ALTER TABLE data ADD COLUMN IF NOT EXISTS model VARCHAR DEFAULT 'all-minilm-l6-v2'",
+        [],
+    )?;
+
+    // Mã QN của câu hỏi, dùng để phát hiện trùng mã với ngân hàng câu hỏi
+    conn.execute(
+        "ALTER TABLE data ADD COLUMN IF NOT EXISTS question_id VARCHAR",
+        [],
+    )?;
+
+    // Nội dung câu hỏi và đáp án đúng, để người duyệt xem được câu trùng trong ngân hàng
+    conn.execute(
+        "ALTER TABLE data ADD COLUMN IF NOT EXISTS question_text VARCHAR",
+        [],
+    )?;
+    conn.execute(
+        "ALTER TABLE data ADD COLUMN IF NOT EXISTS answer_text VARCHAR",
+        [],
+    )?;
+
+    // Bản lượng tử hóa của embedding dạng BLOB cho lượt lọc nhanh ứng viên, chỉ lưu theo một cách lượng tử hóa
+    // (code_mode là "int8" hoặc "binary") để database không phình thêm mỗi lần đổi cấu hình
+    for column in ["question_code BLOB", "answer_code BLOB", "code_mode VARCHAR"] {
+        conn.execute(&format!("ALTER TABLE data ADD COLUMN IF NOT EXISTS {}", column), [])?;
+    }
+
+    // Cách chia đoạn văn bản dài lúc tạo embedding (ChunkSettings::signature); NULL là dữ liệu cũ, văn bản dài
+    // bị model cắt bớt
+    conn.execute(
+        "ALTER TABLE data ADD COLUMN IF NOT EXISTS chunking VARCHAR",
+        [],
+    )?;
+
     Ok(())
 }
 
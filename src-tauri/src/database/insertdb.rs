@@ -1,17 +1,166 @@
-use duckdb::{Connection, Result};
+use crate::functions::quantization::{quantize, QuantizationMode};
+use duckdb::{params, Connection, Result};
+
+// Một câu hỏi lưu vào ngân hàng
+pub struct BankRecord<'a> {
+    pub question_id: &'a str,
+    pub question_text: &'a str,
+    pub answer_text: &'a str,
+    pub question_embedding: &'a [f32],
+    pub answer_embedding: &'a [f32],
+}
+
+// Cách lượng tử hóa và bản mã (phần dẫn, đáp án) ghi vào các cột code_mode, question_code, answer_code
+fn quantized_codes(question: &[f32], answer: &[f32], mode: QuantizationMode) -> (&'static str, Vec<u8>, Vec<u8>) {
+    (mode.name(), quantize(question, mode).to_bytes(), quantize(answer, mode).to_bytes())
+}
+
+// `quantization` khác None thì ghi thêm bản lượng tử hóa của hai embedding; `chunking` là
+// ChunkSettings::signature lúc tạo embedding
+fn insert_record(
+    conn: &Connection,
+    record: &BankRecord,
+    model: &str,
+    quantization: Option<QuantizationMode>,
+    chunking: &str,
+) -> Result<()> {
+    let codes = quantization.map(|mode| quantized_codes(record.question_embedding, record.answer_embedding, mode));
 
-pub fn insert_embeddings(question_embedding: Vec<f32>, answer_embedding: Vec<f32>) -> Result<()> {
-    let conn = Connection::open("data.duckdb")?;
-    
     let query = format!(
-        "INSERT INTO data (question_embedding, answer_embedding) VALUES (array{:?}::REAL[], array{:?}::REAL[])",
-        question_embedding, answer_embedding
+        "INSERT INTO data (question_embedding, answer_embedding, model, question_id, question_text, answer_text, code_mode, question_code, answer_code, chunking) VALUES (array{:?}::REAL[], array{:?}::REAL[], ?, ?, ?, ?, ?, ?, ?, ?)",
+        record.question_embedding, record.answer_embedding
     );
-    
-    conn.execute(&query, [])?;
+    conn.execute(
+        &query,
+        params![
+            model,
+            record.question_id,
+            record.question_text,
+            record.answer_text,
+            codes.as_ref().map(|(mode, _, _)| *mode),
+            codes.as_ref().map(|(_, question, _)| question.as_slice()),
+            codes.as_ref().map(|(_, _, answer)| answer.as_slice()),
+            chunking,
+        ],
+    )?;
     Ok(())
 }
 
+// Ghi toàn bộ câu hỏi vào database `db_path` trong một transaction: lỗi ở bất kỳ câu nào thì không câu nào được lưu.
+// Database có thể là bản sao tạo từ phiên bản cũ nên được bổ sung cột trước khi ghi
+pub fn insert_embeddings_batch(
+    db_path: &str,
+    records: &[BankRecord],
+    model: &str,
+    quantization: Option<QuantizationMode>,
+    chunking: &str,
+) -> Result<()> {
+    let mut conn = Connection::open(db_path)?;
+    crate::database::createdb::ensure_schema(&conn)?;
+    let tx = conn.transaction()?;
+
+    for record in records {
+        insert_record(&tx, record, model, quantization, chunking)?;
+    }
+
+    tx.commit()
+}
+
+// Bổ sung bản lượng tử hóa cho các câu lưu trước khi bật lượng tử hóa hoặc lưu theo cách khác `mode`,
+// trả về số câu đã cập nhật
+pub fn backfill_quantized_embeddings(db_path: &str, mode: QuantizationMode) -> Result<usize> {
+    let mut conn = Connection::open(db_path)?;
+    crate::database::createdb::ensure_schema(&conn)?;
+
+    let pending: Vec<(i64, String, String)> = {
+        let mut stmt = conn.prepare(
+            "SELECT rowid, CAST(question_embedding AS JSON), CAST(answer_embedding AS JSON) FROM data WHERE code_mode IS DISTINCT FROM ?",
+        )?;
+        let rows = stmt.query_map([mode.name()], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.filter_map(Result::ok).collect()
+    };
+
+    let tx = conn.transaction()?;
+    let mut updated = 0;
+    for (rowid, q_json, a_json) in pending {
+        // Bỏ qua dòng hỏng giống query_db
+        let (question, answer) = match (
+            serde_json::from_str::<Vec<f32>>(&q_json),
+            serde_json::from_str::<Vec<f32>>(&a_json),
+        ) {
+            (Ok(question), Ok(answer)) => (question, answer),
+            _ => continue,
+        };
+        let (code_mode, question_code, answer_code) = quantized_codes(&question, &answer, mode);
+        tx.execute(
+            "UPDATE data SET code_mode = ?, question_code = ?, answer_code = ? WHERE rowid = ?",
+            params![code_mode, question_code, answer_code, rowid],
+        )?;
+        updated += 1;
+    }
+    tx.commit()?;
+
+    Ok(updated)
+}
+
+// Embedding mới của một dòng trong ngân hàng; None là giữ nguyên embedding đang lưu của phần đó
+pub struct ChunkedEmbedding {
+    pub rowid: i64,
+    pub question: Option<Vec<f32>>,
+    pub answer: Option<Vec<f32>>,
+}
+
+// Đánh dấu các dòng `rowids` đã được tạo embedding theo cách chia đoạn `chunking`. Dòng có trong `embeddings`
+// được ghi đè embedding, bản lượng tử hóa được tạo lại theo code_mode đang lưu của dòng đó
+pub fn update_chunked_embeddings(
+    db_path: &str,
+    rowids: &[i64],
+    embeddings: &[ChunkedEmbedding],
+    chunking: &str,
+) -> Result<()> {
+    let mut conn = Connection::open(db_path)?;
+    crate::database::createdb::ensure_schema(&conn)?;
+    let tx = conn.transaction()?;
+
+    for update in embeddings {
+        for (column, embedding) in [("question_embedding", &update.question), ("answer_embedding", &update.answer)] {
+            if let Some(embedding) = embedding {
+                let query = format!("UPDATE data SET {} = array{:?}::REAL[] WHERE rowid = ?", column, embedding);
+                tx.execute(&query, [update.rowid])?;
+            }
+        }
+
+        let (code_mode, q_json, a_json): (Option<String>, String, String) = tx.query_row(
+            "SELECT code_mode, CAST(question_embedding AS JSON), CAST(answer_embedding AS JSON) FROM data WHERE rowid = ?",
+            [update.rowid],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        // Dòng chưa có bản lượng tử hóa thì không có gì để tạo lại
+        let Some(code_mode) = code_mode else {
+            continue;
+        };
+        let Some(mode) = QuantizationMode::parse(&code_mode) else {
+            println!("Bỏ qua bản lượng tử hóa của dòng {}: code_mode '{}' không được hỗ trợ", update.rowid, code_mode);
+            continue;
+        };
+        if let (Ok(question), Ok(answer)) = (
+            serde_json::from_str::<Vec<f32>>(&q_json),
+            serde_json::from_str::<Vec<f32>>(&a_json),
+        ) {
+            let (_, question_code, answer_code) = quantized_codes(&question, &answer, mode);
+            tx.execute(
+                "UPDATE data SET question_code = ?, answer_code = ? WHERE rowid = ?",
+                params![question_code, answer_code, update.rowid],
+            )?;
+        }
+    }
+
+    for rowid in rowids {
+        tx.execute("UPDATE data SET chunking = ? WHERE rowid = ?", params![chunking, rowid])?;
+    }
+    tx.commit()
+}
+
 // fn simple_random() -> f32 {
 //     let now = SystemTime::now()
 //         .duration_since(UNIX_EPOCH)
@@ -2,3 +2,4 @@ pub mod createdb;
 pub mod insertdb;
 pub mod showdb;
 pub mod deletedb;
+pub mod backupdb;
@@ -0,0 +1,743 @@
+use crate::functions::contrast_tokens::{apply_contrast, compare_features, ContrastFeatures, ContrastFinding};
+use crate::functions::cosine_similarity::{NormalizedMatrix, BLOCK_ROWS};
+use crate::database::insertdb::{update_chunked_embeddings, ChunkedEmbedding};
+use crate::functions::embedding_cache::{embed_texts, normalize_text};
+use crate::functions::embedding_model::get_model;
+use crate::functions::lexical_similarity::{estimate_jaccard, MinHashSignature};
+use crate::functions::long_text::exceeds_window;
+use crate::functions::plot_similarity::{calculate_similarity_score, ScoringPolicy};
+use crate::functions::quantization::{QuantizationMode, QuantizedMatrix};
+use crate::service::progress::JobContext;
+use crate::service::querydb::{query_bank_codes, query_bank_rows, query_chunking_pending, query_db, BankCode, BankQuestion};
+use crate::service::settings::Settings;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::time::Instant;
+
+// Một câu hỏi gần nhất trong ngân hàng, kèm các điểm thành phần để người duyệt tự đánh giá
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BankMatch {
+    pub question_id: String,
+    pub question_text: String,
+    pub answer_text: String,
+    pub stem_similarity: f32,
+    pub answer_similarity: f32,
+    // Điểm Jaccard (phần dẫn, đáp án) đã trộn vào combined_score, None với bản ghi cũ không lưu nội dung
+    pub lexical_similarity: Option<(f32, f32)>,
+    pub combined_score: f32,
+    pub is_similar: bool,
+    // Khác biệt phủ định, số liệu, token mã đã làm giảm combined_score
+    pub contrast: Option<ContrastFinding>,
+}
+
+// Embedding các câu trong file (phần dẫn, đáp án) theo một model có trong ngân hàng
+#[derive(Clone)]
+pub struct FileEmbeddings {
+    model: String,
+    stems: NormalizedMatrix,
+    answers: NormalizedMatrix,
+}
+
+impl FileEmbeddings {
+    pub fn new<'v>(
+        model: &str,
+        stems: impl IntoIterator<Item = &'v [f32]>,
+        answers: impl IntoIterator<Item = &'v [f32]>,
+    ) -> Self {
+        FileEmbeddings {
+            model: model.to_string(),
+            stems: NormalizedMatrix::from_vectors(stems),
+            answers: NormalizedMatrix::from_vectors(answers),
+        }
+    }
+}
+
+// Các dòng ngân hàng đọc cho một lượt kiểm tra
+pub enum BankRows {
+    // Quét chính xác: mọi dòng kèm vector f32
+    Full(Vec<BankQuestion>),
+    // Lọc nhanh: chỉ bản lượng tử hóa, vector f32 và nội dung được đọc sau cho các ứng viên
+    Quantized {
+        db_path: String,
+        mode: QuantizationMode,
+        codes: Vec<BankCode>,
+    },
+}
+
+impl BankRows {
+    // Đọc theo mục "Quantization" của `policy`
+    pub fn load(db_path: &str, policy: &ScoringPolicy) -> duckdb::Result<Self> {
+        match policy.quantization.mode {
+            Some(mode) => Ok(BankRows::Quantized {
+                db_path: db_path.to_string(),
+                mode,
+                codes: query_bank_codes(db_path, mode)?,
+            }),
+            None => Ok(BankRows::Full(query_db(db_path)?)),
+        }
+    }
+
+    // Số câu trong ngân hàng
+    pub fn len(&self) -> usize {
+        match self {
+            BankRows::Full(rows) => rows.len(),
+            BankRows::Quantized { codes, .. } => codes.len(),
+        }
+    }
+
+    // Các model có trong ngân hàng, không trùng lặp
+    fn models(&self) -> Vec<&str> {
+        let mut models: Vec<&str> = match self {
+            BankRows::Full(rows) => rows.iter().map(|item| item.model.as_str()).collect(),
+            BankRows::Quantized { codes, .. } => codes.iter().map(|code| code.model.as_str()).collect(),
+        };
+        models.sort_unstable();
+        models.dedup();
+        models
+    }
+}
+
+// Embed lại các câu trong ngân hàng lưu với cách chia đoạn khác cấu hình hiện tại (dữ liệu cũ embed nguyên văn
+// nên phần dẫn dài bị model cắt bớt), để vector ngân hàng so được với vector của file cùng model. Chỉ phần vượt
+// cửa sổ token mới được embed lại; model không load được thì giữ nguyên các dòng của model đó để lần sau thử lại.
+// Trả về số câu đã embed lại
+pub fn refresh_chunked_bank_rows(settings: &Settings, job: &JobContext) -> Result<usize, String> {
+    let db_path = &settings.paths.database;
+    let chunking = &settings.long_text;
+    let signature = chunking.signature();
+    let pending = query_chunking_pending(db_path, &signature)
+        .map_err(|e| format!("Lỗi khi truy vấn database: {}", e))?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let mut by_model: BTreeMap<&str, Vec<&(i64, String, String, String)>> = BTreeMap::new();
+    for row in &pending {
+        by_model.entry(row.1.as_str()).or_default().push(row);
+    }
+
+    let mut checked = Vec::new();
+    let mut updates = Vec::new();
+    for (name, rows) in by_model {
+        job.check_cancelled()?;
+        let model = match get_model(name, &settings.paths.model_cache) {
+            Ok(model) => model,
+            Err(e) => {
+                println!("Chưa embed lại được các câu dài trong ngân hàng tạo bằng model {}: {}", name, e);
+                continue;
+            }
+        };
+
+        let too_long = |text: &str| !text.is_empty() && exceeds_window(&model, &normalize_text(text), chunking);
+        let long: Vec<(i64, Option<&str>, Option<&str>)> = rows
+            .iter()
+            .map(|(rowid, _, question, answer)| {
+                (
+                    *rowid,
+                    Some(question.as_str()).filter(|text| too_long(text)),
+                    Some(answer.as_str()).filter(|text| too_long(text)),
+                )
+            })
+            .filter(|(_, question, answer)| question.is_some() || answer.is_some())
+            .collect();
+
+        let texts: Vec<&str> = long.iter().flat_map(|(_, question, answer)| question.iter().chain(answer.iter()).copied()).collect();
+        let mut embedded = embed_texts(&model, &texts, settings, job)?.into_iter();
+        for (rowid, question, answer) in long {
+            updates.push(ChunkedEmbedding {
+                rowid,
+                question: question.and_then(|_| embedded.next()),
+                answer: answer.and_then(|_| embedded.next()),
+            });
+        }
+        checked.extend(rows.iter().map(|row| row.0));
+    }
+
+    update_chunked_embeddings(db_path, &checked, &updates, &signature)
+        .map_err(|e| format!("Lỗi khi cập nhật embedding trong database: {}", e))?;
+    Ok(updates.len())
+}
+
+// Embed lại các câu trong file (`texts` là phần dẫn và đáp án) bằng từng model khác `file_model` có trong
+// ngân hàng, qua cache embedding. Model không load được thì bỏ qua phần ngân hàng của model đó
+pub fn embed_for_bank_models(
+    bank: &BankRows,
+    file_model: &str,
+    texts: &[(&str, &str)],
+    settings: &Settings,
+    job: &JobContext,
+) -> Result<Vec<FileEmbeddings>, String> {
+    let mut embeddings = Vec::new();
+    for name in bank.models().into_iter().filter(|model| *model != file_model) {
+        job.check_cancelled()?;
+        let model = match get_model(name, &settings.paths.model_cache) {
+            Ok(model) => model,
+            Err(e) => {
+                println!("Bỏ qua các câu trong ngân hàng tạo bằng model {}: {}", name, e);
+                continue;
+            }
+        };
+
+        let inputs: Vec<&str> = texts
+            .iter()
+            .flat_map(|(stem, answer)| [*stem, *answer])
+            .filter(|text| !text.is_empty())
+            .collect();
+        let mut embedded = embed_texts(&model, &inputs, settings, job)?.into_iter();
+        let mut next = |text: &str| if text.is_empty() { Vec::new() } else { embedded.next().unwrap_or_default() };
+        let (stems, answers): (Vec<Vec<f32>>, Vec<Vec<f32>>) = texts
+            .iter()
+            .map(|(stem, answer)| {
+                let stem = next(stem);
+                (stem, next(answer))
+            })
+            .unzip();
+
+        embeddings.push(FileEmbeddings::new(
+            name,
+            stems.iter().map(|v| v.as_slice()),
+            answers.iter().map(|v| v.as_slice()),
+        ));
+    }
+    Ok(embeddings)
+}
+
+// Các dòng ngân hàng đã đọc vector f32, chuẩn hóa embedding và tách đặc trưng khác biệt một lần cho cả lượt
+// kiểm tra: cả ngân hàng khi quét chính xác, chỉ các ứng viên của lượt lọc nhanh khi bật lượng tử hóa
+pub struct BankIndex {
+    pub bank: Vec<BankQuestion>,
+    // Mỗi model trong ngân hàng một phần, chỉ so với embedding của file theo cùng model
+    parts: Vec<BankPart>,
+    files: Vec<FileEmbeddings>,
+    // Số câu hỏi trong file
+    file_len: usize,
+    pub features: Vec<ContrastFeatures>,
+    // Đặc trưng chỉ của phần dẫn, dùng khi kiểm tra mâu thuẫn đáp án
+    pub stem_features: Vec<ContrastFeatures>,
+    // Chữ ký MinHash (phần dẫn, đáp án) để trộn điểm từ vựng, None khi dòng không lưu nội dung
+    signatures: Vec<Option<(MinHashSignature, MinHashSignature)>>,
+    // Vị trí trong `bank` của các ứng viên của từng câu trong file; None khi quét chính xác
+    shortlists: Option<Vec<Vec<usize>>>,
+    // Nơi đọc lại ngân hàng khi đo recall của lượt lọc nhanh
+    quantization: Option<(String, QuantizationMode, usize)>,
+}
+
+// Các dòng liền nhau của `bank` được embed bằng cùng một model, kèm vị trí embedding của file theo model đó
+struct BankPart {
+    rows: Range<usize>,
+    questions: NormalizedMatrix,
+    answers: NormalizedMatrix,
+    file: usize,
+}
+
+// Bản lượng tử hóa của các dòng cùng một model và của file theo model đó, cho lượt lọc nhanh
+struct QuantizedPart<'c> {
+    codes: &'c [BankCode],
+    questions: QuantizedMatrix,
+    answers: QuantizedMatrix,
+    file_stems: QuantizedMatrix,
+    file_answers: QuantizedMatrix,
+}
+
+// Khoảng các dòng liền nhau cùng model, kèm vị trí embedding của file theo model đó (nếu có)
+fn model_runs<'m>(models: impl Iterator<Item = &'m str>, files: &[FileEmbeddings]) -> Vec<(Range<usize>, usize)> {
+    let models: Vec<&str> = models.collect();
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < models.len() {
+        let model = models[start];
+        let end = models[start..]
+            .iter()
+            .position(|other| *other != model)
+            .map_or(models.len(), |offset| start + offset);
+        if let Some(file) = files.iter().position(|embeddings| embeddings.model == model) {
+            runs.push((start..end, file));
+        }
+        start = end;
+    }
+    runs
+}
+
+// Xếp hạng toàn bộ ngân hàng bằng điểm xấp xỉ và giữ `shortlist` ứng viên đầu cho mỗi câu trong file.
+// Xếp theo điểm cao hơn giữa phần dẫn và đáp án để giữ được cả câu gần nhất (điểm tổng hợp)
+// lẫn câu trùng phần dẫn khác đáp án. Trả về rowid của các ứng viên
+fn shortlist_rowids(
+    codes: &[BankCode],
+    files: &[FileEmbeddings],
+    mode: QuantizationMode,
+    shortlist: usize,
+    file_len: usize,
+) -> Vec<Vec<i64>> {
+    let parts: Vec<QuantizedPart> = model_runs(codes.iter().map(|code| code.model.as_str()), files)
+        .into_iter()
+        .map(|(rows, file)| {
+            let (codes, file) = (&codes[rows], &files[file]);
+            let dimension = file.stems.dimension();
+            QuantizedPart {
+                codes,
+                questions: QuantizedMatrix::from_codes(codes.iter().map(|code| &code.question_code), mode, dimension),
+                answers: QuantizedMatrix::from_codes(codes.iter().map(|code| &code.answer_code), mode, dimension),
+                file_stems: QuantizedMatrix::from_matrix(&file.stems, mode),
+                file_answers: QuantizedMatrix::from_matrix(&file.answers, mode),
+            }
+        })
+        .collect();
+
+    (0..file_len)
+        .step_by(BLOCK_ROWS)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .flat_map_iter(|start| {
+            let rows = start..(start + BLOCK_ROWS).min(file_len);
+            let mut approximate: Vec<Vec<(i64, f32)>> = vec![Vec::new(); rows.len()];
+            for part in &parts {
+                let width = part.codes.len();
+                let stems = part.file_stems.block_similarities(rows.clone(), &part.questions);
+                let answers = part.file_answers.block_similarities(rows.clone(), &part.answers);
+                for (offset, row_scores) in approximate.iter_mut().enumerate() {
+                    row_scores.extend(part.codes.iter().enumerate().map(|(column, code)| {
+                        let cell = offset * width + column;
+                        (code.rowid, stems[cell].max(answers[cell]))
+                    }));
+                }
+            }
+
+            approximate.into_iter().map(|mut candidates| {
+                if candidates.len() > shortlist {
+                    candidates.select_nth_unstable_by(shortlist - 1, |a, b| b.1.total_cmp(&a.1));
+                    candidates.truncate(shortlist);
+                }
+                candidates.into_iter().map(|(rowid, _)| rowid).collect::<Vec<_>>()
+            })
+        })
+        .collect()
+}
+
+impl BankIndex {
+    // `file` là embedding của file theo từng model (phần tử đầu là model của file); dòng của model không có
+    // trong `file` luôn có điểm 0. Khi lọc nhanh, chỉ vector f32 của các ứng viên được đọc từ database
+    pub fn new(bank: BankRows, file: Vec<FileEmbeddings>, policy: &ScoringPolicy) -> Result<Self, String> {
+        let file_len = file.first().map_or(0, |embeddings| embeddings.stems.len());
+
+        let (rows, shortlists, quantization) = match bank {
+            BankRows::Full(rows) => (rows, None, None),
+            BankRows::Quantized { db_path, mode, codes } => {
+                let shortlist = policy.quantization.shortlist.max(1);
+                let rowids = shortlist_rowids(&codes, &file, mode, shortlist, file_len);
+                let mut wanted: Vec<i64> = rowids.iter().flatten().copied().collect();
+                wanted.sort_unstable();
+                wanted.dedup();
+
+                let rows = query_bank_rows(&db_path, &wanted)
+                    .map_err(|e| format!("Lỗi khi đọc ứng viên trong database: {}", e))?;
+                let position: HashMap<i64, usize> = rows.iter().enumerate().map(|(i, item)| (item.rowid, i)).collect();
+                let shortlists = rowids
+                    .iter()
+                    .map(|ids| ids.iter().filter_map(|rowid| position.get(rowid).copied()).collect())
+                    .collect();
+                (rows, Some(shortlists), Some((db_path, mode, shortlist)))
+            }
+        };
+
+        let parts = model_runs(rows.iter().map(|item| item.model.as_str()), &file)
+            .into_iter()
+            .map(|(range, file)| {
+                let items = &rows[range.clone()];
+                BankPart {
+                    questions: NormalizedMatrix::from_vectors(items.iter().map(|item| item.question_embedding.as_slice())),
+                    answers: NormalizedMatrix::from_vectors(items.iter().map(|item| item.answer_embedding.as_slice())),
+                    rows: range,
+                    file,
+                }
+            })
+            .collect();
+
+        Ok(BankIndex {
+            features: rows
+                .par_iter()
+                .map(|item| ContrastFeatures::new(&item.question_text, &item.answer_text))
+                .collect(),
+            stem_features: rows
+                .par_iter()
+                .map(|item| ContrastFeatures::new(&item.question_text, ""))
+                .collect(),
+            signatures: rows
+                .par_iter()
+                .map(|item| {
+                    (!item.question_text.trim().is_empty()).then(|| {
+                        (policy.hybrid.signature(&item.question_text), policy.hybrid.signature(&item.answer_text))
+                    })
+                })
+                .collect(),
+            bank: rows,
+            parts,
+            files: file,
+            file_len,
+            shortlists,
+            quantization,
+        })
+    }
+
+    // Điểm Jaccard (phần dẫn, đáp án) giữa một câu trong file (chữ ký `signature`) và dòng `position`
+    pub fn lexical(&self, position: usize, signature: &(MinHashSignature, MinHashSignature)) -> Option<(f32, f32)> {
+        self.signatures[position]
+            .as_ref()
+            .map(|(stem, answer)| (estimate_jaccard(&signature.0, stem), estimate_jaccard(&signature.1, answer)))
+    }
+
+    // Điểm cosine (phần dẫn, đáp án) giữa câu `row` trong file và dòng `position` của ngân hàng
+    fn similarity(&self, row: usize, position: usize) -> Option<(f32, f32)> {
+        let part = self.parts.iter().find(|part| part.rows.contains(&position))?;
+        let file = &self.files[part.file];
+        let column = position - part.rows.start;
+        Some((
+            file.stems.similarity(row, &part.questions, column),
+            file.answers.similarity(row, &part.answers, column),
+        ))
+    }
+}
+
+// Điểm cosine (vị trí trong ngân hàng, phần dẫn, đáp án) của một câu hỏi trong file với các dòng đã chấm:
+// mọi dòng có embedding cùng model khi quét chính xác, chỉ các ứng viên khi lọc nhanh
+pub type BankScores = [(usize, f32, f32)];
+
+// Chia `count` câu hỏi thành từng khối chấm điểm song song; `score_block` trả về điểm của từng câu trong khối,
+// `visit` nhận vị trí câu hỏi và điểm của câu đó, kết quả giữ đúng thứ tự câu hỏi
+fn scan_blocks<T, F, S>(count: usize, score_block: S, visit: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &BankScores) -> T + Sync,
+    S: Fn(Range<usize>) -> Vec<Vec<(usize, f32, f32)>> + Sync,
+{
+    (0..count)
+        .step_by(BLOCK_ROWS)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .flat_map_iter(|start| {
+            let rows = start..(start + BLOCK_ROWS).min(count);
+            score_block(rows.clone())
+                .into_iter()
+                .zip(rows)
+                .map(|(scores, row)| visit(row, &scores))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn exact_block(index: &BankIndex, rows: Range<usize>) -> Vec<Vec<(usize, f32, f32)>> {
+    let covered: usize = index.parts.iter().map(|part| part.rows.len()).sum();
+    let mut scores = vec![Vec::with_capacity(covered); rows.len()];
+    for part in &index.parts {
+        let file = &index.files[part.file];
+        let width = part.rows.len();
+        let stems = file.stems.block_similarities(rows.clone(), &part.questions);
+        let answers = file.answers.block_similarities(rows.clone(), &part.answers);
+        for (offset, row_scores) in scores.iter_mut().enumerate() {
+            row_scores.extend((0..width).map(|column| {
+                let cell = offset * width + column;
+                (part.rows.start + column, stems[cell], answers[cell])
+            }));
+        }
+    }
+    scores
+}
+
+// Chấm lại bằng vector f32 các ứng viên của lượt lọc nhanh
+fn shortlist_block(index: &BankIndex, shortlists: &[Vec<usize>], rows: Range<usize>) -> Vec<Vec<(usize, f32, f32)>> {
+    rows.map(|row| {
+        shortlists[row]
+            .iter()
+            .filter_map(|&position| {
+                let (stem, answer) = index.similarity(row, position)?;
+                Some((position, stem, answer))
+            })
+            .collect()
+    })
+    .collect()
+}
+
+// Chấm điểm các câu hỏi trong file với ngân hàng theo từng khối câu hỏi song song: toàn bộ ngân hàng khi quét
+// chính xác, chỉ các ứng viên khi lọc nhanh; `visit` nhận vị trí câu hỏi và điểm của câu đó, kết quả giữ đúng
+// thứ tự câu hỏi
+pub fn scan_bank<T, F>(index: &BankIndex, visit: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &BankScores) -> T + Sync,
+{
+    match &index.shortlists {
+        Some(shortlists) => scan_blocks(index.file_len, |rows| shortlist_block(index, shortlists, rows), visit),
+        None => scan_blocks(index.file_len, |rows| exact_block(index, rows), visit),
+    }
+}
+
+// Tỉ lệ câu gần nhất (theo điểm tổng hợp của embedding, chưa trộn điểm từ vựng và trừ khác biệt) của lượt quét
+// chính xác vẫn nằm trong kết quả của lượt lọc nhanh, kèm thời gian đọc database và chấm điểm của hai lượt
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RecallReport {
+    pub mode: QuantizationMode,
+    pub shortlist: usize,
+    pub k: usize,
+    pub recall: f32,
+    pub exact_millis: u64,
+    pub quantized_millis: u64,
+}
+
+// rowid của `k` câu có điểm tổng hợp cao nhất
+fn top_k_rowids(index: &BankIndex, scores: &BankScores, k: usize, policy: &ScoringPolicy) -> Vec<i64> {
+    let mut ranked: Vec<(usize, f32)> = scores
+        .iter()
+        .map(|&(position, stem, answer)| {
+            let score = calculate_similarity_score(stem, answer, None, None, policy.thresholds.database, policy);
+            (position, score)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.into_iter().take(k).map(|(position, _)| index.bank[position].rowid).collect()
+}
+
+// Chạy lại cả hai lượt từ đầu (đọc database, lọc nhanh hoặc quét chính xác) để đo đúng thời gian của từng lượt;
+// trả về None khi không bật lượng tử hóa
+pub fn measure_recall(index: &BankIndex, policy: &ScoringPolicy) -> Result<Option<RecallReport>, String> {
+    let (db_path, mode, shortlist) = match &index.quantization {
+        Some((db_path, mode, shortlist)) => (db_path, *mode, *shortlist),
+        None => return Ok(None),
+    };
+    let k = policy.bank_top_k;
+    let query_error = |e: duckdb::Error| format!("Lỗi khi truy vấn database: {}", e);
+
+    let started = Instant::now();
+    let exact_index = BankIndex::new(BankRows::Full(query_db(db_path).map_err(query_error)?), index.files.clone(), policy)?;
+    let exact = scan_bank(&exact_index, |_, scores| top_k_rowids(&exact_index, scores, k, policy));
+    let exact_millis = started.elapsed().as_millis() as u64;
+
+    let started = Instant::now();
+    let codes = query_bank_codes(db_path, mode).map_err(query_error)?;
+    let quantized_index = BankIndex::new(
+        BankRows::Quantized { db_path: db_path.clone(), mode, codes },
+        index.files.clone(),
+        policy,
+    )?;
+    let approximate = scan_bank(&quantized_index, |_, scores| top_k_rowids(&quantized_index, scores, k, policy));
+    let quantized_millis = started.elapsed().as_millis() as u64;
+
+    let (found, total) = exact.iter().zip(&approximate).fold((0, 0), |(found, total), (exact, approximate)| {
+        (
+            found + exact.iter().filter(|rowid| approximate.contains(rowid)).count(),
+            total + exact.len(),
+        )
+    });
+
+    Ok(Some(RecallReport {
+        mode,
+        shortlist,
+        k,
+        recall: if total == 0 { 1.0 } else { found as f32 / total as f32 },
+        exact_millis,
+        quantized_millis,
+    }))
+}
+
+// Một dòng ngân hàng khi xếp hạng: vị trí, cosine phần dẫn và đáp án, điểm Jaccard, điểm tổng hợp
+type Candidate = (usize, f32, f32, Option<(f32, f32)>, f32);
+
+// `k` câu hỏi trong ngân hàng có điểm tổng hợp cao nhất, sắp xếp giảm dần. `features` và `signature` là
+// đặc trưng khác biệt và chữ ký MinHash (phần dẫn, đáp án) của câu trong file, dùng để so với bản ghi có lưu nội dung
+pub fn nearest_bank_matches(
+    features: &ContrastFeatures,
+    signature: &(MinHashSignature, MinHashSignature),
+    scores: &BankScores,
+    index: &BankIndex,
+    k: usize,
+    policy: &ScoringPolicy,
+) -> Vec<BankMatch> {
+    let mut candidates: Vec<Candidate> = scores
+        .iter()
+        .map(|&(position, stem, answer)| {
+            let lexical = index.lexical(position, signature);
+            let score = calculate_similarity_score(stem, answer, None, lexical, policy.thresholds.database, policy);
+            (position, stem, answer, lexical, score)
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.4.total_cmp(&a.4));
+
+    // Khác biệt chỉ làm giảm điểm, nên chỉ cần so khác biệt cho tới khi điểm gốc
+    // không còn vượt được câu thứ k hiện tại
+    let mut scored: Vec<(Candidate, Option<ContrastFinding>)> = Vec::with_capacity(k + 1);
+    for (position, stem, answer, lexical, raw_score) in candidates {
+        if scored.len() >= k && scored.last().is_some_and(|(last, _)| raw_score <= last.4) {
+            break;
+        }
+        let contrast = compare_features(features, &index.features[position], &policy.contrast);
+        let combined = apply_contrast(raw_score, contrast.as_ref());
+        scored.push(((position, stem, answer, lexical, combined), contrast));
+        scored.sort_by(|a, b| b.0.4.total_cmp(&a.0.4));
+        scored.truncate(k);
+    }
+
+    scored
+        .into_iter()
+        .map(|((position, stem, answer, lexical, combined), contrast)| {
+            let item = &index.bank[position];
+            BankMatch {
+                question_id: item.question_id.clone(),
+                question_text: item.question_text.clone(),
+                answer_text: item.answer_text.clone(),
+                stem_similarity: stem,
+                answer_similarity: answer,
+                lexical_similarity: lexical,
+                combined_score: combined,
+                is_similar: combined > policy.thresholds.database,
+                contrast,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::embedder::{Embedder, HashingEmbedder};
+
+    const DIMENSION: usize = 256;
+
+    fn policy() -> ScoringPolicy {
+        ScoringPolicy::new(&Settings::default(), None)
+    }
+
+    fn embed(embedder: &HashingEmbedder, text: &str) -> Vec<f32> {
+        embedder.embed(&[text]).unwrap().remove(0)
+    }
+
+    fn bank_row(rowid: i64, model: &str, embedder: &HashingEmbedder, question: &str, answer: &str) -> BankQuestion {
+        BankQuestion {
+            rowid,
+            model: model.to_string(),
+            question_id: format!("QN{}", rowid),
+            question_text: question.to_string(),
+            answer_text: answer.to_string(),
+            question_embedding: embed(embedder, question),
+            answer_embedding: embed(embedder, answer),
+        }
+    }
+
+    fn file_embeddings(model: &str, embedder: &HashingEmbedder, questions: &[(&str, &str)]) -> FileEmbeddings {
+        let stems: Vec<Vec<f32>> = questions.iter().map(|(stem, _)| embed(embedder, stem)).collect();
+        let answers: Vec<Vec<f32>> = questions.iter().map(|(_, answer)| embed(embedder, answer)).collect();
+        FileEmbeddings::new(model, stems.iter().map(|v| v.as_slice()), answers.iter().map(|v| v.as_slice()))
+    }
+
+    fn matches_for(index: &BankIndex, question: (&str, &str), k: usize, policy: &ScoringPolicy) -> Vec<Vec<BankMatch>> {
+        let features = ContrastFeatures::new(question.0, question.1);
+        let signature = (policy.hybrid.signature(question.0), policy.hybrid.signature(question.1));
+        scan_bank(index, |_, scores| nearest_bank_matches(&features, &signature, scores, index, k, policy))
+    }
+
+    #[test]
+    fn nearest_matches_are_sorted_and_limited_to_k() {
+        let policy = policy();
+        let embedder = HashingEmbedder::new(DIMENSION);
+        let question = ("Thủ đô của Việt Nam là thành phố nào?", "Hà Nội");
+        let rows = vec![
+            bank_row(1, "hashing", &embedder, "Quá trình quang hợp diễn ra ở đâu?", "Lục lạp"),
+            bank_row(2, "hashing", &embedder, "Thủ đô của Việt Nam là thành phố nào?", "Hà Nội"),
+            bank_row(3, "hashing", &embedder, "Thủ đô của nước Việt Nam là gì?", "Hà Nội"),
+            bank_row(4, "hashing", &embedder, "Đơn vị đo điện trở là gì?", "Ohm"),
+        ];
+        let file = file_embeddings("hashing", &embedder, &[question]);
+        let index = BankIndex::new(BankRows::Full(rows), vec![file], &policy).unwrap();
+
+        let matches = matches_for(&index, question, 2, &policy).remove(0);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].question_id, "QN2");
+        assert_eq!(matches[1].question_id, "QN3");
+        assert!(matches[0].combined_score >= matches[1].combined_score);
+        assert!(matches[0].is_similar);
+        assert_eq!(matches_for(&index, question, 10, &policy)[0].len(), 4);
+    }
+
+    #[test]
+    fn contrast_penalty_is_applied_before_the_top_k_cut() {
+        let policy = policy();
+        let embedder = HashingEmbedder::new(DIMENSION);
+        let question = ("Số nào là số nguyên tố?", "7");
+        let rows = vec![
+            // Điểm gốc cao nhất nhưng đảo phủ định nên bị giảm mạnh
+            bank_row(1, "hashing", &embedder, "Số nào KHÔNG là số nguyên tố?", "7"),
+            bank_row(2, "hashing", &embedder, "Trong các số sau, số nào là số nguyên tố?", "7"),
+            bank_row(3, "hashing", &embedder, "Đơn vị đo điện trở là gì?", "Ohm"),
+        ];
+        let file = file_embeddings("hashing", &embedder, &[question]);
+        let index = BankIndex::new(BankRows::Full(rows), vec![file], &policy).unwrap();
+
+        // So với cách làm không dừng sớm: chấm khác biệt cho mọi dòng rồi mới lấy k câu đầu
+        let features = ContrastFeatures::new(question.0, question.1);
+        let signature = (policy.hybrid.signature(question.0), policy.hybrid.signature(question.1));
+        let mut expected: Vec<(i64, f32, f32)> = scan_bank(&index, |_, scores| {
+            scores
+                .iter()
+                .map(|&(position, stem, answer)| {
+                    let lexical = index.lexical(position, &signature);
+                    let raw = calculate_similarity_score(stem, answer, None, lexical, policy.thresholds.database, &policy);
+                    let contrast = compare_features(&features, &index.features[position], &policy.contrast);
+                    (index.bank[position].rowid, raw, apply_contrast(raw, contrast.as_ref()))
+                })
+                .collect::<Vec<_>>()
+        })
+        .remove(0);
+        expected.sort_by(|a, b| b.1.total_cmp(&a.1));
+        assert_eq!(expected[0].0, 1);
+        expected.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        for k in 1..=3 {
+            let matches = matches_for(&index, question, k, &policy).remove(0);
+            let ids: Vec<String> = matches.iter().map(|m| m.question_id.clone()).collect();
+            let expected_ids: Vec<String> = expected.iter().take(k).map(|(rowid, _, _)| format!("QN{}", rowid)).collect();
+            assert_eq!(ids, expected_ids);
+        }
+
+        let best = matches_for(&index, question, 1, &policy).remove(0);
+        assert_eq!(best[0].question_id, "QN2");
+        let all = matches_for(&index, question, 3, &policy).remove(0);
+        assert!(all.iter().any(|m| m.question_id == "QN1" && m.contrast.as_ref().is_some_and(|c| c.negation_flip)));
+    }
+
+    #[test]
+    fn bank_rows_are_only_scored_against_their_own_model() {
+        let policy = policy();
+        let hashing = HashingEmbedder::new(DIMENSION);
+        let other = HashingEmbedder::new(64);
+        let questions = [("Thủ đô của Pháp là gì?", "Paris"), ("Đơn vị đo điện trở là gì?", "Ohm")];
+        let rows = vec![
+            bank_row(1, "hashing", &hashing, "Thủ đô của Pháp là gì?", "Paris"),
+            bank_row(2, "hashing", &hashing, "Đơn vị đo điện trở là gì?", "Ohm"),
+            bank_row(3, "other", &other, "Đơn vị đo điện trở là gì?", "Ohm"),
+            bank_row(4, "unloaded", &other, "Thủ đô của Pháp là gì?", "Paris"),
+        ];
+        let files = vec![file_embeddings("hashing", &hashing, &questions), file_embeddings("other", &other, &questions)];
+        let index = BankIndex::new(BankRows::Full(rows), files, &policy).unwrap();
+
+        let scores = scan_bank(&index, |_, scores| scores.to_vec());
+        assert_eq!(scores.len(), 2);
+        for (row, row_scores) in scores.iter().enumerate() {
+            // Dòng của model không có embedding của file thì không được chấm
+            let positions: Vec<usize> = row_scores.iter().map(|score| score.0).collect();
+            assert_eq!(positions, vec![0, 1, 2]);
+            // Mỗi câu trùng nguyên văn với dòng cùng model có cosine 1 dù vector khác số chiều
+            let exact = if row == 0 { vec![0] } else { vec![1, 2] };
+            for &(position, stem, answer) in row_scores {
+                if exact.contains(&position) {
+                    assert!((stem - 1.0).abs() < 1e-5 && (answer - 1.0).abs() < 1e-5);
+                } else {
+                    assert!(stem < 0.9);
+                }
+            }
+        }
+
+        let matches = matches_for(&index, questions[1], 3, &policy);
+        let ids: Vec<&str> = matches[1].iter().take(2).map(|m| m.question_id.as_str()).collect();
+        assert!(ids.contains(&"QN2") && ids.contains(&"QN3"));
+        assert!(matches[1].iter().all(|m| m.question_id != "QN4"));
+    }
+}
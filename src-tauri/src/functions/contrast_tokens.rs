@@ -0,0 +1,400 @@
+use std::collections::BTreeSet;
+
+// Từ phủ định làm đảo nghĩa câu hỏi ("Which is NOT ...", "Phát biểu nào sau đây sai?")
+const NEGATION_WORDS: &[&str] = &[
+    "not", "no", "never", "none", "except", "false", "incorrect", "cannot", "isn't", "aren't",
+    "doesn't", "don't", "wrong", "không", "chưa", "chẳng", "sai", "trừ",
+];
+
+// Cụm có chứa từ phủ định nhưng không mang nghĩa phủ định
+const NEGATION_EXCEPTIONS: &[&str] = &["không gian", "không khí", "số không", "không đổi", "phép trừ"];
+
+// Toán tử thường gặp trong câu hỏi lập trình, xếp theo độ dài giảm dần để tách đúng "==" trước "="
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "++", "--", "<<", ">>", "->",
+    "=>", "+=", "-=", "*=", "/=", "%=", "::", "+", "-", "*", "/", "%", "<", ">", "=", "!", "&",
+    "|", "^", "~",
+];
+
+// Mức giảm điểm khi hai câu giống nhau về ngữ nghĩa nhưng khác ở chi tiết quyết định đáp án,
+// mục "ContrastPenalty" trong settings.json
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContrastPenalties {
+    #[serde(rename = "NegationPenalty")]
+    pub negation: f32,
+    #[serde(rename = "NumericPenalty")]
+    pub numeric: f32,
+    #[serde(rename = "CodeTokenPenalty")]
+    pub code_token: f32,
+}
+
+impl Default for ContrastPenalties {
+    fn default() -> Self {
+        ContrastPenalties {
+            negation: 0.35,
+            numeric: 0.25,
+            code_token: 0.2,
+        }
+    }
+}
+
+// Khác biệt giữa hai câu mà embedding thường bỏ qua; chỉ tạo ra khi có ít nhất một khác biệt
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ContrastFinding {
+    // Một câu hỏi ở dạng phủ định, câu còn lại thì không
+    pub negation_flip: bool,
+    // Số chỉ có ở câu này / câu còn lại
+    pub numbers: Vec<String>,
+    pub other_numbers: Vec<String>,
+    // Toán tử, định danh chỉ có ở câu này / câu còn lại
+    pub code_tokens: Vec<String>,
+    pub other_code_tokens: Vec<String>,
+    // Hệ số nhân vào điểm tương đồng, nằm trong [0, 1]
+    pub factor: f32,
+    pub notes: Vec<String>,
+}
+
+impl ContrastFinding {
+    pub fn apply(&self, score: f32) -> f32 {
+        score * self.factor
+    }
+
+    // Cùng kết quả nhìn từ phía câu còn lại
+    pub fn reversed(&self) -> ContrastFinding {
+        let mut reversed = ContrastFinding {
+            negation_flip: self.negation_flip,
+            numbers: self.other_numbers.clone(),
+            other_numbers: self.numbers.clone(),
+            code_tokens: self.other_code_tokens.clone(),
+            other_code_tokens: self.code_tokens.clone(),
+            factor: self.factor,
+            notes: Vec::new(),
+        };
+        reversed.notes = reversed.build_notes();
+        reversed
+    }
+
+    fn build_notes(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        if self.negation_flip {
+            notes.push("Một câu ở dạng phủ định, câu còn lại thì không".to_string());
+        }
+        if !self.numbers.is_empty() || !self.other_numbers.is_empty() {
+            notes.push(format!(
+                "Khác số liệu: {} / {}",
+                describe(&self.numbers),
+                describe(&self.other_numbers)
+            ));
+        }
+        if !self.code_tokens.is_empty() || !self.other_code_tokens.is_empty() {
+            notes.push(format!(
+                "Khác toán tử/định danh: {} / {}",
+                describe(&self.code_tokens),
+                describe(&self.other_code_tokens)
+            ));
+        }
+        notes
+    }
+}
+
+// Điểm sau khi giảm theo khác biệt (nếu có)
+pub fn apply_contrast(score: f32, finding: Option<&ContrastFinding>) -> f32 {
+    finding.map_or(score, |finding| finding.apply(score))
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '\''))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect()
+}
+
+fn is_negation_word(word: &str) -> bool {
+    NEGATION_WORDS.contains(&word) || word.ends_with("n't")
+}
+
+// Câu hỏi "có ... không?" (ví dụ "Hàm có trả về giá trị không?") hỏi có hay không chứ không phủ định:
+// "không" đứng cuối mệnh đề và trước đó có "có"
+fn is_yes_no_question(clause_words: &[String]) -> bool {
+    match clause_words.split_last() {
+        Some((last, rest)) => last == "không" && rest.iter().any(|word| word == "có"),
+        None => false,
+    }
+}
+
+// Số lần xuất hiện từ phủ định lẻ thì câu ở dạng phủ định ("không sai" là khẳng định)
+fn is_negated(text: &str) -> bool {
+    let mut lower = text.to_lowercase();
+    for exception in NEGATION_EXCEPTIONS {
+        lower = lower.replace(exception, " ");
+    }
+
+    let count: usize = lower
+        .split(['?', '.', '!', ';', ',', '\n'])
+        .map(|clause| {
+            let clause_words = words(clause);
+            let negations = clause_words.iter().filter(|word| is_negation_word(word)).count();
+            if is_yes_no_question(&clause_words) {
+                negations - 1
+            } else {
+                negations
+            }
+        })
+        .sum();
+    count % 2 == 1
+}
+
+// Số nguyên và số thập phân, dấu phẩy thập phân kiểu Việt Nam được chuẩn hóa thành dấu chấm
+fn numbers(text: &str) -> BTreeSet<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = BTreeSet::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        // Bỏ qua cả định danh chứa chữ số như "x12", "int32", "utf8"
+        if chars[i].is_alphabetic() || chars[i] == '_' {
+            while i < chars.len() && is_identifier_char(chars[i]) {
+                i += 1;
+            }
+            continue;
+        }
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+
+        let mut number = String::new();
+        while i < chars.len() {
+            let c = chars[i];
+            let separator = (c == '.' || c == ',')
+                && chars.get(i + 1).is_some_and(|next| next.is_ascii_digit());
+            if c.is_ascii_digit() {
+                number.push(c);
+            } else if separator {
+                number.push('.');
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        result.insert(number);
+    }
+
+    result
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Định danh mang dáng dấp mã nguồn: có dấu gạch dưới, viết kiểu camelCase hoặc được gọi như hàm
+fn looks_like_code(word: &str, followed_by_paren: bool) -> bool {
+    let has_inner_upper = word.chars().skip(1).any(|c| c.is_uppercase())
+        && word.chars().any(|c| c.is_lowercase());
+    word.contains('_') || has_inner_upper || followed_by_paren
+}
+
+fn code_tokens(text: &str) -> BTreeSet<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = BTreeSet::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if is_identifier_char(c) {
+            let start = i;
+            while i < chars.len() && is_identifier_char(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if looks_like_code(&word, chars.get(i) == Some(&'(')) {
+                result.insert(word);
+            }
+            continue;
+        }
+
+        let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+        match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            Some(op) => {
+                // Dấu gạch nối giữa hai chữ ("e-learning") không phải toán tử
+                let hyphen = *op == "-"
+                    && i > 0
+                    && chars[i - 1].is_alphabetic()
+                    && chars.get(i + 1).is_some_and(|next| next.is_alphabetic());
+                if !hyphen {
+                    result.insert(op.to_string());
+                }
+                i += op.chars().count();
+            }
+            None => i += 1,
+        }
+    }
+
+    result
+}
+
+fn difference(a: &BTreeSet<String>, b: &BTreeSet<String>) -> Vec<String> {
+    a.difference(b).cloned().collect()
+}
+
+// Đặc trưng phủ định, số liệu, token mã của một câu; tính một lần cho mỗi câu
+// để không phải tách từ lại khi so với hàng trăm câu khác
+#[derive(Debug, Clone)]
+pub struct ContrastFeatures {
+    empty: bool,
+    negated: bool,
+    numbers: BTreeSet<String>,
+    code_tokens: BTreeSet<String>,
+}
+
+impl ContrastFeatures {
+    // Phủ định chỉ xét trên phần dẫn vì đáp án như "None of the above" không làm đảo nghĩa
+    // câu hỏi; số và token mã xét trên cả phần dẫn lẫn đáp án
+    pub fn new(stem: &str, answer: &str) -> Self {
+        let text = format!("{} {}", stem, answer);
+        ContrastFeatures {
+            empty: text.trim().is_empty(),
+            negated: is_negated(stem),
+            numbers: numbers(&text),
+            code_tokens: code_tokens(&text),
+        }
+    }
+}
+
+// So phần dẫn và đáp án đúng của hai câu.
+// Trả về None khi một trong hai câu không có nội dung (ví dụ bản ghi database cũ chỉ có embedding)
+pub fn compare_contrast(
+    stem: &str,
+    answer: &str,
+    other_stem: &str,
+    other_answer: &str,
+    penalties: &ContrastPenalties,
+) -> Option<ContrastFinding> {
+    compare_features(
+        &ContrastFeatures::new(stem, answer),
+        &ContrastFeatures::new(other_stem, other_answer),
+        penalties,
+    )
+}
+
+pub fn compare_features(
+    features: &ContrastFeatures,
+    other: &ContrastFeatures,
+    penalties: &ContrastPenalties,
+) -> Option<ContrastFinding> {
+    if features.empty || other.empty {
+        return None;
+    }
+
+    let mut finding = ContrastFinding {
+        negation_flip: features.negated != other.negated,
+        numbers: difference(&features.numbers, &other.numbers),
+        other_numbers: difference(&other.numbers, &features.numbers),
+        code_tokens: difference(&features.code_tokens, &other.code_tokens),
+        other_code_tokens: difference(&other.code_tokens, &features.code_tokens),
+        factor: 1.0,
+        notes: Vec::new(),
+    };
+
+    if finding.negation_flip {
+        finding.factor *= 1.0 - penalties.negation;
+    }
+    if !finding.numbers.is_empty() || !finding.other_numbers.is_empty() {
+        finding.factor *= 1.0 - penalties.numeric;
+    }
+    if !finding.code_tokens.is_empty() || !finding.other_code_tokens.is_empty() {
+        finding.factor *= 1.0 - penalties.code_token;
+    }
+    finding.notes = finding.build_notes();
+
+    if finding.notes.is_empty() {
+        None
+    } else {
+        Some(finding)
+    }
+}
+
+fn describe(tokens: &[String]) -> String {
+    if tokens.is_empty() {
+        "(không có)".to_string()
+    } else {
+        tokens.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(values: &[&str]) -> BTreeSet<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn negation_counts_parity_and_skips_exceptions() {
+        assert!(is_negated("Which of the following is NOT a prime number?"));
+        assert!(is_negated("Phát biểu nào sau đây sai?"));
+        assert!(!is_negated("Phát biểu nào sau đây không sai?"));
+        assert!(!is_negated("Không khí gồm những thành phần nào?"));
+        assert!(is_negated("This statement isn't true"));
+    }
+
+    #[test]
+    fn vietnamese_yes_no_questions_are_not_negation() {
+        assert!(!is_negated("Hàm có trả về giá trị không?"));
+        assert!(is_negated("Hàm không trả về giá trị nào?"));
+    }
+
+    #[test]
+    fn numbers_normalize_decimal_comma_and_skip_identifiers() {
+        assert_eq!(numbers("Tính 3,5 + 2.25 rồi nhân 10"), set(&["10", "2.25", "3.5"]));
+        assert_eq!(numbers("Kiểu int32 và biến x12 có giá trị 7"), set(&["7"]));
+        assert_eq!(numbers("Liệt kê 1, 2, 3"), set(&["1", "2", "3"]));
+    }
+
+    #[test]
+    fn code_tokens_keep_operators_and_code_identifiers() {
+        let tokens = code_tokens("if (a == b && isValid(x)) count_total++");
+        assert!(tokens.contains("=="));
+        assert!(tokens.contains("&&"));
+        assert!(tokens.contains("++"));
+        assert!(tokens.contains("isValid"));
+        assert!(tokens.contains("count_total"));
+        assert!(!tokens.contains("="));
+        assert!(!code_tokens("e-learning platform").contains("-"));
+    }
+
+    #[test]
+    fn compare_features_penalizes_each_difference_once() {
+        let penalties = ContrastPenalties::default();
+        let a = ContrastFeatures::new("Which value is 5?", "x == 5");
+        let b = ContrastFeatures::new("Which value is NOT 6?", "x != 6");
+        let finding = compare_features(&a, &b, &penalties).unwrap();
+
+        assert!(finding.negation_flip);
+        assert_eq!(finding.numbers, vec!["5".to_string()]);
+        assert_eq!(finding.other_numbers, vec!["6".to_string()]);
+        assert_eq!(finding.code_tokens, vec!["==".to_string()]);
+        assert_eq!(finding.other_code_tokens, vec!["!=".to_string()]);
+        let expected = (1.0 - penalties.negation) * (1.0 - penalties.numeric) * (1.0 - penalties.code_token);
+        assert!((finding.factor - expected).abs() < 1e-6);
+        assert_eq!(finding.notes.len(), 3);
+
+        let reversed = finding.reversed();
+        assert_eq!(reversed.numbers, finding.other_numbers);
+        assert_eq!(reversed.factor, finding.factor);
+    }
+
+    #[test]
+    fn same_details_or_empty_text_have_no_finding() {
+        let penalties = ContrastPenalties::default();
+        let a = ContrastFeatures::new("What is 2 + 2?", "4");
+        let b = ContrastFeatures::new("Compute 2 + 2", "4");
+        assert!(compare_features(&a, &b, &penalties).is_none());
+        assert!(compare_features(&a, &ContrastFeatures::new("", ""), &penalties).is_none());
+        assert_eq!(apply_contrast(0.8, None), 0.8);
+    }
+}
@@ -1,3 +1,10 @@
+use rayon::prelude::*;
+
+// Số làn cộng dồn độc lập trong tích vô hướng, đủ để compiler tự vector hóa (SIMD)
+const LANES: usize = 8;
+// Số hàng mỗi luồng xử lý một lần khi tính ma trận điểm
+pub const BLOCK_ROWS: usize = 32;
+
 pub fn calculate_cosine_similarity(v1: &[f32], v2: &[f32]) -> f32 {
     let dot_product: f32 = v1.iter().zip(v2.iter()).map(|(a, b)| a * b).sum();
     let norm1: f32 = v1.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -5,5 +12,197 @@ pub fn calculate_cosine_similarity(v1: &[f32], v2: &[f32]) -> f32 {
     dot_product / (norm1 * norm2)
 }
 
+pub fn dot_product(v1: &[f32], v2: &[f32]) -> f32 {
+    let len = v1.len().min(v2.len());
+    let (a, b) = (&v1[..len], &v2[..len]);
+
+    let mut lanes = [0.0f32; LANES];
+    let (chunks_a, chunks_b) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let tail: f32 = chunks_a
+        .remainder()
+        .iter()
+        .zip(chunks_b.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+
+    for (x, y) in chunks_a.zip(chunks_b) {
+        for ((lane, x), y) in lanes.iter_mut().zip(x).zip(y) {
+            *lane += x * y;
+        }
+    }
+
+    lanes.iter().sum::<f32>() + tail
+}
+
+// Các vector đã chuẩn hóa về độ dài 1, xếp liền nhau theo hàng: cosine giữa hai hàng chỉ còn là tích vô hướng.
+// Vector rỗng hoặc khác số chiều (embedding của model khác) được lưu thành hàng 0, điểm với mọi hàng khác là 0
+#[derive(Clone)]
+pub struct NormalizedMatrix {
+    dimension: usize,
+    rows: usize,
+    data: Vec<f32>,
+}
+
+impl NormalizedMatrix {
+    pub fn from_vectors<'a, I>(vectors: I) -> Self
+    where
+        I: IntoIterator<Item = &'a [f32]>,
+    {
+        let vectors: Vec<&[f32]> = vectors.into_iter().collect();
+        let dimension = vectors.iter().map(|v| v.len()).find(|len| *len > 0).unwrap_or(0);
+
+        let mut data = vec![0.0f32; dimension * vectors.len()];
+        data.par_chunks_mut(dimension.max(1))
+            .zip(vectors.par_iter())
+            .filter(|(_, vector)| vector.len() == dimension)
+            .for_each(|(row, vector)| {
+                let norm = dot_product(vector, vector).sqrt();
+                if norm > 0.0 {
+                    for (target, value) in row.iter_mut().zip(vector.iter()) {
+                        *target = value / norm;
+                    }
+                }
+            });
+
+        NormalizedMatrix {
+            dimension,
+            rows: vectors.len(),
+            data,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn row(&self, index: usize) -> &[f32] {
+        &self.data[index * self.dimension..(index + 1) * self.dimension]
+    }
+
+    // Cosine giữa hàng `index` của ma trận này và hàng `other_index` của ma trận kia
+    pub fn similarity(&self, index: usize, other: &NormalizedMatrix, other_index: usize) -> f32 {
+        if self.dimension != other.dimension {
+            return 0.0;
+        }
+        dot_product(self.row(index), other.row(other_index))
+    }
+
+    // Điểm của các hàng `rows` với toàn bộ hàng của `other`, kết quả xếp theo hàng: `rows.len() * other.len()`.
+    // Duyệt `other` ở vòng ngoài để mỗi hàng của nó chỉ đọc từ bộ nhớ một lần cho cả khối
+    pub fn block_similarities(&self, rows: std::ops::Range<usize>, other: &NormalizedMatrix) -> Vec<f32> {
+        let columns = other.len();
+        let mut scores = vec![0.0f32; rows.len() * columns];
+        if self.dimension != other.dimension {
+            return scores;
+        }
+
+        for column in 0..columns {
+            let other_row = other.row(column);
+            for (offset, index) in rows.clone().enumerate() {
+                scores[offset * columns + column] = dot_product(self.row(index), other_row);
+            }
+        }
+        scores
+    }
 
+    // Ma trận điểm đầy đủ giữa các hàng của hai ma trận, các khối hàng được tính song song
+    pub fn similarity_matrix(&self, other: &NormalizedMatrix) -> SimilarityMatrix {
+        let columns = other.len();
+        let scores = (0..self.rows)
+            .step_by(BLOCK_ROWS)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .flat_map_iter(|start| self.block_similarities(start..(start + BLOCK_ROWS).min(self.rows), other))
+            .collect();
 
+        SimilarityMatrix { columns, scores }
+    }
+}
+
+pub struct SimilarityMatrix {
+    columns: usize,
+    scores: Vec<f32>,
+}
+
+impl SimilarityMatrix {
+    pub fn get(&self, row: usize, column: usize) -> f32 {
+        self.scores[row * self.columns + column]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vector giả lập theo hàng, đủ khác nhau để mọi cặp có điểm riêng
+    fn vectors(rows: usize, dimension: usize) -> Vec<Vec<f32>> {
+        (0..rows)
+            .map(|row| (0..dimension).map(|i| ((row * 7 + i * 3) % 11) as f32 - 5.0).collect())
+            .collect()
+    }
+
+    fn matrix(vectors: &[Vec<f32>]) -> NormalizedMatrix {
+        NormalizedMatrix::from_vectors(vectors.iter().map(|v| v.as_slice()))
+    }
+
+    #[test]
+    fn similarity_matrix_matches_cosine_across_partial_blocks() {
+        // Số hàng không chia hết cho BLOCK_ROWS, số chiều không chia hết cho LANES
+        let (left, right) = (vectors(BLOCK_ROWS * 2 + 5, 19), vectors(BLOCK_ROWS + 3, 19));
+        let (a, b) = (matrix(&left), matrix(&right));
+        let scores = a.similarity_matrix(&b);
+
+        for (i, v1) in left.iter().enumerate() {
+            for (j, v2) in right.iter().enumerate() {
+                let expected = calculate_cosine_similarity(v1, v2);
+                assert!((scores.get(i, j) - expected).abs() < 1e-5);
+                assert!((a.similarity(i, &b, j) - expected).abs() < 1e-5);
+            }
+        }
+
+        let block = a.block_similarities(BLOCK_ROWS * 2..left.len(), &b);
+        assert_eq!(block.len(), 5 * right.len());
+        assert!((block[4 * right.len() + 2] - scores.get(BLOCK_ROWS * 2 + 4, 2)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn empty_and_zero_vectors_score_zero() {
+        let empty = NormalizedMatrix::from_vectors(Vec::<&[f32]>::new());
+        assert_eq!(empty.len(), 0);
+        assert_eq!(empty.dimension(), 0);
+        let other = matrix(&vectors(3, 8));
+        assert!(empty.block_similarities(0..0, &other).is_empty());
+        assert!(other.block_similarities(0..3, &empty).is_empty());
+
+        // Hàng rỗng và vector 0 được giữ chỗ bằng hàng 0
+        let rows = vec![vec![0.0; 8], Vec::new(), vectors(1, 8).remove(0)];
+        let a = matrix(&rows);
+        assert_eq!((a.len(), a.dimension()), (3, 8));
+        assert!(a.row(0).iter().chain(a.row(1)).all(|x| *x == 0.0));
+        let scores = a.similarity_matrix(&other);
+        for column in 0..other.len() {
+            assert_eq!(scores.get(0, column), 0.0);
+            assert_eq!(scores.get(1, column), 0.0);
+        }
+        assert!((a.similarity(2, &a, 2) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mismatched_dimensions_score_zero() {
+        // Hàng khác số chiều với hàng đầu (embedding của model khác) thành hàng 0
+        let mixed = matrix(&[vectors(1, 8).remove(0), vectors(1, 4).remove(0)]);
+        assert_eq!(mixed.dimension(), 8);
+        assert!(mixed.row(1).iter().all(|x| *x == 0.0));
+
+        // Hai ma trận khác số chiều cho điểm 0 ở mọi ô
+        let (a, b) = (matrix(&vectors(3, 8)), matrix(&vectors(2, 4)));
+        assert_eq!(a.similarity(0, &b, 0), 0.0);
+        assert_eq!(a.block_similarities(0..3, &b), vec![0.0; 6]);
+        let scores = a.similarity_matrix(&b);
+        assert!((0..3).all(|i| (0..2).all(|j| scores.get(i, j) == 0.0)));
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::{anyhow, Result};
+use fastembed::{
+    EmbeddingModel, InitOptions, InitOptionsUserDefined, Pooling, TextEmbedding, TokenizerFiles,
+    UserDefinedEmbeddingModel,
+};
+use std::fs;
+use std::path::Path;
+use tokenizers::Tokenizer;
+
+// Mọi nơi cần tạo embedding (parser, các hàm kiểm tra trùng) chỉ làm việc qua trait này
+pub trait Embedder: Send + Sync {
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+
+    // Vị trí byte (đầu, cuối) trong `text` của từng token nội dung theo tokenizer của model, không gồm
+    // token đặc biệt; None khi model không có tokenizer
+    fn token_spans(&self, _text: &str) -> Option<Vec<(usize, usize)>> {
+        None
+    }
+
+    // Số token model tự thêm vào mỗi văn bản (token đặc biệt, prefix), chiếm chỗ trong cửa sổ token
+    fn reserved_tokens(&self) -> usize {
+        0
+    }
+}
+
+pub struct FastembedEmbedder {
+    model: TextEmbedding,
+    prefix: &'static str,
+    // Tokenizer đọc từ tokenizer.json của model, bỏ cắt ngắn và padding để đếm đủ token của văn bản dài
+    tokenizer: Tokenizer,
+    reserved: usize,
+}
+
+impl FastembedEmbedder {
+    // `cache_dir` là thư mục tải model (Paths.ModelCache)
+    pub fn from_model(model_name: EmbeddingModel, cache_dir: &Path) -> Result<Self> {
+        // Các model E5 cần prefix "query: " cho bài toán so sánh đối xứng
+        let prefix = match model_name {
+            EmbeddingModel::MultilingualE5Small
+            | EmbeddingModel::MultilingualE5Base
+            | EmbeddingModel::MultilingualE5Large => "query: ",
+            _ => "",
+        };
+
+        let mut options = InitOptions::default();
+        options.model_name = model_name;
+        options.show_download_progress = true;
+        options.cache_dir = cache_dir.to_path_buf();
+
+        FastembedEmbedder::new(TextEmbedding::try_new(options)?, prefix)
+    }
+
+    // Model ONNX do người dùng tự cung cấp, thư mục gồm model.onnx và các file tokenizer
+    pub fn from_directory(dir: &Path) -> Result<Self> {
+        let read = |file_name: &str| {
+            fs::read(dir.join(file_name))
+                .map_err(|e| anyhow!("Không đọc được {} trong {}: {}", file_name, dir.display(), e))
+        };
+
+        let tokenizer_files = TokenizerFiles {
+            tokenizer_file: read("tokenizer.json")?,
+            config_file: read("config.json")?,
+            special_tokens_map_file: read("special_tokens_map.json")?,
+            tokenizer_config_file: read("tokenizer_config.json")?,
+        };
+        let model = UserDefinedEmbeddingModel::new(read("model.onnx")?, tokenizer_files)
+            .with_pooling(Pooling::Mean);
+
+        FastembedEmbedder::new(
+            TextEmbedding::try_new_from_user_defined(model, InitOptionsUserDefined::default())?,
+            "",
+        )
+    }
+
+    fn new(model: TextEmbedding, prefix: &'static str) -> Result<Self> {
+        let mut tokenizer = model.tokenizer.clone();
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| anyhow!("Không thể tắt cắt ngắn của tokenizer: {}", e))?;
+        tokenizer.with_padding(None);
+
+        // Token đặc biệt ([CLS], [SEP], ...) cộng với token của prefix
+        let reserved = tokenizer
+            .encode(prefix, true)
+            .map_err(|e| anyhow!("Lỗi tokenizer: {}", e))?
+            .get_ids()
+            .len();
+
+        Ok(FastembedEmbedder {
+            model,
+            prefix,
+            tokenizer,
+            reserved,
+        })
+    }
+}
+
+impl Embedder for FastembedEmbedder {
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let texts: Vec<String> = texts
+            .iter()
+            .map(|text| format!("{}{}", self.prefix, text))
+            .collect();
+
+        self.model.embed(texts, None)
+    }
+
+    fn token_spans(&self, text: &str) -> Option<Vec<(usize, usize)>> {
+        let encoding = self.tokenizer.encode(text, false).ok()?;
+        Some(
+            encoding
+                .get_offsets()
+                .iter()
+                .zip(encoding.get_special_tokens_mask())
+                .filter(|(_, special)| **special == 0)
+                .map(|(offsets, _)| *offsets)
+                .collect(),
+        )
+    }
+
+    fn reserved_tokens(&self) -> usize {
+        self.reserved
+    }
+}
+
+// FNV-1a: cho cùng kết quả trên mọi máy và mọi lần chạy
+pub fn stable_hash(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Embedding tất định dựa trên băm từ và n-gram ký tự: không cần model, dùng khi offline
+pub struct HashingEmbedder {
+    pub dimension: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        HashingEmbedder { dimension: dimension.max(1) }
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let hash = stable_hash(feature);
+        let index = (hash % self.dimension as u64) as usize;
+        let sign = if (hash >> 63) == 0 { 1.0 } else { -1.0 };
+        vector[index] += sign * weight;
+    }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimension];
+        let lower = text.to_lowercase();
+
+        for word in lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            self.add_feature(&mut vector, &format!("w:{}", word), 1.0);
+        }
+
+        let chars: Vec<char> = lower.chars().filter(|c| !c.is_whitespace()).collect();
+        for window in chars.windows(3) {
+            let trigram: String = window.iter().collect();
+            self.add_feature(&mut vector, &format!("c:{}", trigram), 0.5);
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_one(text)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashing_embedder_has_fixed_dimension_and_unit_norm() {
+        let embedder: &dyn Embedder = &HashingEmbedder::new(64);
+        let vectors = embedder.embed(&["Thủ đô của Việt Nam là gì?", "CPU", ""]).unwrap();
+
+        assert_eq!(vectors.len(), 3);
+        assert!(vectors.iter().all(|vector| vector.len() == 64));
+        let norm = vectors[0].iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-6);
+        // Văn bản rỗng không có đặc trưng nào nên giữ vector 0
+        assert!(vectors[2].iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn hashing_embedder_is_deterministic() {
+        let embedder: &dyn Embedder = &HashingEmbedder::new(128);
+        let first = embedder.embed(&["Central Processing Unit"]).unwrap();
+        let second = HashingEmbedder::new(128).embed(&["Central Processing Unit"]).unwrap();
+
+        assert_eq!(first, second);
+        assert_ne!(first, embedder.embed(&["Photosynthesis"]).unwrap());
+        assert_eq!(HashingEmbedder::new(0).embed(&["x"]).unwrap()[0].len(), 1);
+    }
+}
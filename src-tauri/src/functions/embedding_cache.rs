@@ -0,0 +1,250 @@
+use crate::functions::embedder::{stable_hash, Embedder};
+use crate::functions::embedding_model::SelectedModel;
+use crate::functions::long_text::{mean_pool, split_chunks};
+use crate::service::progress::JobContext;
+use crate::service::settings::Settings;
+use duckdb::{params_from_iter, Connection};
+use std::collections::{HashMap, HashSet};
+
+// Mục "EmbeddingCache" trong settings.json; file cache nằm ở Paths.EmbeddingCache
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct EmbeddingCacheSettings {
+    pub enabled: bool,
+    // Số văn bản đưa vào model mỗi lần
+    pub batch_size: usize,
+}
+
+impl Default for EmbeddingCacheSettings {
+    fn default() -> Self {
+        EmbeddingCacheSettings {
+            enabled: true,
+            batch_size: 64,
+        }
+    }
+}
+
+// Gộp khoảng trắng để cùng một câu hỏi định dạng khác nhau vẫn dùng chung cache
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn cache_key(normalized: &str) -> String {
+    format!("{:016x}-{}", stable_hash(normalized), normalized.len())
+}
+
+fn open_cache(path: &str) -> duckdb::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS embedding_cache (
+            model VARCHAR NOT NULL,
+            text_hash VARCHAR NOT NULL,
+            embedding REAL[] NOT NULL,
+            PRIMARY KEY (model, text_hash)
+        )",
+        [],
+    )?;
+    Ok(conn)
+}
+
+fn read_cached(conn: &Connection, model: &str, keys: &[String]) -> duckdb::Result<HashMap<String, Vec<f32>>> {
+    let mut cached = HashMap::new();
+
+    for chunk in keys.chunks(500) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let query = format!(
+            "SELECT text_hash, CAST(embedding AS JSON) FROM embedding_cache WHERE model = ? AND text_hash IN ({})",
+            placeholders
+        );
+        let mut stmt = conn.prepare(&query)?;
+
+        let params = std::iter::once(model.to_string()).chain(chunk.iter().cloned());
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            let key: String = row.get(0)?;
+            let json: String = row.get(1)?;
+            Ok((key, json))
+        })?;
+
+        for (key, json) in rows.filter_map(Result::ok) {
+            if let Ok(embedding) = serde_json::from_str::<Vec<f32>>(&json) {
+                cached.insert(key, embedding);
+            }
+        }
+    }
+
+    Ok(cached)
+}
+
+fn write_cached(conn: &mut Connection, model: &str, entries: &[(String, Vec<f32>)]) -> duckdb::Result<()> {
+    let tx = conn.transaction()?;
+
+    for (key, embedding) in entries {
+        let query = format!(
+            "INSERT OR REPLACE INTO embedding_cache (model, text_hash, embedding) VALUES (?, ?, array{:?}::REAL[])",
+            embedding
+        );
+        tx.execute(&query, [model, key.as_str()])?;
+    }
+
+    tx.commit()
+}
+
+// Tạo embedding cho toàn bộ văn bản của một file: lấy từ cache nếu có,
+// phần còn lại embed theo từng batch và lưu lại vào cache.
+// Văn bản dài hơn cửa sổ token của model được chia thành các đoạn chồng lấn, embed (và cache) từng đoạn
+// rồi gộp lại thành một vector
+pub fn embed_texts(
+    model: &SelectedModel,
+    texts: &[&str],
+    settings: &Settings,
+    job: &JobContext,
+) -> Result<Vec<Vec<f32>>, String> {
+    let cache = &settings.embedding_cache;
+    let chunking = &settings.long_text;
+
+    let chunked: Vec<Vec<String>> = texts.iter()
+        .map(|text| split_chunks(model, &normalize_text(text), chunking))
+        .collect();
+    let normalized: Vec<String> = chunked.iter().flatten().cloned().collect();
+    let keys: Vec<String> = normalized.iter().map(|text| cache_key(text)).collect();
+
+    let mut conn = if cache.enabled {
+        match open_cache(&settings.paths.embedding_cache) {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                println!("Không thể mở cache embedding: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut embeddings: HashMap<String, Vec<f32>> = match &conn {
+        Some(conn) => read_cached(conn, &model.name, &keys).unwrap_or_else(|e| {
+            println!("Lỗi khi đọc cache embedding: {}", e);
+            HashMap::new()
+        }),
+        None => HashMap::new(),
+    };
+
+    let cached_count = keys.iter().filter(|key| embeddings.contains_key(*key)).count();
+    println!("Cache embedding: {}/{} đoạn văn bản đã có sẵn", cached_count, keys.len());
+    if keys.len() > texts.len() {
+        println!("Đã chia {} văn bản dài thành nhiều đoạn", chunked.iter().filter(|chunks| chunks.len() > 1).count());
+    }
+
+    // Các văn bản chưa có trong cache, mỗi văn bản chỉ embed một lần
+    let mut queued: HashSet<&String> = HashSet::new();
+    let mut missing: Vec<(String, &str)> = Vec::new();
+    for (key, text) in keys.iter().zip(normalized.iter()) {
+        if !embeddings.contains_key(key) && queued.insert(key) {
+            missing.push((key.clone(), text.as_str()));
+        }
+    }
+
+    let total = missing.len();
+    let mut new_entries: Vec<(String, Vec<f32>)> = Vec::with_capacity(total);
+    job.report("embedding", 0, total);
+
+    for batch in missing.chunks(cache.batch_size) {
+        job.check_cancelled()?;
+
+        let batch_texts: Vec<&str> = batch.iter().map(|(_, text)| *text).collect();
+        let batch_embeddings = model
+            .embed(&batch_texts)
+            .map_err(|e| format!("Lỗi khi tạo embedding: {}", e))?;
+
+        for ((key, _), embedding) in batch.iter().zip(batch_embeddings) {
+            new_entries.push((key.clone(), embedding));
+        }
+        job.report("embedding", new_entries.len(), total);
+    }
+
+    if let Some(conn) = conn.as_mut() {
+        if !new_entries.is_empty() {
+            if let Err(e) = write_cached(conn, &model.name, &new_entries) {
+                println!("Lỗi khi ghi cache embedding: {}", e);
+            }
+        }
+    }
+
+    embeddings.extend(new_entries);
+
+    let mut chunk_embeddings = keys.iter()
+        .map(|key| {
+            embeddings
+                .get(key)
+                .cloned()
+                .ok_or_else(|| "Thiếu embedding sau khi xử lý batch".to_string())
+        })
+        .collect::<Result<Vec<_>, String>>()?
+        .into_iter();
+
+    Ok(chunked.iter()
+        .map(|chunks| mean_pool(chunk_embeddings.by_ref().take(chunks.len()).collect()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::embedding_model::{get_model, HASHING_MODEL_NAME};
+    use crate::functions::long_text::ChunkSettings;
+    use std::fs;
+
+    #[test]
+    fn cached_embeddings_are_reused_in_input_order() {
+        let path = std::env::temp_dir().join(format!("embedding_cache_{}.duckdb", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut settings = Settings::default();
+        settings.paths.embedding_cache = path.to_string_lossy().to_string();
+        // Batch nhỏ và cửa sổ 4 từ để văn bản dài bị chia đoạn và nằm ở nhiều batch
+        settings.embedding_cache.batch_size = 2;
+        settings.long_text = ChunkSettings {
+            enabled: true,
+            max_tokens: 4,
+            overlap_tokens: 1,
+        };
+        let model = get_model(HASHING_MODEL_NAME, "").unwrap();
+        let job = JobContext::new(None, None);
+        let texts = [
+            "Thủ đô của Pháp",
+            "CPU",
+            "một hai ba bốn năm sáu bảy tám",
+            "CPU",
+            "  Thủ  đô của   Pháp ",
+        ];
+
+        let first = embed_texts(&model, &texts, &settings, &job).unwrap();
+        let expected: Vec<Vec<f32>> = texts
+            .iter()
+            .map(|text| {
+                let chunks = split_chunks(&model, &normalize_text(text), &settings.long_text);
+                let chunks: Vec<&str> = chunks.iter().map(|chunk| chunk.as_str()).collect();
+                mean_pool(model.embed(&chunks).unwrap())
+            })
+            .collect();
+        assert_eq!(split_chunks(&model, texts[2], &settings.long_text).len(), 3);
+        assert_eq!(first, expected);
+
+        // Mỗi đoạn khác nhau được lưu một lần: 1 + 1 + 3 đoạn. Sau đó thay bản ghi cache của "CPU":
+        // lần gọi sau phải đọc từ cache thay vì embed lại
+        let sentinel = vec![0.5f32; first[1].len()];
+        {
+            let mut conn = open_cache(&settings.paths.embedding_cache).unwrap();
+            let keys: Vec<String> = ["Thủ đô của Pháp", "CPU"].iter().map(|text| cache_key(text)).collect();
+            assert_eq!(read_cached(&conn, HASHING_MODEL_NAME, &keys).unwrap().len(), 2);
+            let count: i64 = conn.query_row("SELECT COUNT(*) FROM embedding_cache", [], |row| row.get(0)).unwrap();
+            assert_eq!(count, 5);
+            write_cached(&mut conn, HASHING_MODEL_NAME, &[(cache_key("CPU"), sentinel.clone())]).unwrap();
+        }
+
+        let second = embed_texts(&model, &texts, &settings, &job).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(second[1], sentinel);
+        assert_eq!(second[3], sentinel);
+        assert_eq!((&second[0], &second[2], &second[4]), (&first[0], &first[2], &first[4]));
+    }
+}
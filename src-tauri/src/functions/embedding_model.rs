@@ -0,0 +1,309 @@
+use crate::functions::embedder::{Embedder, FastembedEmbedder, HashingEmbedder};
+use fastembed::EmbeddingModel;
+use crate::service::settings::{ModelSettings, Settings};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+
+pub const DEFAULT_MODEL_NAME: &str = "all-minilm-l6-v2";
+pub const HASHING_MODEL_NAME: &str = "hashing";
+pub const LOCAL_MODEL_PREFIX: &str = "local:";
+const HASHING_DIMENSION: usize = 384;
+
+// Mỗi model một ô riêng, key là tên model đã chuẩn hóa. Khóa danh sách chỉ giữ trong lúc lấy ô; việc tải và
+// khởi tạo model chỉ khóa ô của model đó, nên model khác và truy vấn trạng thái không phải chờ
+pub struct ModelSlots<T> {
+    slots: Mutex<HashMap<String, Arc<Mutex<Option<T>>>>>,
+}
+
+impl<T: Clone> Default for ModelSlots<T> {
+    fn default() -> Self {
+        ModelSlots::new()
+    }
+}
+
+impl<T: Clone> ModelSlots<T> {
+    pub fn new() -> Self {
+        ModelSlots {
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Các lần gọi cùng key trong lúc đang load sẽ chờ và dùng lại kết quả; load lỗi thì lần gọi sau thử lại
+    pub fn get_or_load<F>(&self, key: &str, load: F) -> Result<T, String>
+    where
+        F: FnOnce() -> Result<T, String>,
+    {
+        let slot = self
+            .slots
+            .lock()
+            .map_err(|_| "Không thể truy cập danh sách model".to_string())?
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+
+        let mut slot = slot
+            .lock()
+            .map_err(|_| format!("Không thể truy cập model {}", key))?;
+        if let Some(value) = slot.as_ref() {
+            return Ok(value.clone());
+        }
+
+        let value = load()?;
+        *slot = Some(value.clone());
+        Ok(value)
+    }
+}
+
+// Các model đã load; toàn bộ ứng dụng dùng chung danh sách này nên mỗi model chỉ được load một lần
+static LOADED_MODELS: LazyLock<ModelSlots<Arc<dyn Embedder>>> = LazyLock::new(ModelSlots::new);
+
+// Trạng thái load của từng model, tách khỏi LOADED_MODELS để truy vấn không bị chặn khi đang load
+static MODEL_STATUS: LazyLock<Mutex<HashMap<String, ModelStatus>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum ModelStatus {
+    NotLoaded,
+    Loading,
+    Ready,
+    Failed(String),
+}
+
+const VIETNAMESE_CHARS: &str =
+    "ăâđêôơưàảãáạằẳẵắặầẩẫấậèẻẽéẹềểễếệìỉĩíịòỏõóọồổỗốộờởỡớợùủũúụừửữứựỳỷỹýỵ";
+
+// Model đã chọn cho một file; tên được lưu kèm embedding trong database
+pub struct SelectedModel {
+    pub name: String,
+    embedder: Arc<dyn Embedder>,
+}
+
+impl Embedder for SelectedModel {
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        self.embedder.embed(texts)
+    }
+
+    fn token_spans(&self, text: &str) -> Option<Vec<(usize, usize)>> {
+        self.embedder.token_spans(text)
+    }
+
+    fn reserved_tokens(&self) -> usize {
+        self.embedder.reserved_tokens()
+    }
+}
+
+pub fn parse_model_name(name: &str) -> Option<EmbeddingModel> {
+    match name.trim().to_lowercase().as_str() {
+        "all-minilm-l6-v2" => Some(EmbeddingModel::AllMiniLML6V2),
+        "multilingual-e5-small" => Some(EmbeddingModel::MultilingualE5Small),
+        "multilingual-e5-base" => Some(EmbeddingModel::MultilingualE5Base),
+        "multilingual-e5-large" => Some(EmbeddingModel::MultilingualE5Large),
+        "paraphrase-multilingual-minilm-l12-v2" => Some(EmbeddingModel::ParaphraseMLMiniLML12V2),
+        "paraphrase-multilingual-mpnet-base-v2" => Some(EmbeddingModel::ParaphraseMLMpnetBaseV2),
+        _ => None,
+    }
+}
+
+// Đoán văn bản là tiếng Việt nếu tỉ lệ ký tự có dấu tiếng Việt đủ lớn
+pub fn is_vietnamese(text: &str) -> bool {
+    let mut letter_count = 0;
+    let mut vietnamese_count = 0;
+
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letter_count += 1;
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        if VIETNAMESE_CHARS.contains(lower) {
+            vietnamese_count += 1;
+        }
+    }
+
+    letter_count > 0 && vietnamese_count * 20 >= letter_count
+}
+
+// Chọn tên model theo thứ tự: theo môn học, theo ngôn ngữ, model mặc định (mục "Model" trong settings.json)
+fn select_model(models: &ModelSettings, subject: Option<&str>, vietnamese: bool) -> String {
+    if let Some(subject) = subject {
+        if let Some(name) = models.subjects.get(subject.trim()) {
+            return name.clone();
+        }
+    }
+
+    if vietnamese {
+        if let Some(name) = &models.vietnamese {
+            return name.clone();
+        }
+    }
+
+    models.default.clone()
+}
+
+// Ngôn ngữ được đoán từ nội dung file
+pub fn select_model_name(models: &ModelSettings, subject: Option<&str>, sample_text: &str) -> String {
+    select_model(models, subject, is_vietnamese(sample_text))
+}
+
+// Dùng khi chưa có nội dung file (load trước model, xem trạng thái): `language` là "vi" thì chọn model tiếng Việt
+pub fn select_model_for_language(models: &ModelSettings, subject: Option<&str>, language: Option<&str>) -> String {
+    let vietnamese = language.is_some_and(|language| language.trim().eq_ignore_ascii_case("vi"));
+    select_model(models, subject, vietnamese)
+}
+
+// Tên model có thể là model fastembed, "hashing" (offline) hoặc "local:<thư mục model ONNX>";
+// model fastembed được tải về thư mục `model_cache`
+fn create_embedder(name: &str, model_cache: &str) -> Result<Arc<dyn Embedder>, String> {
+    let trimmed = name.trim();
+
+    if trimmed.eq_ignore_ascii_case(HASHING_MODEL_NAME) {
+        return Ok(Arc::new(HashingEmbedder::new(HASHING_DIMENSION)));
+    }
+
+    if let Some(dir) = trimmed.strip_prefix(LOCAL_MODEL_PREFIX) {
+        let embedder = FastembedEmbedder::from_directory(Path::new(dir.trim()))
+            .map_err(|e| format!("Không thể khởi tạo model {}: {}", name, e))?;
+        return Ok(Arc::new(embedder));
+    }
+
+    let model_name = parse_model_name(trimmed)
+        .ok_or_else(|| format!("Model embedding không được hỗ trợ: {}", name))?;
+    let embedder = FastembedEmbedder::from_model(model_name, Path::new(model_cache))
+        .map_err(|e| format!("Không thể khởi tạo model {}: {}", name, e))?;
+    Ok(Arc::new(embedder))
+}
+
+fn model_key(name: &str) -> String {
+    let trimmed = name.trim();
+    // Giữ nguyên đường dẫn của model local, chỉ chuẩn hóa tên model có sẵn
+    if trimmed.starts_with(LOCAL_MODEL_PREFIX) {
+        trimmed.to_string()
+    } else {
+        trimmed.to_lowercase()
+    }
+}
+
+fn set_status(key: &str, status: ModelStatus) {
+    if let Ok(mut statuses) = MODEL_STATUS.lock() {
+        statuses.insert(key.to_string(), status);
+    }
+}
+
+pub fn model_status(name: &str) -> ModelStatus {
+    MODEL_STATUS
+        .lock()
+        .ok()
+        .and_then(|statuses| statuses.get(&model_key(name)).cloned())
+        .unwrap_or(ModelStatus::NotLoaded)
+}
+
+pub fn get_model(name: &str, model_cache: &str) -> Result<SelectedModel, String> {
+    let key = model_key(name);
+
+    let embedder = LOADED_MODELS.get_or_load(&key, || {
+        set_status(&key, ModelStatus::Loading);
+
+        // Lỗi khi tải/khởi tạo ONNX được trả về như lỗi thường thay vì làm sập ứng dụng
+        let created = std::panic::catch_unwind(|| create_embedder(&key, model_cache))
+            .unwrap_or_else(|_| Err(format!("Khởi tạo model {} bị lỗi nghiêm trọng", key)));
+
+        match &created {
+            Ok(_) => set_status(&key, ModelStatus::Ready),
+            Err(e) => set_status(&key, ModelStatus::Failed(e.clone())),
+        }
+        created
+    })?;
+
+    Ok(SelectedModel {
+        name: key,
+        embedder,
+    })
+}
+
+pub fn load_model_for(settings: &Settings, subject: Option<&str>, sample_text: &str) -> Result<SelectedModel, String> {
+    let name = select_model_name(&settings.model, subject, sample_text);
+    println!("Đang sử dụng model embedding: {}", name);
+    get_model(&name, &settings.paths.model_cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models() -> ModelSettings {
+        ModelSettings {
+            default: DEFAULT_MODEL_NAME.to_string(),
+            vietnamese: Some("multilingual-e5-small".to_string()),
+            subjects: HashMap::from([("Tin học".to_string(), HASHING_MODEL_NAME.to_string())]),
+        }
+    }
+
+    #[test]
+    fn vietnamese_is_detected_from_diacritics() {
+        assert!(is_vietnamese("Thủ đô của Việt Nam là thành phố nào?"));
+        assert!(is_vietnamese("ĐƠN VỊ ĐO ĐIỆN TRỞ"));
+        assert!(!is_vietnamese("What is the capital of France?"));
+        assert!(!is_vietnamese("12 + 7 = ?"));
+        assert!(!is_vietnamese(""));
+        // Một chữ có dấu lẫn trong đoạn tiếng Anh dài chưa đủ tỉ lệ
+        assert!(!is_vietnamese("The café on the corner serves breakfast, lunch and dinner every single day"));
+    }
+
+    #[test]
+    fn model_is_selected_by_subject_then_language() {
+        let models = models();
+        assert_eq!(select_model_name(&models, Some(" Tin học "), "What is a CPU?"), HASHING_MODEL_NAME);
+        assert_eq!(select_model_name(&models, Some("Toán"), "Số nào là số nguyên tố?"), "multilingual-e5-small");
+        assert_eq!(select_model_name(&models, None, "Which number is prime?"), DEFAULT_MODEL_NAME);
+
+        assert_eq!(select_model_for_language(&models, None, Some(" VI ")), "multilingual-e5-small");
+        assert_eq!(select_model_for_language(&models, None, Some("en")), DEFAULT_MODEL_NAME);
+        assert_eq!(select_model_for_language(&models, Some("Tin học"), Some("vi")), HASHING_MODEL_NAME);
+        assert_eq!(select_model_for_language(&models, None, None), DEFAULT_MODEL_NAME);
+
+        // Không cấu hình model tiếng Việt thì dùng model mặc định
+        let models = ModelSettings::default();
+        assert_eq!(select_model_for_language(&models, None, Some("vi")), DEFAULT_MODEL_NAME);
+    }
+
+    #[test]
+    fn model_names_are_parsed_case_insensitively() {
+        assert!(matches!(parse_model_name(" All-MiniLM-L6-v2 "), Some(EmbeddingModel::AllMiniLML6V2)));
+        assert!(matches!(parse_model_name("multilingual-e5-base"), Some(EmbeddingModel::MultilingualE5Base)));
+        assert!(parse_model_name(HASHING_MODEL_NAME).is_none());
+        assert!(parse_model_name("local:/models/e5").is_none());
+        assert!(parse_model_name("").is_none());
+    }
+
+    #[test]
+    fn failed_load_is_retried_and_success_is_reused() {
+        let slots: ModelSlots<usize> = ModelSlots::new();
+        let mut calls = 0;
+
+        assert!(slots.get_or_load("e5", || { calls += 1; Err("mất mạng".to_string()) }).is_err());
+        assert_eq!(slots.get_or_load("e5", || { calls += 1; Ok(7) }), Ok(7));
+        assert_eq!(slots.get_or_load("e5", || { calls += 1; Ok(8) }), Ok(7));
+        assert_eq!(slots.get_or_load("minilm", || { calls += 1; Ok(9) }), Ok(9));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn model_status_follows_each_load() {
+        assert!(matches!(model_status("status-test-unloaded"), ModelStatus::NotLoaded));
+
+        // Model "hashing" không cần tải nên dùng được khi offline; trạng thái tra theo tên đã chuẩn hóa
+        let model = get_model(" HASHING ", "").unwrap();
+        assert_eq!(model.name, HASHING_MODEL_NAME);
+        assert!(matches!(model_status("Hashing"), ModelStatus::Ready));
+        assert_eq!(model.embed(&["CPU"]).unwrap()[0].len(), HASHING_DIMENSION);
+
+        let missing = "local:/không-tồn-tại/status-test";
+        assert!(get_model(missing, "").is_err());
+        assert!(matches!(model_status(missing), ModelStatus::Failed(_)));
+        // Lần load lỗi không được giữ lại, lần gọi sau load lại và vẫn báo lỗi
+        assert!(get_model(missing, "").is_err());
+        assert!(matches!(model_status(missing), ModelStatus::Failed(reason) if reason.contains("status-test")));
+
+        assert!(get_model("unknown-model", "").is_err());
+        assert!(matches!(model_status("UNKNOWN-MODEL"), ModelStatus::Failed(reason) if reason.contains("không được hỗ trợ")));
+    }
+}
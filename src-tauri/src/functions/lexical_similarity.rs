@@ -0,0 +1,125 @@
+use crate::functions::embedder::stable_hash;
+
+// Chữ ký MinHash của một văn bản, dùng để ước lượng Jaccard giữa hai tập shingle ký tự
+#[derive(Debug, Clone)]
+pub struct MinHashSignature {
+    values: Vec<u64>,
+}
+
+// Chuẩn hóa trước khi cắt shingle: chữ thường, bỏ dấu câu, gộp khoảng trắng
+fn normalize_for_shingles(text: &str) -> Vec<char> {
+    let lower = text.to_lowercase();
+    let cleaned: String = lower
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+
+    cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect()
+}
+
+fn char_shingles(text: &str, shingle_size: usize) -> Vec<String> {
+    let chars = normalize_for_shingles(text);
+    if chars.is_empty() {
+        return Vec::new();
+    }
+
+    // Văn bản ngắn hơn một shingle (ví dụ đáp án "12") được xem như một shingle duy nhất
+    if chars.len() <= shingle_size {
+        return vec![chars.iter().collect()];
+    }
+
+    chars
+        .windows(shingle_size)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+pub fn minhash_signature(text: &str, shingle_size: usize, num_hashes: usize) -> MinHashSignature {
+    let shingles = char_shingles(text, shingle_size.max(1));
+    if shingles.is_empty() {
+        return MinHashSignature { values: Vec::new() };
+    }
+
+    let base_hashes: Vec<u64> = shingles.iter().map(|shingle| stable_hash(shingle)).collect();
+
+    // Mỗi hàm băm i là một phép trộn khác nhau của cùng giá trị FNV gốc
+    let values = (0..num_hashes.max(1) as u64)
+        .map(|seed| {
+            let salt = seed.wrapping_mul(0x9e3779b97f4a7c15) ^ 0xbf58476d1ce4e5b9;
+            base_hashes
+                .iter()
+                .map(|hash| mix(hash ^ salt))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect();
+
+    MinHashSignature { values }
+}
+
+// SplitMix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// Tỷ lệ vị trí trùng nhau trong hai chữ ký ≈ hệ số Jaccard của hai tập shingle
+pub fn estimate_jaccard(a: &MinHashSignature, b: &MinHashSignature) -> f32 {
+    if a.values.is_empty() || b.values.is_empty() || a.values.len() != b.values.len() {
+        return 0.0;
+    }
+
+    let matches = a.values.iter().zip(b.values.iter()).filter(|(x, y)| x == y).count();
+    matches as f32 / a.values.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_texts_have_jaccard_one() {
+        let a = minhash_signature("Thủ đô của Việt Nam là gì?", 5, 128);
+        let b = minhash_signature("thủ đô của việt nam là gì", 5, 128);
+        assert_eq!(estimate_jaccard(&a, &b), 1.0);
+    }
+
+    #[test]
+    fn unrelated_texts_have_low_jaccard() {
+        let a = minhash_signature("Central Processing Unit", 5, 128);
+        let b = minhash_signature("Photosynthesis happens in chloroplasts", 5, 128);
+        assert!(estimate_jaccard(&a, &b) < 0.1);
+    }
+
+    #[test]
+    fn partial_overlap_is_between_zero_and_one() {
+        let a = minhash_signature("The quick brown fox jumps over the lazy dog", 5, 256);
+        let b = minhash_signature("The quick brown fox jumps over the sleepy cat", 5, 256);
+        let jaccard = estimate_jaccard(&a, &b);
+        assert!(jaccard > 0.3 && jaccard < 0.95, "jaccard = {}", jaccard);
+    }
+
+    #[test]
+    fn short_text_is_a_single_shingle() {
+        let a = minhash_signature("12", 5, 64);
+        let b = minhash_signature("12", 5, 64);
+        let c = minhash_signature("13", 5, 64);
+        assert_eq!(estimate_jaccard(&a, &b), 1.0);
+        assert_eq!(estimate_jaccard(&a, &c), 0.0);
+    }
+
+    #[test]
+    fn empty_or_mismatched_signatures_score_zero() {
+        let empty = minhash_signature("  ...  ", 5, 64);
+        let text = minhash_signature("some text", 5, 64);
+        let other_size = minhash_signature("some text", 5, 32);
+        assert_eq!(estimate_jaccard(&empty, &empty), 0.0);
+        assert_eq!(estimate_jaccard(&text, &other_size), 0.0);
+    }
+}
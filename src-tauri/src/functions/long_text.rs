@@ -0,0 +1,220 @@
+use crate::functions::embedder::Embedder;
+
+// Văn bản dài hơn cửa sổ token của model (MiniLM 256 token) bị model cắt mất phần cuối.
+// Mục "LongText" trong settings.json; độ dài đếm bằng tokenizer.json của model, model không có tokenizer
+// (hashing) thì đếm theo từ
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct ChunkSettings {
+    // Tắt thì văn bản dài vẫn được embed nguyên văn và bị model cắt bớt như trước
+    pub enabled: bool,
+    // Cửa sổ token của model, tính cả token đặc biệt và prefix
+    pub max_tokens: usize,
+    // Số token lặp lại giữa hai đoạn liên tiếp để không mất ngữ cảnh ở chỗ cắt
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkSettings {
+    fn default() -> Self {
+        ChunkSettings {
+            enabled: true,
+            max_tokens: 256,
+            overlap_tokens: 32,
+        }
+    }
+}
+
+impl ChunkSettings {
+    // Giá trị cột chunking trong database: embedding của bản ghi được tạo với cách chia đoạn nào
+    pub fn signature(&self) -> String {
+        if self.enabled {
+            format!("tokens:{}/{}", self.max_tokens, self.overlap_tokens)
+        } else {
+            "off".to_string()
+        }
+    }
+}
+
+// Kết quả chia đoạn của một văn bản
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkReport {
+    pub chunks: usize,
+    // Còn đoạn dài hơn cửa sổ token (không chia đoạn, hoặc tokenizer tách đoạn khác lúc đếm): model chỉ thấy phần đầu
+    pub truncated: bool,
+}
+
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(begin)) => {
+                spans.push((begin, index));
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+    if let Some(begin) = start {
+        spans.push((begin, text.len()));
+    }
+    spans
+}
+
+fn token_spans(model: &dyn Embedder, text: &str) -> Vec<(usize, usize)> {
+    model.token_spans(text).unwrap_or_else(|| word_spans(text))
+}
+
+// Số token nội dung vừa một đoạn, sau khi trừ phần model tự thêm vào
+fn chunk_budget(model: &dyn Embedder, settings: &ChunkSettings) -> usize {
+    settings.max_tokens.saturating_sub(model.reserved_tokens()).max(1)
+}
+
+// Văn bản vượt quá cửa sổ token của model
+pub fn exceeds_window(model: &dyn Embedder, text: &str, settings: &ChunkSettings) -> bool {
+    token_spans(model, text).len() > chunk_budget(model, settings)
+}
+
+// Chia văn bản thành các đoạn chồng lấn nhau theo token; văn bản ngắn (hoặc khi tắt chia đoạn) giữ nguyên một đoạn
+pub fn split_chunks(model: &dyn Embedder, text: &str, settings: &ChunkSettings) -> Vec<String> {
+    let spans = token_spans(model, text);
+    let budget = chunk_budget(model, settings);
+    if !settings.enabled || spans.len() <= budget {
+        return vec![text.to_string()];
+    }
+
+    let step = budget - settings.overlap_tokens.min(budget - 1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + budget).min(spans.len());
+        chunks.push(text[spans[start].0..spans[end - 1].1].to_string());
+        if end == spans.len() {
+            break;
+        }
+        start += step;
+    }
+    chunks
+}
+
+// Số đoạn được chia khi embed và đoạn nào còn vượt cửa sổ token; đếm lại từng đoạn vì token ở chỗ cắt
+// có thể bị tách khác so với khi đếm cả văn bản
+pub fn chunk_report(model: &dyn Embedder, text: &str, settings: &ChunkSettings) -> ChunkReport {
+    let chunks = split_chunks(model, text, settings);
+    ChunkReport {
+        chunks: chunks.len(),
+        truncated: chunks.iter().any(|chunk| exceeds_window(model, chunk, settings)),
+    }
+}
+
+// Gộp embedding của các đoạn thành một vector: trung bình các vector đã chuẩn hóa,
+// để đoạn nào cũng đóng góp như nhau vào cosine
+pub fn mean_pool(embeddings: Vec<Vec<f32>>) -> Vec<f32> {
+    if embeddings.len() == 1 {
+        return embeddings.into_iter().next().unwrap_or_default();
+    }
+
+    let dimension = embeddings.iter().map(|embedding| embedding.len()).max().unwrap_or(0);
+    let mut pooled = vec![0.0f32; dimension];
+    for embedding in embeddings.iter().filter(|embedding| embedding.len() == dimension) {
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            for (target, value) in pooled.iter_mut().zip(embedding) {
+                *target += value / norm;
+            }
+        }
+    }
+
+    let norm = pooled.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        pooled.iter_mut().for_each(|x| *x /= norm);
+    }
+    pooled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::embedder::HashingEmbedder;
+
+    // Tokenizer giả: mỗi ký tự khác khoảng trắng là một token, model thêm 2 token đặc biệt
+    struct CharTokens;
+
+    impl Embedder for CharTokens {
+        fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|_| vec![1.0]).collect())
+        }
+
+        fn token_spans(&self, text: &str) -> Option<Vec<(usize, usize)>> {
+            Some(
+                text.char_indices()
+                    .filter(|(_, c)| !c.is_whitespace())
+                    .map(|(index, c)| (index, index + c.len_utf8()))
+                    .collect(),
+            )
+        }
+
+        fn reserved_tokens(&self) -> usize {
+            2
+        }
+    }
+
+    fn settings(max_tokens: usize, overlap_tokens: usize) -> ChunkSettings {
+        ChunkSettings {
+            enabled: true,
+            max_tokens,
+            overlap_tokens,
+        }
+    }
+
+    #[test]
+    fn short_text_stays_one_chunk() {
+        let model = HashingEmbedder::new(16);
+        assert_eq!(split_chunks(&model, "một hai ba", &settings(5, 1)), vec!["một hai ba".to_string()]);
+        assert!(!exceeds_window(&model, "một hai ba", &settings(5, 1)));
+    }
+
+    #[test]
+    fn words_are_split_with_overlap_without_tokenizer() {
+        let model = HashingEmbedder::new(16);
+        let chunks = split_chunks(&model, "a b c d e f g", &settings(3, 1));
+        assert_eq!(chunks, vec!["a b c", "c d e", "e f g"]);
+        assert_eq!(chunk_report(&model, "a b c d e f g", &settings(3, 1)), ChunkReport {
+            chunks: 3,
+            truncated: false,
+        });
+    }
+
+    #[test]
+    fn tokenizer_spans_and_reserved_tokens_set_the_budget() {
+        // Cửa sổ 5 token trừ 2 token đặc biệt: mỗi đoạn 3 ký tự, lặp lại 1
+        let chunks = split_chunks(&CharTokens, "ăbcdé", &settings(5, 1));
+        assert_eq!(chunks, vec!["ăbc", "cdé"]);
+        assert!(exceeds_window(&CharTokens, "abcd", &settings(5, 1)));
+        assert!(!exceeds_window(&CharTokens, "a b c", &settings(5, 1)));
+    }
+
+    #[test]
+    fn disabled_chunking_reports_truncation() {
+        let mut disabled = settings(5, 1);
+        disabled.enabled = false;
+        assert_eq!(disabled.signature(), "off");
+        assert_eq!(chunk_report(&CharTokens, "abcdef", &disabled), ChunkReport {
+            chunks: 1,
+            truncated: true,
+        });
+        assert!(!chunk_report(&CharTokens, "abcdef", &settings(5, 1)).truncated);
+        assert_ne!(settings(5, 1).signature(), settings(5, 2).signature());
+    }
+
+    #[test]
+    fn mean_pool_averages_normalized_vectors() {
+        let pooled = mean_pool(vec![vec![2.0, 0.0], vec![0.0, 0.5]]);
+        let expected = 1.0 / 2.0f32.sqrt();
+        assert!((pooled[0] - expected).abs() < 1e-6 && (pooled[1] - expected).abs() < 1e-6);
+
+        assert_eq!(mean_pool(vec![vec![3.0, 4.0]]), vec![3.0, 4.0]);
+        assert_eq!(mean_pool(vec![vec![0.0, 2.0], vec![1.0]]), vec![0.0, 1.0]);
+    }
+}
@@ -0,0 +1,382 @@
+use crate::functions::contrast_tokens::ContrastFinding;
+use crate::functions::plot_similarity::{component_contributions, ScoreComponent, ScoringPolicy, VERBATIM_JACCARD};
+use std::collections::HashSet;
+
+// Luật đã khiến cặp câu bị đánh dấu trùng
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchRule {
+    // Phần dẫn giống hệt sau khi bỏ hoa thường, dấu câu, khoảng trắng
+    ExactText,
+    // Điểm embedding vượt ngưỡng
+    Semantic,
+    // Chép lại gần nguyên văn (Jaccard MinHash từ VERBATIM_JACCARD trở lên)
+    Lexical,
+    // Hai lựa chọn trong cùng một câu hỏi trùng nhau
+    WithinQuestion,
+    // Cùng câu hỏi và tập lựa chọn, chỉ đảo thứ tự
+    OptionSet,
+    // Điểm cross-encoder vượt ngưỡng re-rank
+    Rerank,
+}
+
+impl MatchRule {
+    fn label(&self) -> &'static str {
+        match self {
+            MatchRule::ExactText => "trùng nguyên văn",
+            MatchRule::Semantic => "tương đồng ngữ nghĩa",
+            MatchRule::Lexical => "chép gần nguyên văn",
+            MatchRule::WithinQuestion => "trùng lựa chọn trong cùng câu hỏi",
+            MatchRule::OptionSet => "cùng tập lựa chọn",
+            MatchRule::Rerank => "re-rank",
+        }
+    }
+
+    // Điểm của luật không tính từ các thành phần (điểm cross-encoder, điểm so tập lựa chọn)
+    fn own_score(&self) -> Option<&'static str> {
+        match self {
+            MatchRule::OptionSet => Some("option_set"),
+            MatchRule::Rerank => Some("rerank"),
+            _ => None,
+        }
+    }
+}
+
+// Đoạn trùng trong văn bản, vị trí tính theo ký tự (không phải byte), `end` không bao gồm
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TokenSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HighlightedPair {
+    pub text: String,
+    pub other_text: String,
+    pub spans: Vec<TokenSpan>,
+    pub other_spans: Vec<TokenSpan>,
+}
+
+// Một dòng của bảng điểm; cộng `contribution` của mọi dòng ra đúng điểm cuối cùng. Ngoài các thành phần
+// còn có dòng "contrast" (similarity là hệ số giảm điểm) và dòng "rerank" / "option_set" khi điểm do luật đó quyết định
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ComponentContribution {
+    pub component: &'static str,
+    pub similarity: f32,
+    pub weight: f32,
+    // Phần điểm dòng này góp vào điểm cuối cùng theo luật gộp, âm với dòng giảm điểm
+    pub contribution: f32,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MatchExplanation {
+    pub rule: MatchRule,
+    pub score: f32,
+    pub stem: HighlightedPair,
+    pub answer: Option<HighlightedPair>,
+    pub contributions: Vec<ComponentContribution>,
+    pub contrast: Option<ContrastFinding>,
+    pub summary: String,
+}
+
+fn normalize_text(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Luật mặc định cho một cặp câu; các bước re-rank, so tập lựa chọn, trùng trong câu tự gán luật riêng
+pub fn detect_rule(stem: &str, other_stem: &str, stem_jaccard: Option<f32>) -> MatchRule {
+    let normalized = normalize_text(stem);
+    if !normalized.is_empty() && normalized == normalize_text(other_stem) {
+        MatchRule::ExactText
+    } else if stem_jaccard.is_some_and(|jaccard| jaccard >= VERBATIM_JACCARD) {
+        MatchRule::Lexical
+    } else {
+        MatchRule::Semantic
+    }
+}
+
+// Các từ (chuỗi chữ và số liên tiếp) kèm vị trí ký tự bắt đầu, kết thúc
+fn words_with_offsets(text: &str) -> Vec<(usize, usize, String)> {
+    let mut words = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, c) in text.chars().enumerate() {
+        if c.is_alphanumeric() {
+            match current.as_mut() {
+                Some((_, word)) => word.extend(c.to_lowercase()),
+                None => current = Some((index, c.to_lowercase().collect())),
+            }
+        } else if let Some((start, word)) = current.take() {
+            words.push((start, index, word));
+        }
+    }
+    if let Some((start, word)) = current {
+        words.push((start, text.chars().count(), word));
+    }
+
+    words
+}
+
+// Đánh dấu các từ thuộc một cụm hai từ liên tiếp cũng xuất hiện ở văn bản kia, rồi gộp các từ
+// liền nhau thành đoạn. Văn bản chỉ có một từ thì so từng từ
+fn overlap_spans(text: &str, other_text: &str) -> Vec<TokenSpan> {
+    let words = words_with_offsets(text);
+    let other_words = words_with_offsets(other_text);
+
+    let marked: Vec<bool> = if words.len() < 2 || other_words.len() < 2 {
+        let other_set: HashSet<&str> = other_words.iter().map(|(_, _, word)| word.as_str()).collect();
+        words.iter().map(|(_, _, word)| other_set.contains(word.as_str())).collect()
+    } else {
+        let other_bigrams: HashSet<(&str, &str)> = other_words
+            .windows(2)
+            .map(|pair| (pair[0].2.as_str(), pair[1].2.as_str()))
+            .collect();
+        let shared: Vec<bool> = words
+            .windows(2)
+            .map(|pair| other_bigrams.contains(&(pair[0].2.as_str(), pair[1].2.as_str())))
+            .collect();
+
+        (0..words.len())
+            .map(|k| (k > 0 && shared[k - 1]) || shared.get(k).copied().unwrap_or(false))
+            .collect()
+    };
+
+    let mut spans: Vec<TokenSpan> = Vec::new();
+    let mut previous_marked = false;
+    for ((start, end, _), is_marked) in words.iter().zip(marked) {
+        match spans.last_mut() {
+            Some(span) if is_marked && previous_marked => span.end = *end,
+            _ if is_marked => spans.push(TokenSpan { start: *start, end: *end }),
+            _ => {}
+        }
+        previous_marked = is_marked;
+    }
+
+    spans
+}
+
+pub fn highlight_pair(text: &str, other_text: &str) -> HighlightedPair {
+    HighlightedPair {
+        text: text.to_string(),
+        other_text: other_text.to_string(),
+        spans: overlap_spans(text, other_text),
+        other_spans: overlap_spans(other_text, text),
+    }
+}
+
+fn component_label(component: &str) -> &str {
+    match component {
+        "stem" => "câu hỏi",
+        "answer" => "đáp án",
+        "options" => "tập lựa chọn",
+        "option" => "lựa chọn",
+        other => other,
+    }
+}
+
+// `stems` là hai phần dẫn (hoặc hai lựa chọn với luật WithinQuestion), `answers` là hai đáp án đúng nếu có.
+// `components` là điểm trước khi giảm theo `contrast`, `threshold` là ngưỡng của loại kiểm tra đã dùng khi gộp.
+// `scored_by` là luật đã cho ra `score` khi khác luật hiển thị, ví dụ cặp trùng nguyên văn có điểm re-rank
+#[allow(clippy::too_many_arguments)]
+pub fn explain_match(
+    rule: MatchRule,
+    scored_by: Option<MatchRule>,
+    stems: (&str, &str),
+    answers: Option<(&str, &str)>,
+    components: &[ScoreComponent],
+    score: f32,
+    contrast: Option<ContrastFinding>,
+    threshold: f32,
+    policy: &ScoringPolicy,
+) -> MatchExplanation {
+    let mut contributions: Vec<ComponentContribution> = components
+        .iter()
+        .zip(component_contributions(components, threshold, policy))
+        .map(|(c, contribution)| ComponentContribution {
+            component: c.component,
+            similarity: c.similarity,
+            weight: c.weight,
+            contribution,
+        })
+        .collect();
+    let combined: f32 = contributions.iter().map(|c| c.contribution).sum();
+
+    match scored_by.unwrap_or(rule).own_score() {
+        // Điểm cross-encoder hoặc điểm so tập lựa chọn thay cho điểm gộp: dòng của luật là phần chênh lệch
+        Some(component) => contributions.push(ComponentContribution {
+            component,
+            similarity: score,
+            weight: 0.0,
+            contribution: score - combined,
+        }),
+        None => {
+            if let Some(contrast) = &contrast {
+                contributions.push(ComponentContribution {
+                    component: "contrast",
+                    similarity: contrast.factor,
+                    weight: 0.0,
+                    contribution: contrast.apply(combined) - combined,
+                });
+            }
+        }
+    }
+
+    let mut summary = format!("Luật: {}", rule.label());
+    let parts: Vec<String> = components
+        .iter()
+        .map(|c| format!("{} {:.0}%", component_label(c.component), c.similarity * 100.0))
+        .collect();
+    if !parts.is_empty() {
+        summary.push_str(&format!("; {}", parts.join(", ")));
+    }
+    if let Some(contrast) = &contrast {
+        summary.push_str(&format!("; đã giảm điểm: {}", contrast.notes.join(", ")));
+    }
+
+    MatchExplanation {
+        rule,
+        score,
+        stem: highlight_pair(stems.0, stems.1),
+        answer: answers.map(|(answer, other_answer)| highlight_pair(answer, other_answer)),
+        contributions,
+        contrast,
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::plot_similarity::combine_components;
+    use crate::service::settings::Settings;
+
+    fn policy() -> ScoringPolicy {
+        ScoringPolicy::new(&Settings::default(), None)
+    }
+
+    fn spans(spans: &[TokenSpan]) -> Vec<(usize, usize)> {
+        spans.iter().map(|span| (span.start, span.end)).collect()
+    }
+
+    fn slice(text: &str, span: &TokenSpan) -> String {
+        text.chars().skip(span.start).take(span.end - span.start).collect()
+    }
+
+    fn components() -> Vec<ScoreComponent> {
+        vec![
+            ScoreComponent { component: "stem", similarity: 0.92, weight: 0.7 },
+            ScoreComponent { component: "answer", similarity: 0.81, weight: 0.3 },
+        ]
+    }
+
+    fn sum(explanation: &MatchExplanation) -> f32 {
+        explanation.contributions.iter().map(|c| c.contribution).sum()
+    }
+
+    #[test]
+    fn spans_are_char_offsets_of_shared_bigrams() {
+        let text = "Thủ đô của Việt Nam là gì?";
+        let pair = highlight_pair(text, "Thủ đô của Pháp là gì?");
+
+        // Vị trí tính theo ký tự: "Thủ đô của" dài 10 ký tự nhưng 15 byte
+        assert_eq!(spans(&pair.spans), vec![(0, 10), (20, 25)]);
+        assert_eq!(slice(text, &pair.spans[0]), "Thủ đô của");
+        assert_eq!(slice(text, &pair.spans[1]), "là gì");
+        assert_eq!(spans(&pair.other_spans), vec![(0, 10), (16, 21)]);
+    }
+
+    #[test]
+    fn single_words_are_matched_alone() {
+        let pair = highlight_pair("CPU", "Đơn vị xử lý trung tâm (cpu)");
+        assert_eq!(spans(&pair.spans), vec![(0, 3)]);
+        assert_eq!(slice(&pair.other_text, &pair.other_spans[0]), "cpu");
+
+        assert!(highlight_pair("Quang hợp", "Hô hấp tế bào").spans.is_empty());
+    }
+
+    #[test]
+    fn rule_detection_prefers_exact_text_then_verbatim_copies() {
+        assert_eq!(detect_rule("Thủ đô của Việt Nam?", "thủ đô  của VIỆT NAM", None), MatchRule::ExactText);
+        assert_eq!(detect_rule("Thủ đô của Việt Nam?", "Thủ đô của Lào?", Some(VERBATIM_JACCARD)), MatchRule::Lexical);
+        assert_eq!(detect_rule("Thủ đô của Việt Nam?", "Thủ đô của Lào?", Some(0.5)), MatchRule::Semantic);
+        // Hai phần dẫn rỗng (chỉ có dấu câu) không được coi là trùng nguyên văn
+        assert_eq!(detect_rule("?", "...", None), MatchRule::Semantic);
+    }
+
+    #[test]
+    fn contributions_add_up_to_score_after_contrast() {
+        let policy = policy();
+        let threshold = policy.thresholds.in_file;
+        let contrast = ContrastFinding {
+            negation_flip: true,
+            numbers: Vec::new(),
+            other_numbers: Vec::new(),
+            code_tokens: Vec::new(),
+            other_code_tokens: Vec::new(),
+            factor: 0.6,
+            notes: vec!["phủ định".to_string()],
+        };
+        let score = contrast.apply(combine_components(&components(), threshold, &policy));
+
+        let explanation = explain_match(
+            MatchRule::Semantic,
+            None,
+            ("Câu nào KHÔNG phải là số nguyên tố?", "Câu nào là số nguyên tố?"),
+            None,
+            &components(),
+            score,
+            Some(contrast),
+            threshold,
+            &policy,
+        );
+
+        assert!((sum(&explanation) - score).abs() < 1e-6);
+        let row = explanation.contributions.last().unwrap();
+        assert_eq!(row.component, "contrast");
+        assert!(row.contribution < 0.0);
+        assert!(explanation.summary.contains("phủ định"));
+    }
+
+    #[test]
+    fn recorded_rule_keeps_its_own_score_row() {
+        let policy = policy();
+        let threshold = policy.thresholds.in_file;
+
+        for scored_by in [MatchRule::Rerank, MatchRule::OptionSet] {
+            // Cặp trùng nguyên văn hiển thị luật ExactText nhưng điểm do re-rank / so tập lựa chọn quyết định
+            let explanation = explain_match(
+                MatchRule::ExactText,
+                Some(scored_by),
+                ("Thủ đô của Pháp?", "thủ đô của pháp"),
+                Some(("Paris", "Paris")),
+                &components(),
+                0.97,
+                None,
+                threshold,
+                &policy,
+            );
+
+            assert_eq!(explanation.rule, MatchRule::ExactText);
+            assert_eq!(explanation.contributions.last().unwrap().component, scored_by.own_score().unwrap());
+            assert!((sum(&explanation) - 0.97).abs() < 1e-6);
+        }
+
+        let semantic = explain_match(
+            MatchRule::Semantic,
+            None,
+            ("Thủ đô của Pháp?", "Thành phố thủ đô nước Pháp?"),
+            None,
+            &components(),
+            combine_components(&components(), threshold, &policy),
+            None,
+            threshold,
+            &policy,
+        );
+        assert_eq!(semantic.contributions.len(), 2);
+        assert!((sum(&semantic) - semantic.score).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,14 @@
 pub mod process_docx;
 pub mod cosine_similarity;
 pub mod plot_similarity;
-pub mod load_accurancy;
\ No newline at end of file
+pub mod lexical_similarity;
+pub mod contrast_tokens;
+pub mod reranker;
+pub mod load_accurancy;
+pub mod embedder;
+pub mod embedding_model;
+pub mod embedding_cache;
+pub mod long_text;
+pub mod bank_matches;
+pub mod quantization;
+pub mod match_explanation;
@@ -1,8 +1,355 @@
-pub fn calculate_similarity_score(question_similarity: f32, answer_similarity: f32) -> f32 {
-    if question_similarity >= 0.5 && answer_similarity >= 0.5 {
-        (question_similarity + answer_similarity) / 2.0
-    } else {
-        question_similarity.min(answer_similarity)
+use crate::functions::contrast_tokens::{apply_contrast, compare_features, ContrastFeatures, ContrastFinding, ContrastPenalties};
+use crate::functions::cosine_similarity::calculate_cosine_similarity;
+use crate::functions::lexical_similarity::{estimate_jaccard, minhash_signature, MinHashSignature};
+use crate::functions::quantization::QuantizationSettings;
+use crate::service::settings::{Settings, ThresholdSettings};
+
+// Từ mức Jaccard này trở lên coi như chép lại gần nguyên văn
+pub const VERBATIM_JACCARD: f32 = 0.8;
+
+// Trọng số kết hợp điểm embedding và điểm từ vựng (MinHash), mục "HybridScoring" trong settings.json
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct HybridWeights {
+    #[serde(rename = "SemanticWeight")]
+    pub semantic: f32,
+    #[serde(rename = "LexicalWeight")]
+    pub lexical: f32,
+    pub shingle_size: usize,
+    pub num_hashes: usize,
+}
+
+impl Default for HybridWeights {
+    fn default() -> Self {
+        HybridWeights {
+            semantic: 0.7,
+            lexical: 0.3,
+            shingle_size: 5,
+            num_hashes: 128,
+        }
+    }
+}
+
+impl HybridWeights {
+    pub fn signature(&self, text: &str) -> MinHashSignature {
+        minhash_signature(text, self.shingle_size, self.num_hashes)
+    }
+}
+
+// Trộn điểm cosine với điểm Jaccard; không có văn bản để so (ví dụ bản ghi database) thì giữ nguyên cosine
+pub fn blend_similarity(semantic: f32, lexical: Option<f32>, weights: &HybridWeights) -> f32 {
+    match lexical {
+        Some(lexical) if weights.semantic + weights.lexical > 0.0 => {
+            let blended = (weights.semantic * semantic + weights.lexical * lexical)
+                / (weights.semantic + weights.lexical);
+            // Bản sao gần như nguyên văn không bị embedding kéo điểm xuống
+            if lexical >= VERBATIM_JACCARD {
+                blended.max(lexical)
+            } else {
+                blended
+            }
+        }
+        _ => semantic,
+    }
+}
+
+// Cách gộp điểm câu hỏi, đáp án và tập lựa chọn thành một điểm duy nhất
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CombinationRule {
+    // Trung bình có trọng số nếu mọi thành phần đạt Gate, ngược lại lấy thành phần thấp nhất
+    GatedAverage,
+    #[serde(alias = "average")]
+    WeightedAverage,
+    Min,
+    Max,
+}
+
+impl CombinationRule {
+    // Phần điểm mỗi thành phần góp vào điểm gộp, cùng thứ tự với `components`; tổng đúng bằng điểm gộp.
+    // Luật lấy một thành phần (Min, Max, GatedAverage không qua Gate) dồn toàn bộ điểm cho thành phần đó
+    fn contributions(&self, components: &[(f32, f32)], gate: f32) -> Vec<f32> {
+        let lowest = (0..components.len()).min_by(|&a, &b| components[a].0.total_cmp(&components[b].0));
+        let highest = (0..components.len()).max_by(|&a, &b| components[a].0.total_cmp(&components[b].0));
+        let min = lowest.map_or(f32::INFINITY, |index| components[index].0);
+        let total_weight: f32 = components.iter().map(|(_, weight)| weight).sum();
+
+        let only = |index: Option<usize>| {
+            let mut contributions = vec![0.0f32; components.len()];
+            if let Some(index) = index {
+                contributions[index] = components[index].0;
+            }
+            contributions
+        };
+        let average = || {
+            if total_weight > 0.0 {
+                components.iter().map(|(score, weight)| score * weight / total_weight).collect()
+            } else {
+                only(lowest)
+            }
+        };
+
+        match self {
+            CombinationRule::GatedAverage if min >= gate => average(),
+            CombinationRule::GatedAverage => only(lowest),
+            CombinationRule::WeightedAverage => average(),
+            CombinationRule::Min => only(lowest),
+            CombinationRule::Max => only(highest),
+        }
     }
 }
 
+// Trọng số và cách gộp điểm, mục "ScoringPolicy" trong settings.json
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct ScoringSettings {
+    pub stem_weight: f32,
+    pub answer_weight: f32,
+    pub option_weight: f32,
+    pub rule: CombinationRule,
+    // null: dùng ngưỡng của từng loại kiểm tra làm Gate
+    pub gate: Option<f32>,
+    pub bank_top_k: usize,
+}
+
+impl Default for ScoringSettings {
+    fn default() -> Self {
+        ScoringSettings {
+            stem_weight: 0.5,
+            answer_weight: 0.5,
+            option_weight: 0.0,
+            rule: CombinationRule::GatedAverage,
+            gate: None,
+            bank_top_k: 5,
+        }
+    }
+}
+
+// Chính sách chấm điểm dùng chung cho process_docx, fill_format_check và các hàm kiểm tra trong middleware,
+// lấy từ settings.json đã đọc một lần cho cả lượt kiểm tra
+#[derive(Debug, Clone)]
+pub struct ScoringPolicy {
+    pub stem_weight: f32,
+    pub answer_weight: f32,
+    pub option_weight: f32,
+    pub rule: CombinationRule,
+    // Không cấu hình Gate thì mỗi thành phần phải đạt chính ngưỡng của loại kiểm tra đang chấm,
+    // giữ đúng quy tắc cũ "câu hỏi > ngưỡng && đáp án > ngưỡng"
+    pub gate: Option<f32>,
+    pub thresholds: ThresholdSettings,
+    pub hybrid: HybridWeights,
+    // Mức giảm điểm khi hai câu khác nhau ở phủ định, số liệu hoặc token mã
+    pub contrast: ContrastPenalties,
+    // Số câu gần nhất trong ngân hàng trả về cho mỗi câu hỏi
+    pub bank_top_k: usize,
+    // Lọc nhanh ứng viên trong ngân hàng bằng embedding lượng tử hóa
+    pub quantization: QuantizationSettings,
+}
+
+impl ScoringPolicy {
+    // Môn học đã được hiệu chỉnh ngưỡng riêng thì dùng ngưỡng của môn đó
+    pub fn new(settings: &Settings, subject: Option<&str>) -> Self {
+        let scoring = &settings.scoring_policy;
+        ScoringPolicy {
+            stem_weight: scoring.stem_weight,
+            answer_weight: scoring.answer_weight,
+            option_weight: scoring.option_weight,
+            rule: scoring.rule,
+            gate: scoring.gate,
+            thresholds: settings.thresholds_for(subject),
+            hybrid: settings.hybrid_scoring.clone(),
+            contrast: settings.contrast_penalty.clone(),
+            bank_top_k: scoring.bank_top_k,
+            quantization: settings.quantization.clone(),
+        }
+    }
+
+    // Gate dùng khi điểm sẽ được so với `threshold`
+    pub fn gate_for(&self, threshold: f32) -> f32 {
+        self.gate.unwrap_or(threshold)
+    }
+}
+
+// Độ tương đồng giữa hai tập lựa chọn, không phụ thuộc thứ tự: trung bình điểm ghép cặp tốt nhất theo cả hai chiều
+pub fn option_set_similarity(options1: &[Vec<f32>], options2: &[Vec<f32>]) -> Option<f32> {
+    let options1: Vec<&Vec<f32>> = options1.iter().filter(|emb| !emb.is_empty()).collect();
+    let options2: Vec<&Vec<f32>> = options2.iter().filter(|emb| !emb.is_empty()).collect();
+    if options1.is_empty() || options2.is_empty() {
+        return None;
+    }
+
+    let best_match = |from: &[&Vec<f32>], to: &[&Vec<f32>]| {
+        from.iter()
+            .map(|a| {
+                to.iter()
+                    .map(|b| calculate_cosine_similarity(a, b))
+                    .fold(f32::NEG_INFINITY, f32::max)
+            })
+            .sum::<f32>()
+            / from.len() as f32
+    };
+
+    Some((best_match(&options1, &options2) + best_match(&options2, &options1)) / 2.0)
+}
+
+// Một thành phần của điểm tổng hợp: "stem", "answer" hoặc "options"
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ScoreComponent {
+    pub component: &'static str,
+    pub similarity: f32,
+    pub weight: f32,
+}
+
+// Các thành phần đã trộn điểm từ vựng, theo đúng thứ tự và trọng số mà calculate_similarity_score dùng
+pub fn score_components(
+    question_similarity: f32,
+    answer_similarity: f32,
+    options: Option<f32>,
+    lexical: Option<(f32, f32)>,
+    policy: &ScoringPolicy,
+) -> Vec<ScoreComponent> {
+    let mut components = vec![
+        ScoreComponent {
+            component: "stem",
+            similarity: blend_similarity(question_similarity, lexical.map(|(q, _)| q), &policy.hybrid),
+            weight: policy.stem_weight,
+        },
+        ScoreComponent {
+            component: "answer",
+            similarity: blend_similarity(answer_similarity, lexical.map(|(_, a)| a), &policy.hybrid),
+            weight: policy.answer_weight,
+        },
+    ];
+    if let Some(options) = options {
+        if policy.option_weight > 0.0 {
+            components.push(ScoreComponent {
+                component: "options",
+                similarity: options,
+                weight: policy.option_weight,
+            });
+        }
+    }
+    components
+}
+
+// Phần điểm của từng thành phần theo luật gộp của `policy`, cùng thứ tự với `components`
+pub fn component_contributions(components: &[ScoreComponent], threshold: f32, policy: &ScoringPolicy) -> Vec<f32> {
+    let pairs: Vec<(f32, f32)> = components.iter().map(|c| (c.similarity, c.weight)).collect();
+    policy.rule.contributions(&pairs, policy.gate_for(threshold))
+}
+
+// `threshold` là ngưỡng của loại kiểm tra sẽ so với điểm này (policy.thresholds.in_file, .database, ...)
+pub fn combine_components(components: &[ScoreComponent], threshold: f32, policy: &ScoringPolicy) -> f32 {
+    component_contributions(components, threshold, policy).iter().sum()
+}
+
+// `options` là độ tương đồng tập lựa chọn, `lexical` là điểm Jaccard (câu hỏi, đáp án);
+// mỗi phần là None khi không có dữ liệu của cả hai phía (ví dụ bản ghi database chỉ có embedding)
+pub fn calculate_similarity_score(
+    question_similarity: f32,
+    answer_similarity: f32,
+    options: Option<f32>,
+    lexical: Option<(f32, f32)>,
+    threshold: f32,
+    policy: &ScoringPolicy,
+) -> f32 {
+    let components = score_components(question_similarity, answer_similarity, options, lexical, policy);
+    combine_components(&components, threshold, policy)
+}
+
+// Chữ ký MinHash (câu hỏi, đáp án) và đặc trưng khác biệt của một câu, tính một lần cho mỗi câu
+pub struct PairSide<'a> {
+    pub signatures: &'a (MinHashSignature, MinHashSignature),
+    pub features: &'a ContrastFeatures,
+}
+
+// Các thành phần điểm của cặp câu trong file, khác biệt phủ định, số liệu, token mã (nếu có) và điểm
+// Jaccard phần dẫn. Dùng chung cho fill_format_check và hiệu chỉnh ngưỡng để hai nơi chấm giống nhau
+pub fn file_pair_components(
+    question_similarity: f32,
+    answer_similarity: f32,
+    options: Option<f32>,
+    sides: (PairSide, PairSide),
+    policy: &ScoringPolicy,
+) -> (Vec<ScoreComponent>, Option<ContrastFinding>, f32) {
+    let (a, b) = sides;
+    let lexical = (
+        estimate_jaccard(&a.signatures.0, &b.signatures.0),
+        estimate_jaccard(&a.signatures.1, &b.signatures.1),
+    );
+    let components = score_components(question_similarity, answer_similarity, options, Some(lexical), policy);
+    let contrast = compare_features(a.features, b.features, &policy.contrast);
+    (components, contrast, lexical.0)
+}
+
+// Điểm của cặp câu trong file sau khi giảm theo khác biệt và điểm gốc (so với ngưỡng in_file)
+pub fn file_pair_score(components: &[ScoreComponent], contrast: Option<&ContrastFinding>, policy: &ScoringPolicy) -> (f32, f32) {
+    let score = combine_components(components, policy.thresholds.in_file, policy);
+    (apply_contrast(score, contrast), score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn verbatim_copies_keep_lexical_score() {
+        let weights = HybridWeights::default();
+        assert!(close(blend_similarity(0.5, Some(0.9), &weights), 0.9));
+        assert!(close(blend_similarity(0.5, Some(0.5), &weights), 0.5));
+        assert!(close(blend_similarity(0.42, None, &weights), 0.42));
+    }
+
+    #[test]
+    fn policy_rule_combines_stem_answer_and_options() {
+        let settings = Settings::default();
+        let expected = [
+            (CombinationRule::GatedAverage, 0.8),
+            (CombinationRule::WeightedAverage, 0.8),
+            (CombinationRule::Min, 0.7),
+            (CombinationRule::Max, 0.9),
+        ];
+        for (rule, score) in expected {
+            let mut policy = ScoringPolicy::new(&settings, None);
+            policy.rule = rule;
+            policy.option_weight = 0.5;
+            let combined = calculate_similarity_score(0.9, 0.7, Some(0.8), None, 0.6, &policy);
+            assert!(close(combined, score), "{:?}: {}", rule, combined);
+        }
+    }
+
+    #[test]
+    fn option_sets_match_regardless_of_order() {
+        let a = vec![vec![1.0, 0.0], vec![0.0, 1.0], Vec::new()];
+        let b = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+        assert!(close(option_set_similarity(&a, &b).unwrap(), 1.0));
+        assert!(option_set_similarity(&a, &[]).is_none());
+    }
+
+    #[test]
+    fn gated_average_averages_only_when_every_component_passes() {
+        let components = [(0.8, 0.5), (0.6, 0.5)];
+        let passed = CombinationRule::GatedAverage.contributions(&components, 0.5);
+        assert!(close(passed[0], 0.4) && close(passed[1], 0.3));
+
+        let failed = CombinationRule::GatedAverage.contributions(&components, 0.7);
+        assert_eq!(failed, vec![0.0, 0.6]);
+    }
+
+    #[test]
+    fn single_component_rules_credit_that_component() {
+        let components = [(0.9, 1.0), (0.3, 1.0), (0.5, 0.0)];
+        assert_eq!(CombinationRule::Min.contributions(&components, 0.0), vec![0.0, 0.3, 0.0]);
+        assert_eq!(CombinationRule::Max.contributions(&components, 0.0), vec![0.9, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn zero_weights_fall_back_to_lowest_component() {
+        let contributions = CombinationRule::WeightedAverage.contributions(&[(0.9, 0.0), (0.4, 0.0)], 0.0);
+        assert_eq!(contributions, vec![0.0, 0.4]);
+    }
+}
@@ -3,31 +3,28 @@ use docx_rust::document::{
     BodyContent, ParagraphContent, RunContent, Table, TableCell, TableCellContent, TableRowContent,
 };
 use docx_rust::DocxFile;
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::{io::Cursor, sync::LazyLock};
-use std::path::PathBuf;
+use std::io::Cursor;
+
+use crate::functions::embedding_cache::{embed_texts, normalize_text};
+use crate::functions::embedding_model::{load_model_for, SelectedModel};
+use crate::functions::long_text::chunk_report;
+use crate::service::progress::JobContext;
+use crate::service::settings::Settings;
 
 #[derive(Debug)]
 pub struct Question {
-    #[allow(dead_code)]
     pub id: String,
     pub text: String,
     pub correct_answer_text: String,
     pub question_embedding: Vec<f32>,
     pub answer_embedding: Vec<f32>,
+    // Số đoạn phần dẫn được chia khi embed, 1 nếu nằm gọn trong cửa sổ token của model
+    pub stem_chunks: usize,
+    // Phần dẫn (hoặc một đoạn của nó) vẫn dài hơn cửa sổ token: model chỉ thấy phần đầu
+    pub truncated: bool,
 }
 
-static MODEL: LazyLock<TextEmbedding> = LazyLock::new(|| {
-    let mut options = InitOptions::default();
-    options.model_name = EmbeddingModel::AllMiniLML6V2;
-    options.show_download_progress = true;
-    options.cache_dir = PathBuf::from("FUC-mini");
-        
-    TextEmbedding::try_new(options)
-        .expect("Failed to init embedding model")
-});
-
 fn get_table_cell_content(cell_data: &TableCell<'_>) -> String {
     cell_data
         .content
@@ -57,6 +54,8 @@ fn parse_table(table: &Table<'_>) -> Result<Question> {
         correct_answer_text: String::new(),
         question_embedding: Vec::new(),
         answer_embedding: Vec::new(),
+        stem_chunks: 1,
+        truncated: false,
     };
 
     let mut answer_texts: std::collections::HashMap<String, String> =
@@ -72,6 +71,17 @@ fn parse_table(table: &Table<'_>) -> Result<Question> {
         })
         .ok_or(anyhow!("File sai format: Thiếu nội dung câu hỏi"))?;
 
+    question.id = table
+        .rows
+        .first()
+        .and_then(|first_row| first_row.cells.first())
+        .and_then(|cell| match cell {
+            TableRowContent::TableCell(cell_data) => Some(get_table_cell_content(cell_data)),
+            _ => None,
+        })
+        .and_then(|cell_text| cell_text.strip_prefix("QN=").map(|id| id.trim().to_string()))
+        .unwrap_or_default();
+
     for row in table.rows.iter() {
         let first_cell_text = match &row.cells.first() {
             Some(TableRowContent::TableCell(cell_data)) => get_table_cell_content(cell_data),
@@ -104,33 +114,17 @@ fn parse_table(table: &Table<'_>) -> Result<Question> {
         }
     }
 
-    if !question.text.is_empty() {
-        let question_embeddings = MODEL.embed(vec![&question.text], None)?;
-        question.question_embedding = question_embeddings
-            .into_iter()
-            .next()
-            .expect("Failed to calculate question embedding");
-    }
-
-    if !question.correct_answer_text.is_empty() {
-        let answer_embeddings = MODEL.embed(vec![&question.correct_answer_text], None)?;
-        question.answer_embedding = answer_embeddings
-            .into_iter()
-            .next()
-            .expect("Failed to calculate answer embedding");
-    }
-
     Ok(question)
 }
 
-pub fn read_docx_content_from_bytes(bytes: &[u8]) -> Result<Vec<Question>> {
+// Đọc các bảng câu hỏi theo thứ tự trong file, chưa tạo embedding
+pub fn parse_docx_questions(bytes: &[u8]) -> Result<Vec<Question>> {
     let cursor = Cursor::new(bytes);
 
     let docx = DocxFile::from_reader(cursor)?;
     let docx = docx.parse()?;
 
-    let questions = docx
-        .document
+    docx.document
         .body
         .content
         .par_iter()
@@ -139,7 +133,56 @@ pub fn read_docx_content_from_bytes(bytes: &[u8]) -> Result<Vec<Question>> {
             _ => None,
         })
         .map(|table| parse_table(table))
-        .collect::<Result<Vec<_>>>()?;
+        .collect::<Result<Vec<_>>>()
+}
+
+pub fn read_docx_content_from_bytes(
+    bytes: &[u8],
+    subject: Option<&str>,
+    settings: &Settings,
+    job: &JobContext,
+) -> Result<(Vec<Question>, SelectedModel)> {
+    job.report("parsing", 0, 1);
+    let mut questions = parse_docx_questions(bytes)?;
+    job.report("parsing", 1, 1);
+    job.check_cancelled().map_err(|e| anyhow!(e))?;
+
+    let sample_text = questions
+        .iter()
+        .map(|q| q.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let model = load_model_for(settings, subject, &sample_text).map_err(|e| anyhow!(e))?;
+
+    // Gom toàn bộ câu hỏi và đáp án của file để embed theo batch
+    let mut texts: Vec<&str> = Vec::new();
+    let mut targets: Vec<(usize, bool)> = Vec::new();
+    for (i, question) in questions.iter().enumerate() {
+        if !question.text.is_empty() {
+            texts.push(&question.text);
+            targets.push((i, true));
+        }
+        if !question.correct_answer_text.is_empty() {
+            texts.push(&question.correct_answer_text);
+            targets.push((i, false));
+        }
+    }
+
+    let embeddings = embed_texts(&model, &texts, settings, job).map_err(|e| anyhow!(e))?;
+
+    for question in questions.iter_mut().filter(|q| !q.text.is_empty()) {
+        let report = chunk_report(&model, &normalize_text(&question.text), &settings.long_text);
+        question.stem_chunks = report.chunks;
+        question.truncated = report.truncated;
+    }
+
+    for ((i, is_question), embedding) in targets.into_iter().zip(embeddings) {
+        if is_question {
+            questions[i].question_embedding = embedding;
+        } else {
+            questions[i].answer_embedding = embedding;
+        }
+    }
 
-    Ok(questions)
+    Ok((questions, model))
 }
\ No newline at end of file
@@ -0,0 +1,339 @@
+use crate::functions::cosine_similarity::NormalizedMatrix;
+use rayon::prelude::*;
+
+// Cách lượng tử hóa embedding cho lượt lọc nhanh ứng viên trong ngân hàng câu hỏi
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuantizationMode {
+    // Mỗi chiều một số nguyên 8 bit, nhỏ hơn f32 bốn lần
+    Int8,
+    // Mỗi chiều một bit dấu, nhỏ hơn f32 ba mươi hai lần
+    Binary,
+}
+
+// Mục "Quantization" trong settings.json; Mode là null thì quét chính xác như cũ.
+// Bật lượng tử hóa thì mỗi câu lưu thêm một bản mã (384 chiều: 768 byte với int8, 96 byte với binary,
+// so với 3 KB của hai vector f32); lượt kiểm tra chỉ đọc bản mã của cả ngân hàng, vector f32 và nội dung
+// chỉ được đọc cho các ứng viên
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct QuantizationSettings {
+    pub mode: Option<QuantizationMode>,
+    // Số ứng viên giữ lại sau lượt lọc nhanh để chấm lại bằng vector f32 đầy đủ
+    pub shortlist: usize,
+    // Quét thêm một lượt chính xác để đo tỉ lệ câu gần nhất bị bỏ sót do lượng tử hóa
+    pub measure_recall: bool,
+}
+
+impl Default for QuantizationSettings {
+    fn default() -> Self {
+        QuantizationSettings {
+            mode: None,
+            shortlist: 100,
+            measure_recall: false,
+        }
+    }
+}
+
+impl QuantizationMode {
+    // Giá trị cột code_mode trong database, trùng với tên trong settings.json
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuantizationMode::Int8 => "int8",
+            QuantizationMode::Binary => "binary",
+        }
+    }
+
+    // Đọc lại tên trong cột code_mode theo đúng cách settings.json đọc Quantization.Mode
+    pub fn parse(name: &str) -> Option<QuantizationMode> {
+        serde_json::from_value(serde_json::Value::String(name.trim().to_lowercase())).ok()
+    }
+}
+
+// Bản lượng tử hóa của một embedding, lưu kèm vector f32 trong database
+#[derive(Debug, Clone)]
+pub enum QuantizedCode {
+    Int8(Vec<i8>),
+    Binary(Vec<u64>),
+}
+
+impl QuantizedCode {
+    // Dạng BLOB lưu trong database: mỗi chiều int8 một byte, mỗi từ 64 bit tám byte little-endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            QuantizedCode::Int8(values) => values.iter().map(|value| *value as u8).collect(),
+            QuantizedCode::Binary(words) => words.iter().flat_map(|word| word.to_le_bytes()).collect(),
+        }
+    }
+
+    // None khi BLOB không đúng định dạng của `mode`
+    pub fn from_bytes(bytes: &[u8], mode: QuantizationMode) -> Option<Self> {
+        match mode {
+            QuantizationMode::Int8 => Some(QuantizedCode::Int8(bytes.iter().map(|byte| *byte as i8).collect())),
+            QuantizationMode::Binary if bytes.len().is_multiple_of(8) => Some(QuantizedCode::Binary(
+                bytes
+                    .chunks(8)
+                    .map(|chunk| {
+                        let mut word = [0u8; 8];
+                        word.copy_from_slice(chunk);
+                        u64::from_le_bytes(word)
+                    })
+                    .collect(),
+            )),
+            QuantizationMode::Binary => None,
+        }
+    }
+}
+
+// Chia theo giá trị tuyệt đối lớn nhất để dùng hết khoảng -127..127; cosine không phụ thuộc độ dài
+// vector nên không cần lưu hệ số chia
+pub fn quantize_int8(vector: &[f32]) -> Vec<i8> {
+    let max = vector.iter().fold(0.0f32, |max, value| max.max(value.abs()));
+    if max == 0.0 {
+        return vec![0; vector.len()];
+    }
+    vector.iter().map(|value| (value / max * 127.0).round() as i8).collect()
+}
+
+// Bit thứ `i` bật khi chiều thứ `i` dương, 64 chiều mỗi từ
+pub fn quantize_binary(vector: &[f32]) -> Vec<u64> {
+    vector
+        .chunks(64)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| **value > 0.0)
+                .fold(0u64, |word, (bit, _)| word | (1u64 << bit))
+        })
+        .collect()
+}
+
+pub fn quantize(vector: &[f32], mode: QuantizationMode) -> QuantizedCode {
+    match mode {
+        QuantizationMode::Int8 => QuantizedCode::Int8(quantize_int8(vector)),
+        QuantizationMode::Binary => QuantizedCode::Binary(quantize_binary(vector)),
+    }
+}
+
+// Các bản lượng tử hóa xếp liền nhau theo hàng, chỉ dùng để xếp hạng ứng viên: điểm xấp xỉ cosine
+// nhưng không đủ chính xác để so với ngưỡng. Hàng không hợp lệ (khác số chiều) có điểm 0 với mọi hàng
+pub enum QuantizedMatrix {
+    Int8 {
+        dimension: usize,
+        codes: Vec<i8>,
+        norms: Vec<f32>,
+    },
+    Binary {
+        dimension: usize,
+        words: usize,
+        codes: Vec<u64>,
+        valid: Vec<bool>,
+    },
+}
+
+impl QuantizedMatrix {
+    // Lượng tử hóa từng hàng của ma trận đã chuẩn hóa (embedding của file)
+    pub fn from_matrix(matrix: &NormalizedMatrix, mode: QuantizationMode) -> Self {
+        let codes: Vec<QuantizedCode> = (0..matrix.len())
+            .into_par_iter()
+            .map(|index| quantize(matrix.row(index), mode))
+            .collect();
+        QuantizedMatrix::from_codes(&codes, mode, matrix.dimension())
+    }
+
+    // Bản lượng tử hóa đọc từ database; `dimension` là số chiều của embedding gốc
+    pub fn from_codes<'a>(
+        codes: impl IntoIterator<Item = &'a QuantizedCode>,
+        mode: QuantizationMode,
+        dimension: usize,
+    ) -> Self {
+        match mode {
+            QuantizationMode::Int8 => {
+                let mut flat = Vec::new();
+                let mut norms = Vec::new();
+                for code in codes {
+                    match code {
+                        QuantizedCode::Int8(values) if values.len() == dimension => {
+                            norms.push((int8_dot(values, values) as f32).sqrt());
+                            flat.extend_from_slice(values);
+                        }
+                        _ => {
+                            norms.push(0.0);
+                            flat.resize(flat.len() + dimension, 0);
+                        }
+                    }
+                }
+                QuantizedMatrix::Int8 {
+                    dimension,
+                    codes: flat,
+                    norms,
+                }
+            }
+            QuantizationMode::Binary => {
+                let words = dimension.div_ceil(64);
+                let mut flat = Vec::new();
+                let mut valid = Vec::new();
+                for code in codes {
+                    match code {
+                        QuantizedCode::Binary(values) if values.len() == words => {
+                            valid.push(true);
+                            flat.extend_from_slice(values);
+                        }
+                        _ => {
+                            valid.push(false);
+                            flat.resize(flat.len() + words, 0);
+                        }
+                    }
+                }
+                QuantizedMatrix::Binary {
+                    dimension,
+                    words,
+                    codes: flat,
+                    valid,
+                }
+            }
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        match self {
+            QuantizedMatrix::Int8 { dimension, .. } | QuantizedMatrix::Binary { dimension, .. } => *dimension,
+        }
+    }
+
+    // Điểm xấp xỉ của các hàng `rows` với toàn bộ hàng của `other`, xếp theo hàng như
+    // `NormalizedMatrix::block_similarities`
+    pub fn block_similarities(&self, rows: std::ops::Range<usize>, other: &QuantizedMatrix) -> Vec<f32> {
+        let columns = other.len();
+        let mut scores = vec![0.0f32; rows.len() * columns];
+        if self.dimension() != other.dimension() {
+            return scores;
+        }
+
+        match (self, other) {
+            (
+                QuantizedMatrix::Int8 { dimension, codes, norms },
+                QuantizedMatrix::Int8 { codes: other_codes, norms: other_norms, .. },
+            ) => {
+                for column in 0..columns {
+                    let other_row = &other_codes[column * dimension..(column + 1) * dimension];
+                    for (offset, index) in rows.clone().enumerate() {
+                        let norm = norms[index] * other_norms[column];
+                        if norm > 0.0 {
+                            let row = &codes[index * dimension..(index + 1) * dimension];
+                            scores[offset * columns + column] = int8_dot(row, other_row) as f32 / norm;
+                        }
+                    }
+                }
+            }
+            (
+                QuantizedMatrix::Binary { dimension, words, codes, valid },
+                QuantizedMatrix::Binary { codes: other_codes, valid: other_valid, .. },
+            ) => {
+                // Tỉ lệ chiều khác dấu quy về khoảng -1..1 giống cosine
+                for column in (0..columns).filter(|column| other_valid[*column]) {
+                    let other_row = &other_codes[column * words..(column + 1) * words];
+                    for (offset, index) in rows.clone().enumerate().filter(|(_, index)| valid[*index]) {
+                        let row = &codes[index * words..(index + 1) * words];
+                        let differing: u32 = row.iter().zip(other_row).map(|(a, b)| (a ^ b).count_ones()).sum();
+                        if *dimension > 0 {
+                            scores[offset * columns + column] = 1.0 - 2.0 * differing as f32 / *dimension as f32;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        scores
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            QuantizedMatrix::Int8 { norms, .. } => norms.len(),
+            QuantizedMatrix::Binary { valid, .. } => valid.len(),
+        }
+    }
+}
+
+fn int8_dot(a: &[i8], b: &[i8]) -> i32 {
+    a.iter().zip(b).map(|(x, y)| *x as i32 * *y as i32).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int8_scales_by_largest_magnitude() {
+        assert_eq!(quantize_int8(&[0.5, -1.0, 0.25, 0.0]), vec![64, -127, 32, 0]);
+        assert_eq!(quantize_int8(&[0.0, 0.0]), vec![0, 0]);
+    }
+
+    #[test]
+    fn binary_sets_bits_for_positive_dimensions() {
+        let mut vector = vec![-1.0f32; 70];
+        vector[0] = 0.3;
+        vector[3] = 0.1;
+        vector[65] = 2.0;
+        assert_eq!(quantize_binary(&vector), vec![0b1001, 0b10]);
+    }
+
+    #[test]
+    fn codes_round_trip_through_bytes() {
+        let int8 = QuantizedCode::Int8(vec![-127, 0, 5, 127]);
+        match QuantizedCode::from_bytes(&int8.to_bytes(), QuantizationMode::Int8) {
+            Some(QuantizedCode::Int8(values)) => assert_eq!(values, vec![-127, 0, 5, 127]),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let binary = QuantizedCode::Binary(vec![u64::MAX, 0x0102_0304_0506_0708]);
+        let bytes = binary.to_bytes();
+        assert_eq!(bytes.len(), 16);
+        match QuantizedCode::from_bytes(&bytes, QuantizationMode::Binary) {
+            Some(QuantizedCode::Binary(words)) => assert_eq!(words, vec![u64::MAX, 0x0102_0304_0506_0708]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(QuantizedCode::from_bytes(&[1, 2, 3], QuantizationMode::Binary).is_none());
+    }
+
+    #[test]
+    fn quantized_scores_approximate_cosine() {
+        let rows = [vec![1.0f32, -1.0, 1.0, -1.0], vec![0.9, -0.8, 0.7, 0.2], vec![-1.0, 1.0, -1.0, 1.0]];
+        for mode in [QuantizationMode::Int8, QuantizationMode::Binary] {
+            let codes: Vec<QuantizedCode> = rows.iter().map(|row| quantize(row, mode)).collect();
+            let matrix = QuantizedMatrix::from_codes(&codes, mode, 4);
+            let scores = matrix.block_similarities(0..1, &matrix);
+
+            assert_eq!(matrix.len(), 3);
+            assert!((scores[0] - 1.0).abs() < 1e-3, "{:?} {:?}", mode, scores);
+            assert!(scores[1] > 0.4, "{:?} {:?}", mode, scores);
+            assert!(scores[2] < 0.0, "{:?} {:?}", mode, scores);
+        }
+    }
+
+    #[test]
+    fn rows_with_wrong_dimension_score_zero() {
+        let codes = vec![QuantizedCode::Int8(vec![1, 2, 3, 4]), QuantizedCode::Int8(vec![1, 2])];
+        let matrix = QuantizedMatrix::from_codes(&codes, QuantizationMode::Int8, 4);
+        let scores = matrix.block_similarities(0..2, &matrix);
+        assert_eq!(scores[1], 0.0);
+        assert_eq!(scores[3], 0.0);
+
+        let binary = vec![QuantizedCode::Int8(vec![1, 2, 3, 4])];
+        let matrix = QuantizedMatrix::from_codes(&binary, QuantizationMode::Binary, 4);
+        assert_eq!(matrix.block_similarities(0..1, &matrix), vec![0.0]);
+    }
+
+    #[test]
+    fn parse_reads_stored_mode_names() {
+        for mode in [QuantizationMode::Int8, QuantizationMode::Binary] {
+            assert_eq!(QuantizationMode::parse(mode.name()), Some(mode));
+        }
+        assert_eq!(QuantizationMode::parse(" INT8 "), Some(QuantizationMode::Int8));
+        assert_eq!(QuantizationMode::parse("float16"), None);
+
+        let empty = QuantizedMatrix::from_codes(&Vec::<QuantizedCode>::new(), QuantizationMode::Int8, 4);
+        assert_eq!(empty.len(), 0);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::functions::embedding_model::ModelSlots;
+use fastembed::{RerankInitOptions, RerankerModel, TextRerank};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+
+pub const DEFAULT_RERANKER_NAME: &str = "bge-reranker-base";
+
+// Reranker đã load, dùng chung cho mọi lần kiểm tra giống LOADED_MODELS của embedding
+static LOADED_RERANKERS: LazyLock<ModelSlots<Arc<TextRerank>>> = LazyLock::new(ModelSlots::new);
+
+// Cấu hình bước re-rank, mục "Reranker" trong settings.json; mặc định tắt để kiểm tra nhanh
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct RerankSettings {
+    pub enabled: bool,
+    pub model: String,
+    pub top_k: usize,
+    pub threshold: f32,
+}
+
+impl Default for RerankSettings {
+    fn default() -> Self {
+        RerankSettings {
+            enabled: false,
+            model: DEFAULT_RERANKER_NAME.to_string(),
+            top_k: 5,
+            threshold: 0.5,
+        }
+    }
+}
+
+pub fn parse_reranker_name(name: &str) -> Option<RerankerModel> {
+    match name.trim().to_lowercase().as_str() {
+        "bge-reranker-base" => Some(RerankerModel::BGERerankerBase),
+        "bge-reranker-v2-m3" => Some(RerankerModel::BGERerankerV2M3),
+        "jina-reranker-v1-turbo-en" => Some(RerankerModel::JINARerankerV1TurboEn),
+        "jina-reranker-v2-base-multilingual" => Some(RerankerModel::JINARerankerV2BaseMultiligual),
+        _ => None,
+    }
+}
+
+// `model_cache` là thư mục tải model (Paths.ModelCache)
+pub fn get_reranker(name: &str, model_cache: &str) -> Result<Arc<TextRerank>, String> {
+    let key = name.trim().to_lowercase();
+    let model_name = parse_reranker_name(&key)
+        .ok_or_else(|| format!("Model reranker không được hỗ trợ: {}", name))?;
+
+    LOADED_RERANKERS.get_or_load(&key, || {
+        let options = RerankInitOptions::new(model_name)
+            .with_cache_dir(PathBuf::from(model_cache))
+            .with_show_download_progress(true);
+
+        let reranker = std::panic::catch_unwind(|| TextRerank::try_new(options))
+            .map_err(|_| format!("Khởi tạo reranker {} bị lỗi nghiêm trọng", name))?
+            .map_err(|e| format!("Không thể khởi tạo reranker {}: {}", name, e))?;
+        Ok(Arc::new(reranker))
+    })
+}
+
+// Reranker theo mục "Reranker" trong settings.json; None khi tắt hoặc không load được, lúc đó dùng điểm cosine
+pub fn load_reranker(settings: &RerankSettings, model_cache: &str) -> Option<Arc<TextRerank>> {
+    if !settings.enabled {
+        return None;
+    }
+    match get_reranker(&settings.model, model_cache) {
+        Ok(reranker) => Some(reranker),
+        Err(e) => {
+            println!("Bỏ qua bước re-rank: {}", e);
+            None
+        }
+    }
+}
+
+// `top_k` ứng viên đầu đưa vào cross-encoder; `candidates` đã xếp giảm dần theo điểm cosine
+pub fn shortlist<'c>(candidates: &'c [(usize, f32)], settings: &RerankSettings) -> &'c [(usize, f32)] {
+    &candidates[..settings.top_k.min(candidates.len())]
+}
+
+// Các ứng viên có điểm re-rank vượt ngưỡng, kèm điểm đó; `scores` cùng thứ tự với `shortlist`
+pub fn reranked_matches(shortlist: &[(usize, f32)], scores: Vec<f32>, settings: &RerankSettings) -> Vec<(usize, f32)> {
+    shortlist
+        .iter()
+        .zip(scores)
+        .filter(|(_, score)| *score > settings.threshold)
+        .map(|((j, _), score)| (*j, score))
+        .collect()
+}
+
+// Câu hỏi kèm đáp án đúng (nối bằng ", "), là văn bản đưa vào cross-encoder
+pub fn rerank_text(question: &str, answers: &str) -> String {
+    format!("{}\n{}", question, answers)
+}
+
+// Điểm cross-encoder (đưa về 0..1 bằng sigmoid) của từng ứng viên so với câu truy vấn, theo đúng thứ tự đầu vào
+pub fn rerank_candidates(reranker: &TextRerank, query: &str, candidates: &[&str]) -> Result<Vec<f32>, String> {
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let results = reranker
+        .rerank(query, candidates.to_vec(), false, None)
+        .map_err(|e| format!("Lỗi khi re-rank: {}", e))?;
+
+    let mut scores = vec![0.0; candidates.len()];
+    for result in results {
+        if let Some(score) = scores.get_mut(result.index) {
+            *score = 1.0 / (1.0 + (-result.score).exp());
+        }
+    }
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(top_k: usize) -> RerankSettings {
+        RerankSettings {
+            enabled: true,
+            top_k,
+            ..RerankSettings::default()
+        }
+    }
+
+    #[test]
+    fn shortlist_keeps_the_top_k_candidates() {
+        let candidates = [(3, 0.9), (1, 0.8), (4, 0.4), (0, 0.1)];
+
+        assert_eq!(shortlist(&candidates, &settings(2)), &candidates[..2]);
+        assert_eq!(shortlist(&candidates, &settings(10)), &candidates[..]);
+        assert!(shortlist(&candidates, &settings(0)).is_empty());
+        assert!(shortlist(&[], &settings(5)).is_empty());
+    }
+
+    #[test]
+    fn only_shortlisted_candidates_above_threshold_match() {
+        let candidates = [(3, 0.9), (1, 0.8), (4, 0.7), (0, 0.65)];
+        let settings = settings(3);
+        let top = shortlist(&candidates, &settings);
+
+        // Điểm re-rank thay điểm cosine: câu 3 có cosine cao nhất nhưng cross-encoder cho điểm dưới ngưỡng
+        let matches = reranked_matches(top, vec![0.2, 0.95, 0.51], &settings);
+        assert_eq!(matches, vec![(1, 0.95), (4, 0.51)]);
+        assert!(reranked_matches(top, Vec::new(), &settings).is_empty());
+    }
+
+    #[test]
+    fn disabled_or_unknown_reranker_is_skipped() {
+        assert!(load_reranker(&RerankSettings::default(), "").is_none());
+
+        let unknown = RerankSettings {
+            model: "cross-encoder-unknown".to_string(),
+            ..settings(5)
+        };
+        assert!(load_reranker(&unknown, "").is_none());
+        assert!(matches!(get_reranker(&unknown.model, ""), Err(e) if e.contains("cross-encoder-unknown")));
+
+        assert!(parse_reranker_name(" BGE-Reranker-Base ").is_some());
+        assert!(parse_reranker_name("").is_none());
+    }
+}
@@ -7,42 +7,82 @@ mod middleware;
 
 use crate::functions::process_docx::read_docx_content_from_bytes;
 use crate::database::createdb::create_database;
-use crate::database::insertdb::insert_embeddings;
-use crate::service::querydb::query_db;
-use crate::functions::cosine_similarity::calculate_cosine_similarity;
-use crate::functions::plot_similarity::calculate_similarity_score;
-use crate::middleware::check_duplicate_answers::{check_duplicate_answers, check_duplicates_within_question};
-use crate::functions::load_accurancy::load_similarity_threshold;
+use crate::database::insertdb::{backfill_quantized_embeddings, insert_embeddings_batch, BankRecord};
+use crate::service::querydb::{query_chunking_pending, query_question_ids};
+use crate::functions::cosine_similarity::NormalizedMatrix;
+use crate::functions::plot_similarity::{calculate_similarity_score, file_pair_components, file_pair_score, option_set_similarity, score_components, PairSide, ScoreComponent, ScoringPolicy};
+use crate::functions::match_explanation::{detect_rule, explain_match, MatchRule};
+use crate::functions::lexical_similarity::estimate_jaccard;
+use crate::functions::contrast_tokens::{apply_contrast, compare_features, ContrastFeatures, ContrastFinding};
+use crate::functions::bank_matches::{embed_for_bank_models, measure_recall, nearest_bank_matches, refresh_chunked_bank_rows, scan_bank, BankIndex, BankMatch, BankRows, FileEmbeddings};
+use crate::functions::reranker::{load_reranker, rerank_candidates, rerank_text, reranked_matches, shortlist};
+use crate::middleware::check_duplicate_answers::{check_duplicate_answers, check_duplicates_within_question, AnswerEntry};
 use docx_rust::DocxFile;
 use docx_rust::document::{BodyContent, Paragraph, ParagraphContent, Run, RunContent, Text };
 use crate::middleware::fill_format::extract_cell_text;
+use crate::middleware::check_duplicate_ids::{apply_renumbering, collect_table_ids, find_id_conflicts, plan_renumbering, IdConflict};
+use crate::middleware::repair_docx::repair_tables;
+use crate::middleware::cluster_duplicates::{cluster_pairs, DuplicateCluster};
+use crate::middleware::option_set_match::{match_option_sets, ItemMatch, ItemMatchKind};
+use crate::middleware::answer_conflicts::{conflict_from_item_match, find_bank_conflicts, find_file_conflict, AnswerConflict};
+use crate::middleware::validate_metadata::{validate_question_metadata, validation_rules, MetadataIssue};
+use crate::service::export_docx::extract_questions_from_content;
+use crate::service::template_docx::build_question_template;
+use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use crate::service::progress::{cancel_job, JobContext, StageTimer};
+use crate::service::settings::{load_settings, save_settings, Settings};
+use crate::database::backupdb::backup_before_import;
+use crate::service::calibration::{calibrate_thresholds, record_decision, CalibrationReport, ReviewDecision};
+use crate::functions::embedding_model::{get_model, model_status, select_model_for_language, ModelStatus};
 
 #[tauri::command]
-async fn read_docx(file_data: Vec<u8>) -> Result<String, String> {
+async fn read_docx(window: tauri::Window, file_data: Vec<u8>, subject: Option<String>, job_id: Option<String>) -> Result<String, String> {
+    let job = JobContext::new(job_id, Some(window));
+
+    tauri::async_runtime::spawn_blocking(move || run_read_docx(file_data, subject, &job))
+        .await
+        .map_err(|e| format!("Lỗi khi chạy tác vụ nền: {}", e))?
+}
+
+fn run_read_docx(file_data: Vec<u8>, subject: Option<String>, job: &JobContext) -> Result<String, String> {
     #[allow(non_snake_case)]
     let fileData = file_data;
+
+    let settings = load_settings()?;
+    if let Some(backup_path) = backup_before_import(&settings)? {
+        println!("Đã sao lưu database vào {}", backup_path.display());
+    }
     
-    if let Err(e) = create_database() {
+    if let Err(e) = create_database(&settings.paths.database) {
         return Err(format!("Lỗi khi tạo database: {}", e));
     }
     
-    match read_docx_content_from_bytes(&fileData) {
-        Ok(questions) => {
-            let mut result_messages = Vec::new();
-            
-            for (i, q) in questions.iter().enumerate() {
-                match insert_embeddings(q.question_embedding.clone(), q.answer_embedding.clone()) {
-                    Ok(_) => {
-                        result_messages.push(format!(
-                            "Câu hỏi {} đã lưu vào database thành công!",
-                            i + 1
-                        ));
-                    },
-                    Err(e) => {
-                        return Err(format!("Lỗi khi lưu vào database câu hỏi {}: {}", i + 1, e));
-                    }
-                }
+    match read_docx_content_from_bytes(&fileData, subject.as_deref(), &settings, job) {
+        Ok((questions, model)) => {
+            // Kiểm tra lần cuối trước khi ghi để không lưu dở dang
+            job.check_cancelled()?;
+            job.report("writing", 0, questions.len());
+
+            let records: Vec<BankRecord> = questions.iter()
+                .map(|q| BankRecord {
+                    question_id: &q.id,
+                    question_text: &q.text,
+                    answer_text: &q.correct_answer_text,
+                    question_embedding: &q.question_embedding,
+                    answer_embedding: &q.answer_embedding,
+                })
+                .collect();
+
+            if let Err(e) = insert_embeddings_batch(&settings.paths.database, &records, &model.name, settings.quantization.mode, &settings.long_text.signature()) {
+                return Err(format!("Lỗi khi lưu vào database: {}", e));
             }
+
+            job.report("writing", questions.len(), questions.len());
+
+            let result_messages: Vec<String> = (0..questions.len())
+                .map(|i| format!("Câu hỏi {} đã lưu vào database thành công!", i + 1))
+                .collect();
             
             Ok(result_messages.join("\n\n"))
         },
@@ -50,140 +90,230 @@ async fn read_docx(file_data: Vec<u8>) -> Result<String, String> {
     }
 }
 
-#[tauri::command]
-async fn process_docx(file_data: Vec<u8>) -> Result<String, String> {
-    let _similarity_threshold = match load_similarity_threshold() {
-        Ok(t) => t,
+// Số câu trong ngân hàng chưa được embed lại theo cách chia đoạn hiện tại. Kiểm tra và nhập câu hỏi không sửa các
+// câu đã có trong ngân hàng: các câu này được so bằng embedding đang lưu cho tới khi chạy rechunk_bank_embeddings
+fn count_chunking_pending(settings: &Settings) -> usize {
+    match query_chunking_pending(&settings.paths.database, &settings.long_text.signature()) {
+        Ok(rows) => rows.len(),
         Err(e) => {
-            println!("Lỗi khi load threshold: {}", e);
-            0.6
+            println!("Lỗi khi truy vấn các câu chưa chia đoạn trong database: {}", e);
+            0
+        }
+    }
+}
+
+// Trùng mã QN trong file và với ngân hàng câu hỏi, cùng các trường MARK, UNIT, LO, MIX CHOICES,
+// CREATOR-REVIEWER của từng bảng; dùng chung cho process_docx và fill_format_check
+fn check_ids_and_metadata(content: &[BodyContent], settings: &Settings, subject: Option<&str>) -> (Vec<IdConflict>, Vec<MetadataIssue>) {
+    let bank_ids: HashSet<String> = match query_question_ids(&settings.paths.database) {
+        Ok(ids) => ids.into_iter().collect(),
+        Err(e) => {
+            println!("Lỗi khi truy vấn mã câu hỏi trong database: {}", e);
+            HashSet::new()
         }
     };
+    let id_conflicts = find_id_conflicts(&collect_table_ids(content), &bank_ids);
+
+    let validation_rules = validation_rules(settings, subject);
+    let metadata_issues = extract_questions_from_content(content)
+        .iter()
+        .enumerate()
+        .flat_map(|(i, question)| validate_question_metadata(i + 1, question, &validation_rules))
+        .collect();
+
+    (id_conflicts, metadata_issues)
+}
+
+// Các cụm câu trùng trong file cho kết quả kiểm tra; `ids` là mã QN của từng câu theo thứ tự trong file
+fn clusters_json(clusters: &[DuplicateCluster], ids: &[&str]) -> Vec<serde_json::Value> {
+    clusters.iter()
+        .map(|cluster| serde_json::json!({
+            "cluster_id": cluster.cluster_id,
+            "ids": cluster.members.iter().map(|&member| ids[member]).collect::<Vec<_>>(),
+            "positions": cluster.members.iter().map(|&member| member + 1).collect::<Vec<_>>(),
+            "canonical_id": ids[cluster.canonical],
+        }))
+        .collect()
+}
+
+#[tauri::command]
+async fn process_docx(window: tauri::Window, file_data: Vec<u8>, subject: Option<String>, job_id: Option<String>) -> Result<String, String> {
+    let job = JobContext::new(job_id, Some(window));
+
+    tauri::async_runtime::spawn_blocking(move || run_process_docx(file_data, subject, &job))
+        .await
+        .map_err(|e| format!("Lỗi khi chạy tác vụ nền: {}", e))?
+}
+
+fn run_process_docx(file_data: Vec<u8>, subject: Option<String>, job: &JobContext) -> Result<String, String> {
+    let settings = load_settings()?;
+    let policy = ScoringPolicy::new(&settings, subject.as_deref());
+    let mut timer = StageTimer::start();
     
-    match read_docx_content_from_bytes(&file_data) {
-        Ok(questions) => {
-            let db_result = query_db();
+    match read_docx_content_from_bytes(&file_data, subject.as_deref(), &settings, job) {
+        Ok((questions, model)) => {
+            timer.finish("reading");
+
+            let (id_conflicts, metadata_issues) = {
+                let doc_file = DocxFile::from_reader(std::io::Cursor::new(&file_data))
+                    .map_err(|e| format!("Lỗi khi đọc mã QN trong file DOCX: {}", e))?;
+                let docx = doc_file.parse()
+                    .map_err(|e| format!("Lỗi khi đọc mã QN trong file DOCX: {}", e))?;
+                check_ids_and_metadata(&docx.document.body.content, &settings, subject.as_deref())
+            };
+            timer.finish("validating");
+
+            let chunking_pending = count_chunking_pending(&settings);
+            let db_result = BankRows::load(&settings.paths.database, &policy);
+            timer.finish("loading_bank");
             
             match db_result {
                 Ok(embeddings) => {
-                    let mut results = Vec::new();
-                    let mut processed_questions = std::collections::HashSet::new();
-                    
-                    let all_answers: Vec<String> = questions.iter()
-                        .map(|q| q.correct_answer_text.clone())
+                    // Chuẩn hóa embedding một lần, điểm giữa các câu trong file và với ngân hàng tính song song
+                    let stems = NormalizedMatrix::from_vectors(questions.iter().map(|q| q.question_embedding.as_slice()));
+                    let answers = NormalizedMatrix::from_vectors(questions.iter().map(|q| q.answer_embedding.as_slice()));
+                    let stem_similarities = stems.similarity_matrix(&stems);
+                    let answer_similarities = answers.similarity_matrix(&answers);
+                    let features: Vec<ContrastFeatures> = questions.par_iter()
+                        .map(|q| ContrastFeatures::new(&q.text, &q.correct_answer_text))
+                        .collect();
+                    // Chữ ký MinHash của câu hỏi và đáp án để tính điểm từ vựng với các câu trong file và ngân hàng
+                    let signatures: Vec<_> = questions.iter()
+                        .map(|q| (policy.hybrid.signature(&q.text), policy.hybrid.signature(&q.correct_answer_text)))
+                        .collect();
+
+                    // Câu trong ngân hàng tạo bằng model khác được so với file embed lại bằng model đó
+                    let texts: Vec<(&str, &str)> = questions.iter()
+                        .map(|q| (q.text.as_str(), q.correct_answer_text.as_str()))
+                        .collect();
+                    let mut file_embeddings = vec![FileEmbeddings::new(
+                        &model.name,
+                        questions.iter().map(|q| q.question_embedding.as_slice()),
+                        questions.iter().map(|q| q.answer_embedding.as_slice()),
+                    )];
+                    file_embeddings.extend(embed_for_bank_models(&embeddings, &model.name, &texts, &settings, job)?);
+                    timer.finish("bank_embedding");
+
+                    let bank_index = BankIndex::new(embeddings, file_embeddings, &policy)?;
+                    timer.finish("bank_index");
+                    // k câu gần nhất trong ngân hàng, câu đầu tiên quyết định kết luận trùng
+                    let all_bank_matches = scan_bank(&bank_index, |i, scores| {
+                        nearest_bank_matches(&features[i], &signatures[i], scores, &bank_index, policy.bank_top_k, &policy)
+                    });
+                    timer.finish("bank_scan");
+
+                    let all_answers: Vec<AnswerEntry> = questions.iter()
+                        .map(|q| AnswerEntry { id: &q.id, answer: &q.correct_answer_text, embedding: &q.answer_embedding })
                         .collect();
                     
-                    let duplicate_answers = check_duplicate_answers(&all_answers);
+                    let answer_overlaps = check_duplicate_answers(&all_answers, &policy);
+
+                    // Mọi cặp câu trùng trong file (i < j), tính song song rồi gom thành cụm như fill_format_check
+                    let pair_score = |i: usize, j: usize| {
+                        let lexical = (
+                            estimate_jaccard(&signatures[i].0, &signatures[j].0),
+                            estimate_jaccard(&signatures[i].1, &signatures[j].1),
+                        );
+                        let contrast = compare_features(&features[i], &features[j], &policy.contrast);
+                        apply_contrast(
+                            calculate_similarity_score(stem_similarities.get(i, j), answer_similarities.get(i, j), None, Some(lexical), policy.thresholds.in_file, &policy),
+                            contrast.as_ref(),
+                        )
+                    };
+                    let (score_pair, threshold) = (&pair_score, policy.thresholds.in_file);
+                    let pairs: Vec<(usize, usize, f32)> = (0..questions.len())
+                        .into_par_iter()
+                        .flat_map_iter(|i| {
+                            ((i + 1)..questions.len())
+                                .map(move |j| (i, j, score_pair(i, j)))
+                                .filter(move |(_, _, score)| *score > threshold)
+                        })
+                        .collect();
+                    job.check_cancelled()?;
+
+                    let clusters = cluster_pairs(questions.len(), &pairs);
+                    let cluster_of: HashMap<usize, &DuplicateCluster> = clusters.iter()
+                        .flat_map(|cluster| cluster.members.iter().map(move |&member| (member, cluster)))
+                        .collect();
+                    let mut best_pair_score = vec![0.0f32; questions.len()];
+                    for &(i, j, score) in &pairs {
+                        best_pair_score[i] = best_pair_score[i].max(score);
+                        best_pair_score[j] = best_pair_score[j].max(score);
+                    }
+
+                    let mut results = Vec::new();
                     
                     for (i, docx_item1) in questions.iter().enumerate() {
-                        let mut found_similar = false;
-                        
-                        // Kiểm tra với các câu hỏi khác trong file
-                        for (j, docx_item2) in questions.iter().enumerate() {
-                            if i != j {
-                                let question_similarity = calculate_cosine_similarity(
-                                    &docx_item1.question_embedding,
-                                    &docx_item2.question_embedding
-                                );
-                                
-                                let answer_similarity = calculate_cosine_similarity(
-                                    &docx_item1.answer_embedding,
-                                    &docx_item2.answer_embedding
-                                );
-                                
-                                if question_similarity > 0.5 && answer_similarity > 0.5 {
-                                    results.push(serde_json::json!({
-                                        "docx_question": docx_item1.text,
-                                        "docx_answer": docx_item1.correct_answer_text,
-                                        "answers": [],
-                                        "correct_answer_keys": [],
-                                        "true_answer": docx_item1.correct_answer_text,
-                                        "similar_docx_question": docx_item2.text,
-                                        "similar_docx_answer": docx_item2.correct_answer_text,
-                                        "similarity_score": calculate_similarity_score(question_similarity, answer_similarity),
-                                        "is_similar": true
-                                    }));
-                                    
-                                    found_similar = true;
-                                    processed_questions.insert(docx_item1.text.clone());
-                                    processed_questions.insert(docx_item2.text.clone());
-                                    break;
-                                }
-                            }
-                        }
-                        
-                        if !found_similar && !processed_questions.contains(&docx_item1.text) {
-                            let mut max_similarity = None;
-                            
-                            for db_item in &embeddings {
-                                let question_similarity = calculate_cosine_similarity(
-                                    &docx_item1.question_embedding,
-                                    &db_item.0
-                                );
-                                
-                                let answer_similarity = calculate_cosine_similarity(
-                                    &docx_item1.answer_embedding,
-                                    &db_item.1
-                                );
-                                
-                                if question_similarity > 0.5 && answer_similarity > 0.5 {
-                                    results.push(serde_json::json!({
-                                        "docx_question": docx_item1.text,
-                                        "docx_answer": docx_item1.correct_answer_text,
-                                        "answers": [],
-                                        "correct_answer_keys": [],
-                                        "true_answer": docx_item1.correct_answer_text,
-                                        "db_question": "Câu hỏi từ Database",
-                                        "db_answer": "Đáp án từ Database",
-                                        "similarity_score": calculate_similarity_score(question_similarity, answer_similarity),
-                                        "is_similar": true
-                                    }));
-                                    
-                                    processed_questions.insert(docx_item1.text.clone());
-                                    found_similar = true;
-                                    break;
-                                } else {
-                                    let current_avg = (question_similarity + answer_similarity) / 2.0;
-                                    if let Some((_, _, prev_avg)) = &max_similarity {
-                                        if current_avg > *prev_avg {
-                                            max_similarity = Some((db_item, (question_similarity, answer_similarity), current_avg));
-                                        }
-                                    } else {
-                                        max_similarity = Some((db_item, (question_similarity, answer_similarity), current_avg));
-                                    }
-                                }
-                            }
-
-                            if !found_similar {
-                                if let Some((_db_item, (q_sim, a_sim), _)) = max_similarity {
-                                    results.push(serde_json::json!({
-                                        "docx_question": docx_item1.text,
-                                        "docx_answer": docx_item1.correct_answer_text,
-                                        "answers": [],
-                                        "correct_answer_keys": [],
-                                        "true_answer": docx_item1.correct_answer_text,
-                                        "db_question": "Câu hỏi từ Database",
-                                        "db_answer": "Đáp án từ Database",
-                                        "similarity_score": calculate_similarity_score(q_sim, a_sim),
-                                        "is_similar": false
-                                    }));
-                                    
-                                    processed_questions.insert(docx_item1.text.clone());
-                                }
-                            }
+                        job.check_cancelled()?;
+                        job.report("comparing", i + 1, questions.len());
+
+                        let cluster = cluster_of.get(&i).copied();
+                        let bank_matches = &all_bank_matches[i];
+                        let bank_similar = bank_matches.first().is_some_and(|best| best.is_similar);
+
+                        // Câu trong cụm trùng được so với câu đề xuất giữ lại; câu đề xuất giữ lại chỉ báo
+                        // trùng trong file khi không trùng với ngân hàng
+                        if let Some(cluster) = cluster.filter(|cluster| cluster.canonical != i || !bank_similar) {
+                            let is_canonical = cluster.canonical == i;
+                            let similar = if is_canonical {
+                                cluster.members.iter().copied().find(|&member| member != i).unwrap_or(i)
+                            } else {
+                                cluster.canonical
+                            };
+                            results.push(serde_json::json!({
+                                "docx_question": docx_item1.text,
+                                "docx_answer": docx_item1.correct_answer_text,
+                                "answers": [],
+                                "correct_answer_keys": [],
+                                "true_answer": docx_item1.correct_answer_text,
+                                "stem_chunks": docx_item1.stem_chunks,
+                                "truncated": docx_item1.truncated,
+                                "similar_docx_question": questions[similar].text,
+                                "similar_docx_answer": questions[similar].correct_answer_text,
+                                "similarity_score": best_pair_score[i],
+                                "cluster_id": cluster.cluster_id,
+                                "cluster_positions": cluster.members.iter().map(|&member| member + 1).collect::<Vec<_>>(),
+                                "canonical_position": cluster.canonical + 1,
+                                "is_canonical": is_canonical,
+                                "is_similar": true
+                            }));
+                        } else if let Some(best) = bank_matches.first() {
+                            results.push(serde_json::json!({
+                                "docx_question": docx_item1.text,
+                                "docx_answer": docx_item1.correct_answer_text,
+                                "answers": [],
+                                "correct_answer_keys": [],
+                                "true_answer": docx_item1.correct_answer_text,
+                                "stem_chunks": docx_item1.stem_chunks,
+                                "truncated": docx_item1.truncated,
+                                "db_question": best.question_text,
+                                "db_answer": best.answer_text,
+                                "db_question_id": best.question_id,
+                                "contrast": best.contrast,
+                                "bank_matches": bank_matches,
+                                "similarity_score": best.combined_score,
+                                "cluster_id": cluster.map(|cluster| cluster.cluster_id),
+                                "is_similar": best.is_similar
+                            }));
                         }
                     }
                     
-                    let result = if let Some((ans1, ans2, sim)) = duplicate_answers {
-                        serde_json::json!({
-                            "similarities": results,
-                            "duplicate_answers": [ans1, ans2, sim]
-                        })
-                    } else {
-                        serde_json::json!({
-                            "similarities": results
-                        })
-                    };
+                    timer.finish("comparing");
+
+                    let result = serde_json::json!({
+                        "similarities": results,
+                        "answer_overlaps": answer_overlaps,
+                        "clusters": clusters_json(&clusters, &questions.iter().map(|q| q.id.as_str()).collect::<Vec<_>>()),
+                        "id_errors": id_conflicts,
+                        "metadata_issues": metadata_issues,
+                        "chunking_pending": chunking_pending,
+                        "truncated_positions": questions.iter()
+                            .enumerate()
+                            .filter(|(_, q)| q.truncated)
+                            .map(|(i, _)| i + 1)
+                            .collect::<Vec<_>>(),
+                        "timings": timer.timings(),
+                    });
                     
                     Ok(result.to_string())
                 },
@@ -195,10 +325,15 @@ async fn process_docx(file_data: Vec<u8>) -> Result<String, String> {
 }
 
 #[tauri::command]
-fn fill_format_check(file_data: Vec<u8>) -> Result<String, String> {
-    use crate::functions::cosine_similarity::calculate_cosine_similarity;
-    use crate::functions::plot_similarity::calculate_similarity_score;
+async fn fill_format_check(window: tauri::Window, file_data: Vec<u8>, subject: Option<String>, job_id: Option<String>) -> Result<String, String> {
+    let job = JobContext::new(job_id, Some(window));
+
+    tauri::async_runtime::spawn_blocking(move || run_fill_format_check(file_data, subject, &job))
+        .await
+        .map_err(|e| format!("Lỗi khi chạy tác vụ nền: {}", e))?
+}
 
+fn run_fill_format_check(file_data: Vec<u8>, subject: Option<String>, job: &JobContext) -> Result<String, String> {
     let temp_dir = std::env::temp_dir();
     let temp_file = temp_dir.join("temp_docx_check.docx");
 
@@ -208,91 +343,416 @@ fn fill_format_check(file_data: Vec<u8>) -> Result<String, String> {
     let file_path = temp_file.to_str()
         .ok_or("Không thể chuyển đổi đường dẫn file tạm")?;
 
-    let _similarity_threshold = match load_similarity_threshold() {
-        Ok(value) => value,
-        Err(e) => {
-            println!("Lỗi khi load threshold: {}", e);
-            0.6 
-        }
-    };
-    
-    println!("Đang sử dụng threshold: {}", _similarity_threshold);
+    let settings = load_settings()?;
+    let policy = ScoringPolicy::new(&settings, subject.as_deref());
+    println!("Đang sử dụng ngưỡng: {:?}", policy.thresholds);
+    let mut timer = StageTimer::start();
 
-    let questions = crate::middleware::fill_format::read_docx_content(file_path)
+    let (questions, model) = crate::middleware::fill_format::read_docx_content(file_path, subject.as_deref(), &settings, job)
         .map_err(|e| format!("Lỗi khi đọc file DOCX: {}", e))?;
+    timer.finish("reading");
     
-    let _result_text = format!("Tổng số câu hỏi: {}\n\n", questions.len());
     let mut result_items = Vec::new();
-    let mut duplicate_answers_info = Option::<(String, String, f32)>::None;
 
-    let db_embeddings = match query_db() {
+    let chunking_pending = count_chunking_pending(&settings);
+    let db_embeddings = match BankRows::load(&settings.paths.database, &policy) {
         Ok(embeddings) => embeddings,
         Err(e) => {
             println!("Lỗi khi truy vấn database: {}", e);
-            Vec::new() 
+            BankRows::Full(Vec::new())
         }
     };
+    let db_count = db_embeddings.len();
+    timer.finish("loading_bank");
+
+    let (id_conflicts, metadata_issues) = {
+        let doc_file = DocxFile::from_file(file_path)
+            .map_err(|e| format!("Lỗi khi đọc mã QN trong file DOCX: {}", e))?;
+        let docx = doc_file.parse()
+            .map_err(|e| format!("Lỗi khi đọc mã QN trong file DOCX: {}", e))?;
+        check_ids_and_metadata(&docx.document.body.content, &settings, subject.as_deref())
+    };
+    timer.finish("validating");
+
+    let signatures: Vec<_> = questions.iter()
+        .map(|q| (policy.hybrid.signature(&q.text), policy.hybrid.signature(&q.correct_answers.join(" "))))
+        .collect();
+
+    // Chuẩn hóa embedding và tách đặc trưng khác biệt một lần cho mỗi câu thay vì cho mỗi cặp
+    let stems = NormalizedMatrix::from_vectors(questions.iter().map(|q| q.question_embedding.as_slice()));
+    let answers = NormalizedMatrix::from_vectors(questions.iter().map(|q| q.answer_embedding.as_slice()));
+    let stem_similarities = stems.similarity_matrix(&stems);
+    let answer_similarities = answers.similarity_matrix(&answers);
+    let features: Vec<ContrastFeatures> = questions.par_iter()
+        .map(|q| ContrastFeatures::new(&q.text, &q.correct_answers.join(" ")))
+        .collect();
+    let stem_features: Vec<ContrastFeatures> = questions.par_iter()
+        .map(|q| ContrastFeatures::new(&q.text, ""))
+        .collect();
+
+    let rerank_settings = &settings.reranker;
+    let reranker = load_reranker(rerank_settings, &settings.paths.model_cache);
+    let rerank_texts: Vec<String> = questions.iter()
+        .map(|q| rerank_text(&q.text, &q.correct_answers.join(", ")))
+        .collect();
+
+    let pair_components = |i: usize, j: usize| {
+        let (q1, q2) = (&questions[i], &questions[j]);
+        file_pair_components(
+            stem_similarities.get(i, j),
+            answer_similarities.get(i, j),
+            option_set_similarity(&q1.option_embeddings, &q2.option_embeddings),
+            (
+                PairSide { signatures: &signatures[i], features: &features[i] },
+                PairSide { signatures: &signatures[j], features: &features[j] },
+            ),
+            &policy,
+        )
+    };
+
+    // Điểm sau khi giảm theo khác biệt, điểm gốc và khác biệt đã dùng để giảm điểm
+    let pair_score = |i: usize, j: usize| {
+        let (components, contrast, _) = pair_components(i, j);
+        let (score, raw_score) = file_pair_score(&components, contrast.as_ref(), &policy);
+        (score, raw_score, contrast)
+    };
+
+    // Tất cả các cặp câu trùng trong file, key (i, j) với i < j
+    let mut file_pairs: HashMap<(usize, usize), f32> = HashMap::new();
+    // Luật cho điểm cao nhất của cặp khi không phải luật tính từ nội dung (re-rank, tập lựa chọn)
+    let mut pair_rules: HashMap<(usize, usize), MatchRule> = HashMap::new();
+
+    // Điểm của mọi cặp i < j, tính song song; vòng lặp bên dưới chỉ còn chọn ứng viên và re-rank
+    let score_pair = &pair_score;
+    let pair_scores: HashMap<(usize, usize), (f32, f32, Option<ContrastFinding>)> = (0..questions.len())
+        .into_par_iter()
+        .flat_map_iter(|i| ((i + 1)..questions.len()).map(move |j| ((i, j), score_pair(i, j))))
+        .collect();
+    timer.finish("file_pairs");
+    job.check_cancelled()?;
+
+    // Cặp câu đủ giống theo embedding nhưng được giảm điểm vì khác phủ định, số liệu hoặc token mã
+    let contrast_findings: HashMap<(usize, usize), ContrastFinding> = pair_scores.iter()
+        .filter(|(_, (_, raw_score, _))| *raw_score > policy.thresholds.in_file)
+        .filter_map(|(&key, (_, _, contrast))| Some((key, contrast.clone()?)))
+        .collect();
+
+    for i in 0..questions.len() {
+        job.check_cancelled()?;
+        job.report("comparing", i + 1, questions.len());
+
+        let mut candidates: Vec<(usize, f32)> = (0..questions.len())
+            .filter(|j| *j != i)
+            .map(|j| (j, pair_scores[&(i.min(j), i.max(j))].0))
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        // Re-rank top-k ứng viên bằng cross-encoder nếu được bật trong cấu hình, lỗi thì dùng điểm cosine
+        let mut matches: Option<Vec<(usize, f32)>> = None;
+        if let Some(reranker) = &reranker {
+            let top = shortlist(&candidates, rerank_settings);
+            let candidate_texts: Vec<&str> = top.iter()
+                .map(|(j, _)| rerank_texts[*j].as_str())
+                .collect();
+
+            match rerank_candidates(reranker, &rerank_texts[i], &candidate_texts) {
+                Ok(scores) => matches = Some(reranked_matches(top, scores, rerank_settings)),
+                Err(e) => println!("{}, dùng điểm cosine thay thế", e),
+            }
+        }
+        let reranked = matches.is_some();
+        let matches = matches.unwrap_or_else(|| {
+            candidates.into_iter()
+                .filter(|(_, score)| *score > policy.thresholds.in_file)
+                .collect()
+        });
+
+        for (j, score) in matches {
+            let key = (i.min(j), i.max(j));
+            let entry = file_pairs.entry(key).or_insert(score);
+            if score >= *entry {
+                *entry = score;
+                if reranked {
+                    pair_rules.insert(key, MatchRule::Rerank);
+                } else {
+                    pair_rules.remove(&key);
+                }
+            }
+        }
+    }
+    timer.finish("reranking");
+
+    // So khớp toàn bộ tập lựa chọn, không phụ thuộc thứ tự: câu chỉ đảo lựa chọn vẫn là cùng một câu
+    let item_matches: Vec<(usize, usize, ItemMatch)> = {
+        let (questions, policy, stem_similarities) = (&questions, &policy, &stem_similarities);
+        (0..questions.len())
+            .into_par_iter()
+            .flat_map_iter(|i| {
+                ((i + 1)..questions.len()).filter_map(move |j| {
+                    match_option_sets(&questions[i], &questions[j], stem_similarities.get(i, j), policy)
+                        .map(|item_match| (i, j, item_match))
+                })
+            })
+            .collect()
+    };
+    for (i, j, item_match) in &item_matches {
+        if item_match.kind != ItemMatchKind::DifferentKey {
+            let score = (item_match.stem_similarity + item_match.option_set_similarity) / 2.0;
+            let entry = file_pairs.entry((*i, *j)).or_insert(0.0);
+            if score > *entry {
+                *entry = score;
+                pair_rules.insert((*i, *j), MatchRule::OptionSet);
+            }
+        }
+    }
+    timer.finish("option_sets");
+    job.check_cancelled()?;
+
+    // k câu gần nhất trong ngân hàng và câu trùng phần dẫn nhưng khác đáp án, cùng một lượt quét điểm
+    // Câu trong ngân hàng tạo bằng model khác được so với file embed lại bằng model đó
+    let bank_answers: Vec<String> = questions.iter().map(|q| q.correct_answers.join(" ")).collect();
+    let bank_texts: Vec<(&str, &str)> = questions.iter()
+        .zip(&bank_answers)
+        .map(|(q, answer)| (q.text.as_str(), answer.as_str()))
+        .collect();
+    let mut file_embeddings = vec![FileEmbeddings::new(
+        &model.name,
+        questions.iter().map(|q| q.question_embedding.as_slice()),
+        questions.iter().map(|q| q.answer_embedding.as_slice()),
+    )];
+    file_embeddings.extend(embed_for_bank_models(&db_embeddings, &model.name, &bank_texts, &settings, job)?);
+    timer.finish("bank_embedding");
+
+    let bank_index = BankIndex::new(db_embeddings, file_embeddings, &policy)?;
+    timer.finish("bank_index");
+    let (all_bank_matches, bank_conflicts): (Vec<Vec<BankMatch>>, Vec<Vec<AnswerConflict>>) =
+        scan_bank(&bank_index, |i, scores| {
+            (
+                nearest_bank_matches(&features[i], &signatures[i], scores, &bank_index, policy.bank_top_k, &policy),
+                find_bank_conflicts(i, &questions[i], &stem_features[i], scores, &bank_index, &policy),
+            )
+        })
+        .into_iter()
+        .unzip();
+    timer.finish("bank_scan");
+    job.check_cancelled()?;
+
+    // Đo tỉ lệ bỏ sót của lượt lọc nhanh so với quét chính xác khi được bật trong cấu hình
+    let quantization_recall = if policy.quantization.measure_recall {
+        let report = match measure_recall(&bank_index, &policy) {
+            Ok(report) => report,
+            Err(e) => {
+                println!("Không đo được recall của lượt lọc nhanh: {}", e);
+                None
+            }
+        };
+        if let Some(report) = &report {
+            println!(
+                "Recall@{} của lượt lọc {:?}: {:.2}% ({} ms so với {} ms quét chính xác)",
+                report.k, report.mode, report.recall * 100.0, report.quantized_millis, report.exact_millis
+            );
+        }
+        timer.finish("quantization_recall");
+        report
+    } else {
+        None
+    };
+
+    // Câu trùng phần dẫn nhưng khác đáp án, trong file và với ngân hàng câu hỏi
+    let mut answer_conflicts: Vec<AnswerConflict> = (0..questions.len())
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let (questions, policy) = (&questions, &policy);
+            let (stem_similarities, answer_similarities) = (&stem_similarities, &answer_similarities);
+            ((i + 1)..questions.len())
+                .filter_map(move |j| {
+                    find_file_conflict(i, j, questions, stem_similarities.get(i, j), answer_similarities.get(i, j), policy)
+                })
+                .chain(bank_conflicts[i].iter().cloned())
+        })
+        .collect();
+    for (i, j, item_match) in &item_matches {
+        let already_reported = answer_conflicts.iter()
+            .any(|conflict| conflict.position == i + 1 && conflict.other_position == Some(j + 1));
+        if item_match.kind == ItemMatchKind::DifferentKey && !already_reported {
+            answer_conflicts.push(conflict_from_item_match(*i, *j, &questions, item_match, answer_similarities.get(*i, *j)));
+        }
+    }
+    timer.finish("answer_conflicts");
+
+    // Các câu dùng lại cùng đáp án đúng, kể cả đáp án ngắn như "A" hay "12"
+    let answer_texts: Vec<String> = questions.iter().map(|q| q.correct_answers.join(", ")).collect();
+    let all_answers: Vec<AnswerEntry> = questions.iter()
+        .zip(&answer_texts)
+        .map(|(q, answer)| AnswerEntry { id: &q.id, answer, embedding: &q.answer_embedding })
+        .collect();
+    let answer_overlaps = check_duplicate_answers(&all_answers, &policy);
+    timer.finish("answer_overlaps");
+
+    let pairs: Vec<(usize, usize, f32)> = file_pairs.iter().map(|(&(i, j), &score)| (i, j, score)).collect();
+    let clusters = cluster_pairs(questions.len(), &pairs);
+    let cluster_of: HashMap<usize, &DuplicateCluster> = clusters.iter()
+        .flat_map(|cluster| cluster.members.iter().map(move |&member| (member, cluster)))
+        .collect();
+
+    let mut best_pair_score = vec![0.0f32; questions.len()];
+    for &(i, j, score) in &pairs {
+        best_pair_score[i] = best_pair_score[i].max(score);
+        best_pair_score[j] = best_pair_score[j].max(score);
+    }
+    timer.finish("clustering");
+
+    let question_label = |index: usize| {
+        let id = &questions[index].id;
+        if id.is_empty() { format!("câu {}", index + 1) } else { format!("QN={}", id) }
+    };
+
+    // Giải thích cặp câu trong file nhìn từ phía câu `i`; trùng nguyên văn được ưu tiên hơn luật đã ghi nhận
+    // khi hiển thị, bảng điểm vẫn theo luật đã cho điểm cặp
+    let explain_file_pair = |i: usize, j: usize, score: f32| {
+        let (q1, q2) = (&questions[i], &questions[j]);
+        let (components, contrast, stem_jaccard) = pair_components(i, j);
+        let detected = detect_rule(&q1.text, &q2.text, Some(stem_jaccard));
+        let recorded = pair_rules.get(&(i.min(j), i.max(j))).copied();
+        let rule = if detected == MatchRule::ExactText { detected } else { recorded.unwrap_or(detected) };
+        let (answer, other_answer) = (q1.correct_answers.join(", "), q2.correct_answers.join(", "));
+        explain_match(
+            rule,
+            recorded,
+            (q1.text.as_str(), q2.text.as_str()),
+            Some((answer.as_str(), other_answer.as_str())),
+            &components,
+            score,
+            contrast,
+            policy.thresholds.in_file,
+            &policy,
+        )
+    };
     
     for (i, q1) in questions.iter().enumerate() {
+        job.check_cancelled()?;
+
         let mut is_similar = false;
         let mut similarity_score = 0.0;
         let mut similarity_type = "none"; 
         let mut similar_to = String::new();
 
-        if let Some((ans1, ans2, sim)) = check_duplicates_within_question(q1) {
-            duplicate_answers_info = Some((ans1.clone(), ans2.clone(), sim));
+        let bank_matches = &all_bank_matches[i];
+
+        let within_question = check_duplicates_within_question(q1, &policy);
+        if let Some(strongest) = within_question.first() {
             is_similar = true;
-            similarity_score = sim;
+            similarity_score = strongest.similarity;
             similarity_type = "question";
-            similar_to = format!("Trùng trong cùng câu hỏi: {} và {}", ans1, ans2);
+            similar_to = format!(
+                "Trùng trong cùng câu hỏi: {}",
+                within_question.iter()
+                    .map(|duplicate| format!("{} và {}", duplicate.option, duplicate.other_option))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            );
         }
         else {
-            for (j, q2) in questions.iter().enumerate() {
-                if i != j {
-                    let question_similarity = calculate_cosine_similarity(
-                        &q1.question_embedding,
-                        &q2.question_embedding
-                    );
-                    
-                    let answer_similarity = calculate_cosine_similarity(
-                        &q1.answer_embedding,
-                        &q2.answer_embedding
-                    );
-                    
-                    if question_similarity > _similarity_threshold && answer_similarity > _similarity_threshold {
+            let cluster = cluster_of.get(&i).copied();
+
+            // Các câu trong cụm trùng, trừ câu đề xuất giữ lại
+            if let Some(cluster) = cluster.filter(|cluster| cluster.canonical != i) {
+                let others: Vec<String> = cluster.members.iter()
+                    .filter(|&&member| member != i)
+                    .map(|&member| question_label(member))
+                    .collect();
+                is_similar = true;
+                similarity_score = best_pair_score[i];
+                similarity_type = "file";
+                similar_to = format!(
+                    "Trùng trong file (nhóm {}) với: {}; đề xuất giữ {}",
+                    cluster.cluster_id,
+                    others.join(", "),
+                    question_label(cluster.canonical)
+                );
+            }
+
+            if !is_similar {
+                if let Some(best) = bank_matches.first() {
+                    if best.is_similar {
                         is_similar = true;
-                        similarity_score = calculate_similarity_score(question_similarity, answer_similarity);
-                        similarity_type = "file"; 
-                        similar_to = format!("Trùng trong file: {} và {}", q1.text, q2.text);
-                        break;
+                        similarity_score = best.combined_score;
+                        similarity_type = "database";
                     }
+                    let best_label = if best.question_id.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" QN={}", best.question_id)
+                    };
+                    similar_to = format!("Trùng với câu hỏi{} trong database có độ tương đồng {:.2}%", best_label, best.combined_score * 100.0);
                 }
             }
 
-            if !is_similar && !db_embeddings.is_empty() {
-                let mut max_db_similarity = 0.0;
-                
-                for (db_q_embedding, db_a_embedding) in &db_embeddings {
-                    let q_similarity = calculate_cosine_similarity(&q1.question_embedding, db_q_embedding);
-                    let a_similarity = calculate_cosine_similarity(&q1.answer_embedding, db_a_embedding);
-                    
-                    let combined_similarity = calculate_similarity_score(q_similarity, a_similarity);
-                    
-                    if combined_similarity > max_db_similarity {
-                        max_db_similarity = combined_similarity;
-                        similar_to = format!("Trùng với câu hỏi trong database có độ tương đồng {:.2}%", combined_similarity * 100.0);
-                    }
-                }
-                
-                if max_db_similarity > _similarity_threshold {
-                    is_similar = true;
-                    similarity_score = max_db_similarity;
-                    similarity_type = "database";
-                    similar_to = format!("Trùng với câu hỏi trong database có độ tương đồng {:.2}%", max_db_similarity * 100.0);
-                }
+            // Câu đề xuất giữ lại của cụm, không trùng với ngân hàng
+            if let Some(cluster) = cluster.filter(|_| !is_similar) {
+                let others: Vec<String> = cluster.members.iter()
+                    .filter(|&&member| member != i)
+                    .map(|&member| question_label(member))
+                    .collect();
+                is_similar = true;
+                similarity_score = best_pair_score[i];
+                similarity_type = "file";
+                similar_to = format!(
+                    "Câu đề xuất giữ lại của nhóm trùng {} (cùng nhóm: {})",
+                    cluster.cluster_id,
+                    others.join(", ")
+                );
             }
         }
 
+        // Giải thích cho từng cặp đã bị đánh dấu: trong cùng câu hỏi, trong file và với ngân hàng
+        let mut explanations: Vec<serde_json::Value> = Vec::new();
+        for duplicate in &within_question {
+            let components = [ScoreComponent { component: "option", similarity: duplicate.raw_similarity, weight: 1.0 }];
+            explanations.push(serde_json::json!({
+                "source": "question",
+                "explanation": explain_match(
+                    MatchRule::WithinQuestion,
+                    None,
+                    (duplicate.option.as_str(), duplicate.other_option.as_str()),
+                    None,
+                    &components,
+                    duplicate.similarity,
+                    duplicate.contrast.clone(),
+                    policy.thresholds.within_question,
+                    &policy,
+                ),
+            }));
+        }
+        for &(a, b, score) in &pairs {
+            let other = if a == i { b } else if b == i { a } else { continue };
+            explanations.push(serde_json::json!({
+                "source": "file",
+                "other_id": questions[other].id,
+                "other_position": other + 1,
+                "explanation": explain_file_pair(i, other, score),
+            }));
+        }
+        if let Some(best) = bank_matches.first().filter(|best| best.is_similar) {
+            let components = score_components(best.stem_similarity, best.answer_similarity, None, best.lexical_similarity, &policy);
+            let answer = q1.correct_answers.join(", ");
+            explanations.push(serde_json::json!({
+                "source": "database",
+                "other_id": best.question_id,
+                "explanation": explain_match(
+                    detect_rule(&q1.text, &best.question_text, best.lexical_similarity.map(|(stem, _)| stem)),
+                    None,
+                    (q1.text.as_str(), best.question_text.as_str()),
+                    Some((answer.as_str(), best.answer_text.as_str())),
+                    &components,
+                    best.combined_score,
+                    best.contrast.clone(),
+                    policy.thresholds.database,
+                    &policy,
+                ),
+            }));
+        }
+
         let formatted_answers: Vec<String> = q1.answers.iter()
             .enumerate()
             .filter_map(|(i, ans)| {
@@ -327,8 +787,19 @@ fn fill_format_check(file_data: Vec<u8>) -> Result<String, String> {
             .map(|key| key.to_lowercase())
             .collect();
 
+        let id_errors: Vec<&String> = id_conflicts.iter()
+            .filter(|conflict| conflict.id == q1.id)
+            .map(|conflict| &conflict.message)
+            .collect();
+
+        let question_metadata_issues: Vec<&MetadataIssue> = metadata_issues.iter()
+            .filter(|issue| issue.position == q1.position)
+            .collect();
+
         let item = serde_json::json!({
             "id": q1.id,
+            "id_errors": id_errors,
+            "metadata_issues": question_metadata_issues,
             "docx_question": q1.text,
             "docx_answer": docx_answer,
             "similarity_score": format!("{:.0}%", similarity_score * 100.0),
@@ -336,17 +807,71 @@ fn fill_format_check(file_data: Vec<u8>) -> Result<String, String> {
             "answers": formatted_answers,
             "correct_answer_keys": correct_answer_keys,
             "correct_answers": q1.correct_answers,
+            "options": q1.answers,
             "similarity_type": similarity_type,
-            "similar_to": similar_to
+            "similar_to": similar_to,
+            "explanations": explanations,
+            "within_question_duplicates": within_question,
+            "stem_chunks": q1.stem_chunks,
+            "truncated": q1.truncated,
+            "bank_matches": bank_matches,
+            "cluster_id": cluster_of.get(&i).map(|cluster| cluster.cluster_id),
+            "answer_conflicts": answer_conflicts.iter()
+                .filter(|conflict| conflict.position == i + 1 || conflict.other_position == Some(i + 1))
+                .collect::<Vec<_>>(),
+            "item_matches": item_matches.iter()
+                .filter_map(|(a, b, item_match)| {
+                    let (other, item_match) = if *a == i {
+                        (*b, item_match.clone())
+                    } else if *b == i {
+                        (*a, item_match.reversed())
+                    } else {
+                        return None;
+                    };
+                    Some(serde_json::json!({
+                        "other_id": questions[other].id,
+                        "other_position": other + 1,
+                        "finding": item_match,
+                    }))
+                })
+                .collect::<Vec<_>>(),
+            "contrast_findings": contrast_findings.iter()
+                .filter_map(|(&(a, b), finding)| {
+                    let (other, finding) = if a == i {
+                        (b, finding.clone())
+                    } else if b == i {
+                        (a, finding.reversed())
+                    } else {
+                        return None;
+                    };
+                    Some(serde_json::json!({
+                        "other_id": questions[other].id,
+                        "other_position": other + 1,
+                        "finding": finding,
+                    }))
+                })
+                .collect::<Vec<_>>(),
+            "is_canonical": cluster_of.get(&i).is_some_and(|cluster| cluster.canonical == i)
         });
 
         result_items.push(item);
     }
+    timer.finish("report");
     
     let result = serde_json::json!({
         "similarities": result_items,
-        "db_count": db_embeddings.len(),
-        "duplicate_answers": duplicate_answers_info.map(|(a1, a2, sim)| vec![a1, a2, sim.to_string()]),
+        "db_count": db_count,
+        "answer_conflicts": answer_conflicts,
+        "answer_overlaps": answer_overlaps,
+        "clusters": clusters_json(&clusters, &questions.iter().map(|q| q.id.as_str()).collect::<Vec<_>>()),
+        "model": model.name,
+        // Cặp có luật "rerank" mang điểm của model này thay vì điểm embedding
+        "reranker": reranker.as_ref().map(|_| &rerank_settings.model),
+        "id_errors": id_conflicts,
+        "metadata_issues": metadata_issues,
+        "chunking_pending": chunking_pending,
+        "timings": timer.timings(),
+        "quantization_recall": quantization_recall,
     });
 
     match serde_json::to_string(&result) {
@@ -355,6 +880,11 @@ fn fill_format_check(file_data: Vec<u8>) -> Result<String, String> {
     }
 }
 
+#[tauri::command]
+fn cancel_check(job_id: String) -> bool {
+    cancel_job(&job_id)
+}
+
 #[tauri::command]
 fn get_temp_file_path() -> String {
     // Lấy thư mục tạm của hệ thống
@@ -398,7 +928,7 @@ async fn filter_docx(file_path: String, duplicate_ids: Vec<String>, _original_fi
 }
 
 #[tauri::command]
-async fn filter_docx_with_data(file_data: Vec<u8>, duplicate_ids: Vec<String>, original_filename: Option<String>) -> Result<String, String> {
+async fn filter_docx_with_data(file_data: Vec<u8>, duplicate_ids: Vec<String>, original_filename: Option<String>, clusters: Option<Vec<Vec<String>>>) -> Result<String, String> {
     use docx_rust::document::{BodyContent};
 
     // println!("Danh sách duplicate_ids FE gửi lên: {:?}", duplicate_ids);
@@ -460,12 +990,34 @@ async fn filter_docx_with_data(file_data: Vec<u8>, duplicate_ids: Vec<String>, o
         }
     };
 
+    // Danh sách giữ lại tính theo mã QN, nên hai bảng cùng mã không phân biệt được: phải đánh lại mã
+    // (renumber_docx_ids) trước khi xuất thay vì âm thầm bỏ bớt một bảng
+    let file_conflicts: Vec<String> = find_id_conflicts(&collect_table_ids(&docx.document.body.content), &HashSet::new())
+        .into_iter()
+        .map(|conflict| conflict.message)
+        .collect();
+    if !file_conflicts.is_empty() {
+        let _ = std::fs::remove_file(&temp_file_path);
+        return Err(format!(
+            "Không thể xuất file khi còn mã QN trùng nhau, hãy đánh lại mã trước: {}",
+            file_conflicts.join("; ")
+        ));
+    }
+
     let mut filtered_body_content: Vec<BodyContent> = Vec::new();
     let mut kept_count = 0;
     let mut removed_count = 0;
     let mut kept_ids = Vec::new();
     let mut removed_ids = Vec::new();
 
+    // Mỗi cụm câu trùng chỉ giữ lại một câu, dù có nhiều mã của cụm nằm trong danh sách giữ lại
+    let cluster_of_id: HashMap<&String, usize> = clusters.iter()
+        .flatten()
+        .enumerate()
+        .flat_map(|(cluster_index, ids)| ids.iter().map(move |id| (id, cluster_index)))
+        .collect();
+    let mut kept_clusters = HashSet::new();
+
     // Log thông tin debug
     // println!("Tổng số phần tử trong body: {}", docx.document.body.content.len());
 
@@ -487,7 +1039,13 @@ async fn filter_docx_with_data(file_data: Vec<u8>, duplicate_ids: Vec<String>, o
             }
             
             if let Some(id) = found_id {
-                if duplicate_ids.contains(&id) {
+                let cluster_available = cluster_of_id.get(&id)
+                    .is_none_or(|cluster_index| !kept_clusters.contains(cluster_index));
+
+                if duplicate_ids.contains(&id) && cluster_available {
+                    if let Some(cluster_index) = cluster_of_id.get(&id) {
+                        kept_clusters.insert(*cluster_index);
+                    }
                     filtered_body_content.push(BodyContent::Table(table.clone()));
                     kept_count += 1;
                     kept_ids.push(id.clone());
@@ -609,19 +1167,214 @@ async fn filter_docx_with_data(file_data: Vec<u8>, duplicate_ids: Vec<String>, o
     Ok(new_file_path_str)
 }
 
+#[tauri::command]
+async fn renumber_docx_ids(file_data: Vec<u8>, original_filename: Option<String>) -> Result<String, String> {
+    let doc_file = DocxFile::from_reader(std::io::Cursor::new(file_data))
+        .map_err(|e| format!("Không thể đọc file DOCX: {}", e))?;
+    let mut docx = doc_file.parse()
+        .map_err(|e| format!("Lỗi khi phân tích DOCX: {}", e))?;
+
+    let settings = load_settings()?;
+    let bank_ids: HashSet<String> = query_question_ids(&settings.paths.database)
+        .map_err(|e| format!("Lỗi khi truy vấn mã câu hỏi trong database: {}", e))?
+        .into_iter()
+        .collect();
+
+    let table_ids = collect_table_ids(&docx.document.body.content);
+    let changes = plan_renumbering(&table_ids, &bank_ids, &settings.id_scheme);
+
+    if changes.is_empty() {
+        return Ok(serde_json::json!({ "file_path": null, "changes": changes }).to_string());
+    }
+
+    apply_renumbering(&mut docx.document.body.content, &changes);
+
+    let output_file_stem = match original_filename {
+        Some(ref original_name) => std::path::Path::new(original_name)
+            .file_stem().unwrap_or_default().to_string_lossy().to_string(),
+        None => "renumbered_docx".to_string(),
+    };
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let new_file_path = std::env::current_dir()
+        .map_err(|e| format!("Không thể lấy thư mục hiện tại: {}", e))?
+        .join(format!("{}_renumbered_{}.docx", output_file_stem, timestamp));
+
+    docx.write_file(&new_file_path)
+        .map_err(|e| format!("Lỗi khi ghi file DOCX: {}", e))?;
+
+    for change in &changes {
+        println!("Đổi QN={} thành QN={} (bảng {})", change.old_id, change.new_id, change.position);
+    }
+
+    Ok(serde_json::json!({
+        "file_path": new_file_path.to_string_lossy(),
+        "changes": changes,
+    }).to_string())
+}
+
+#[tauri::command]
+async fn repair_docx(file_data: Vec<u8>, original_filename: Option<String>) -> Result<String, String> {
+    let doc_file = DocxFile::from_reader(std::io::Cursor::new(file_data))
+        .map_err(|e| format!("Không thể đọc file DOCX: {}", e))?;
+    let mut docx = doc_file.parse()
+        .map_err(|e| format!("Lỗi khi phân tích DOCX: {}", e))?;
+
+    let fixes = repair_tables(&mut docx.document.body.content);
+
+    if fixes.is_empty() {
+        return Ok(serde_json::json!({ "file_path": null, "fixes": fixes }).to_string());
+    }
+
+    let output_file_stem = match original_filename {
+        Some(ref original_name) => std::path::Path::new(original_name)
+            .file_stem().unwrap_or_default().to_string_lossy().to_string(),
+        None => "repaired_docx".to_string(),
+    };
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let new_file_path = std::env::current_dir()
+        .map_err(|e| format!("Không thể lấy thư mục hiện tại: {}", e))?
+        .join(format!("{}_fixed_{}.docx", output_file_stem, timestamp));
+
+    docx.write_file(&new_file_path)
+        .map_err(|e| format!("Lỗi khi ghi file DOCX: {}", e))?;
+
+    println!("Đã sửa {} lỗi định dạng, xuất file: {}", fixes.len(), new_file_path.display());
+
+    Ok(serde_json::json!({
+        "file_path": new_file_path.to_string_lossy(),
+        "fixes": fixes,
+    }).to_string())
+}
+
+#[tauri::command]
+async fn generate_question_template(count: usize, subject: Option<String>, start_id: Option<u64>) -> Result<String, String> {
+    if count == 0 {
+        return Err("Số lượng câu hỏi trong file mẫu phải lớn hơn 0".to_string());
+    }
+
+    let settings = load_settings()?;
+    let mut docx = build_question_template(count, subject.as_deref(), start_id, &settings.id_scheme);
+
+    let output_file_stem = match subject {
+        Some(ref subject) => format!("{}_template", subject.trim()),
+        None => "question_template".to_string(),
+    };
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let new_file_path = std::env::current_dir()
+        .map_err(|e| format!("Không thể lấy thư mục hiện tại: {}", e))?
+        .join(format!("{}_{}.docx", output_file_stem, timestamp));
+
+    docx.write_file(&new_file_path)
+        .map_err(|e| format!("Lỗi khi ghi file mẫu DOCX: {}", e))?;
+
+    println!("Đã tạo file mẫu {} câu hỏi: {}", count, new_file_path.display());
+
+    Ok(new_file_path.to_string_lossy().to_string())
+}
+
+// Load trước model của môn học và ngôn ngữ ("vi", "en") để lần kiểm tra đầu tiên không phải chờ tải model
+#[tauri::command]
+async fn warm_up_model(subject: Option<String>, language: Option<String>) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = load_settings()?;
+        let name = select_model_for_language(&settings.model, subject.as_deref(), language.as_deref());
+        get_model(&name, &settings.paths.model_cache).map(|model| model.name)
+    })
+    .await
+    .map_err(|e| format!("Lỗi khi load model: {}", e))?
+}
+
+#[tauri::command]
+fn get_model_status(model_name: Option<String>, subject: Option<String>, language: Option<String>) -> Result<ModelStatus, String> {
+    let name = match model_name {
+        Some(name) => name,
+        None => select_model_for_language(&load_settings()?.model, subject.as_deref(), language.as_deref()),
+    };
+    Ok(model_status(&name))
+}
+
+#[tauri::command]
+fn get_settings() -> Result<Settings, String> {
+    load_settings()
+}
+
+// Kiểm tra toàn bộ cấu hình trước khi ghi, trả về cấu hình đã lưu
+#[tauri::command]
+fn set_settings(settings: Settings) -> Result<Settings, String> {
+    save_settings(&settings)?;
+    load_settings()
+}
+
+// Ghi quyết định trùng/không trùng của người duyệt vào file log, trả về đường dẫn file
+#[tauri::command]
+fn record_review_decision(decision: ReviewDecision) -> Result<String, String> {
+    record_decision(&decision)
+}
+
+// Hiệu chỉnh ngưỡng từ file CSV đã gán nhãn (mặc định là file log quyết định), có thể lưu ngưỡng đề xuất
+#[tauri::command]
+async fn calibrate_thresholds_from_csv(csv_path: Option<String>, save: bool) -> Result<CalibrationReport, String> {
+    tauri::async_runtime::spawn_blocking(move || calibrate_thresholds(csv_path.as_deref(), save))
+        .await
+        .map_err(|e| format!("Lỗi khi chạy tác vụ nền: {}", e))?
+}
+
+// Bổ sung bản lượng tử hóa theo cấu hình hiện tại cho các câu đã có trong ngân hàng
+#[tauri::command]
+async fn quantize_bank_embeddings() -> Result<String, String> {
+    let settings = load_settings()?;
+    let mode = settings.quantization.mode
+        .ok_or_else(|| "Chưa bật lượng tử hóa (Quantization.Mode trong settings.json)".to_string())?;
+    let db_path = settings.paths.database;
+
+    let updated = tauri::async_runtime::spawn_blocking(move || backfill_quantized_embeddings(&db_path, mode))
+        .await
+        .map_err(|e| format!("Lỗi khi chạy tác vụ nền: {}", e))?
+        .map_err(|e| format!("Lỗi khi lượng tử hóa embedding: {}", e))?;
+
+    Ok(format!("Đã lượng tử hóa {} câu hỏi trong database", updated))
+}
+
+// Embed lại các câu dài trong ngân hàng theo cách chia đoạn hiện tại mà không cần nhập file mới
+#[tauri::command]
+async fn rechunk_bank_embeddings(window: tauri::Window, job_id: Option<String>) -> Result<String, String> {
+    let job = JobContext::new(job_id, Some(window));
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let settings = load_settings()?;
+        if let Some(backup_path) = backup_before_import(&settings)? {
+            println!("Đã sao lưu database vào {}", backup_path.display());
+        }
+        let count = refresh_chunked_bank_rows(&settings, &job)?;
+        Ok(format!("Đã embed lại {} câu dài trong ngân hàng theo cách chia đoạn hiện tại", count))
+    })
+    .await
+    .map_err(|e| format!("Lỗi khi chạy tác vụ nền: {}", e))?
+}
+
 #[tauri::command]
 fn backup_duckdb() -> Result<String, String> {
-    let db_path = "data.duckdb";
-    let backup_path = "new_data.duckdb";
+    let settings = load_settings()?;
+    let db_path = settings.paths.database;
+    let backup_path = settings.paths.backup_database;
 
     // Thực hiện checkpoint để flush dữ liệu
-    if let Ok(conn) = duckdb::Connection::open(db_path) {
+    if let Ok(conn) = duckdb::Connection::open(&db_path) {
         let _ = conn.execute("PRAGMA checkpoint;", []);
     }
 
-    std::fs::copy(db_path, backup_path)
+    std::fs::copy(&db_path, &backup_path)
         .map_err(|e| format!("Không thể sao lưu database: {}", e))?;
-    Ok(backup_path.to_string())
+    Ok(backup_path)
 }
 
 #[cfg(not(feature = "test_fill_format"))]
@@ -635,6 +1388,18 @@ fn main() {
             filter_docx_with_data,
             get_temp_file_path,
             backup_duckdb,
+            cancel_check,
+            renumber_docx_ids,
+            generate_question_template,
+            repair_docx,
+            warm_up_model,
+            get_model_status,
+            get_settings,
+            set_settings,
+            record_review_decision,
+            calibrate_thresholds_from_csv,
+            quantize_bank_embeddings,
+            rechunk_bank_embeddings,
             insert_filtered_to_new_db  // <-- Thêm dòng này
         ])
         .run(tauri::generate_context!())
@@ -676,7 +1441,8 @@ fn main() {
     };
     
     // Gọi hàm fill_format_check
-    match fill_format_check(file_data) {
+    let job = JobContext::new(None, None);
+    match run_fill_format_check(file_data, None, &job) {
         Ok(json_str) => {
             println!("\n=== KẾT QUẢ KIỂM TRA ===");
 
@@ -714,7 +1480,17 @@ fn main() {
 }
 
 #[tauri::command]
-async fn insert_filtered_to_new_db() -> Result<String, String> {
+async fn insert_filtered_to_new_db(window: tauri::Window, job_id: Option<String>) -> Result<String, String> {
+    let job = JobContext::new(job_id, Some(window));
+
+    tauri::async_runtime::spawn_blocking(move || run_insert_filtered_to_new_db(&job))
+        .await
+        .map_err(|e| format!("Lỗi khi chạy tác vụ nền: {}", e))?
+}
+
+fn run_insert_filtered_to_new_db(job: &JobContext) -> Result<String, String> {
+    let settings = load_settings()?;
+
     // 1. Tìm file filtered mới nhất
     let filtered_file_path = match find_latest_filtered_file() {
         Ok(path) => path,
@@ -724,16 +1500,16 @@ async fn insert_filtered_to_new_db() -> Result<String, String> {
     println!("Tìm thấy file filtered mới nhất: {}", filtered_file_path);
     
     // 2. Tạo bản sao database
-    match create_new_database_copy() {
-        Ok(_) => println!("Đã tạo bản sao new_data.duckdb thành công"),
+    match create_new_database_copy(&settings) {
+        Ok(_) => println!("Đã tạo bản sao {} thành công", settings.paths.backup_database),
         Err(e) => return Err(format!("Không thể tạo bản sao database: {}", e))
     };
     
-    // 3. Đọc và insert dữ liệu từ file filtered vào new_data.duckdb
-    match insert_filtered_data_to_new_db(&filtered_file_path).await {
+    // 3. Đọc và insert dữ liệu từ file filtered vào bản sao database
+    match insert_filtered_data_to_new_db(&filtered_file_path, &settings, job) {
         Ok(message) => {
             println!("{}", message);
-            Ok(format!("Đã insert dữ liệu từ {} vào new_data.duckdb thành công", filtered_file_path))
+            Ok(format!("Đã insert dữ liệu từ {} vào {} thành công", filtered_file_path, settings.paths.backup_database))
         },
         Err(e) => Err(format!("Lỗi khi insert dữ liệu: {}", e))
     }
@@ -779,16 +1555,17 @@ fn find_latest_filtered_file() -> Result<String, String> {
 }
 
 // Helper function: Tạo bản sao database
-fn create_new_database_copy() -> Result<(), String> {
+fn create_new_database_copy(settings: &Settings) -> Result<(), String> {
     let current_dir = std::env::current_dir()
         .map_err(|e| format!("Không thể lấy thư mục hiện tại: {}", e))?;
     
-    let source_path = current_dir.join("data.duckdb");
-    let backup_path = current_dir.join("new_data.duckdb");
+    let paths = &settings.paths;
+    let source_path = current_dir.join(&paths.database);
+    let backup_path = current_dir.join(&paths.backup_database);
     
     // Kiểm tra xem file data.duckdb có tồn tại không
     if !source_path.exists() {
-        return Err("File data.duckdb không tồn tại".to_string());
+        return Err(format!("File {} không tồn tại", paths.database));
     }
     
     // Copy file data.duckdb thành new_data.duckdb
@@ -798,56 +1575,38 @@ fn create_new_database_copy() -> Result<(), String> {
     Ok(())
 }
 
-// Helper function: Insert dữ liệu từ file filtered vào new_data.duckdb
-async fn insert_filtered_data_to_new_db(file_path: &str) -> Result<String, String> {
+// Helper function: Insert dữ liệu từ file filtered vào bản sao database trong một transaction,
+// hủy giữa chừng thì bản sao không có câu nào được thêm
+fn insert_filtered_data_to_new_db(file_path: &str, settings: &Settings, job: &JobContext) -> Result<String, String> {
     // Đọc file DOCX filtered
     let file_data = std::fs::read(file_path)
         .map_err(|e| format!("Không thể đọc file filtered: {}", e))?;
     
     // Parse DOCX và lấy dữ liệu câu hỏi
-    match read_docx_content_from_bytes(&file_data) {
-        Ok(questions) => {
-            let mut success_count = 0;
-            let mut error_count = 0;
-            
-            for (i, q) in questions.iter().enumerate() {
-                match insert_embeddings_to_new_database(q.question_embedding.clone(), q.answer_embedding.clone()) {
-                    Ok(_) => {
-                        success_count += 1;
-                        println!("Đã insert câu hỏi {} thành công", i + 1);
-                    },
-                    Err(e) => {
-                        error_count += 1;
-                        println!("Lỗi khi insert câu hỏi {}: {}", i + 1, e);
-                    }
-                }
-            }
+    match read_docx_content_from_bytes(&file_data, None, settings, job) {
+        Ok((questions, model)) => {
+            // Kiểm tra lần cuối trước khi ghi để không lưu dở dang
+            job.check_cancelled()?;
+            job.report("writing", 0, questions.len());
+
+            let records: Vec<BankRecord> = questions.iter()
+                .map(|q| BankRecord {
+                    question_id: &q.id,
+                    question_text: &q.text,
+                    answer_text: &q.correct_answer_text,
+                    question_embedding: &q.question_embedding,
+                    answer_embedding: &q.answer_embedding,
+                })
+                .collect();
+
+            let backup_path = &settings.paths.backup_database;
+            insert_embeddings_batch(backup_path, &records, &model.name, settings.quantization.mode, &settings.long_text.signature())
+                .map_err(|e| format!("Không thể insert vào {}: {}", backup_path, e))?;
+
+            job.report("writing", questions.len(), questions.len());
             
-            Ok(format!(
-                "Đã insert {} câu hỏi thành công, {} lỗi vào new_data.duckdb", 
-                success_count, 
-                error_count
-            ))
+            Ok(format!("Đã insert {} câu hỏi vào {}", questions.len(), backup_path))
         },
         Err(e) => Err(format!("Lỗi khi đọc nội dung file filtered: {}", e))
     }
 }
-
-// Helper function: Insert embeddings vào new_data.duckdb (theo format insertdb.rs)
-fn insert_embeddings_to_new_database(question_embedding: Vec<f32>, answer_embedding: Vec<f32>) -> Result<(), String> {
-    use duckdb::Connection;
-    
-    let conn = Connection::open("new_data.duckdb")
-        .map_err(|e| format!("Không thể mở new_data.duckdb: {}", e))?;
-    
-    // Sử dụng format tương tự insertdb.rs
-    let query = format!(
-        "INSERT INTO data (question_embedding, answer_embedding) VALUES (array{:?}::REAL[], array{:?}::REAL[])",
-        question_embedding, answer_embedding
-    );
-    
-    conn.execute(&query, [])
-        .map_err(|e| format!("Không thể insert vào new_data.duckdb: {}", e))?;
-    
-    Ok(())
-}
\ No newline at end of file
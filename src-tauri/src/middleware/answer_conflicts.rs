@@ -0,0 +1,269 @@
+use crate::functions::bank_matches::{BankIndex, BankScores};
+use crate::functions::contrast_tokens::{apply_contrast, compare_contrast, compare_features, ContrastFeatures};
+use crate::functions::plot_similarity::ScoringPolicy;
+use crate::middleware::fill_format::Question;
+use crate::middleware::option_set_match::ItemMatch;
+
+// Hai câu cùng phần dẫn nhưng đáp án đúng khác nhau: một trong hai câu sai đáp án
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnswerConflict {
+    // Vị trí câu hỏi trong file, bắt đầu từ 1
+    pub position: usize,
+    pub id: String,
+    // Vị trí câu còn lại nếu nằm trong cùng file
+    pub other_position: Option<usize>,
+    pub other_id: String,
+    pub source: String,
+    pub severity: String,
+    pub stem_similarity: f32,
+    pub answer_similarity: f32,
+    pub answer: String,
+    pub other_answer: String,
+    pub message: String,
+}
+
+fn normalize_answer(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Đáp án được coi là khác nhau khi cả nội dung lẫn embedding đều không khớp
+fn answers_differ(answer: &str, other_answer: &str, answer_similarity: f32, policy: &ScoringPolicy) -> bool {
+    let same_text = !answer.trim().is_empty() && normalize_answer(answer) == normalize_answer(other_answer);
+    !same_text && answer_similarity <= policy.thresholds.cross_answers
+}
+
+fn label(id: &str, position: Option<usize>) -> String {
+    match (id.is_empty(), position) {
+        (false, _) => format!("QN={}", id),
+        (true, Some(position)) => format!("câu {}", position),
+        (true, None) => "câu không có mã".to_string(),
+    }
+}
+
+pub fn correct_answer_text(question: &Question) -> String {
+    question.correct_answers.join(", ")
+}
+
+// So câu thứ `index` trong file với ngân hàng câu hỏi; `scores` là điểm cosine của câu này
+// với các câu đã chấm trong ngân hàng, `stem_features` là đặc trưng khác biệt của riêng phần dẫn
+pub fn find_bank_conflicts(
+    index: usize,
+    question: &Question,
+    stem_features: &ContrastFeatures,
+    scores: &BankScores,
+    bank: &BankIndex,
+    policy: &ScoringPolicy,
+) -> Vec<AnswerConflict> {
+    let answer = correct_answer_text(question);
+
+    scores
+        .iter()
+        // Khác biệt chỉ làm giảm điểm nên bỏ qua sớm các câu chưa đạt ngưỡng
+        .filter(|(_, stem, _)| *stem > policy.thresholds.database)
+        .filter_map(|&(position, stem, answer_similarity)| {
+            let item = &bank.bank[position];
+            let contrast = compare_features(stem_features, &bank.stem_features[position], &policy.contrast);
+            let stem_similarity = apply_contrast(stem, contrast.as_ref());
+            if stem_similarity <= policy.thresholds.database {
+                return None;
+            }
+
+            if !answers_differ(&answer, &item.answer_text, answer_similarity, policy) {
+                return None;
+            }
+
+            Some(AnswerConflict {
+                position: index + 1,
+                id: question.id.clone(),
+                other_position: None,
+                other_id: item.question_id.clone(),
+                source: "database".to_string(),
+                severity: "high".to_string(),
+                stem_similarity,
+                answer_similarity,
+                answer: answer.clone(),
+                other_answer: item.answer_text.clone(),
+                message: format!(
+                    "{} trùng phần dẫn với {} trong ngân hàng nhưng khác đáp án",
+                    label(&question.id, Some(index + 1)),
+                    label(&item.question_id, None)
+                ),
+            })
+        })
+        .collect()
+}
+
+// So hai câu trong cùng file; `i`, `j` là vị trí bắt đầu từ 0, `raw_similarity` và `answer_similarity` là cosine
+// phần dẫn và đáp án của cặp lấy từ ma trận điểm đã tính trên embedding chuẩn hóa
+pub fn find_file_conflict(
+    i: usize,
+    j: usize,
+    questions: &[Question],
+    raw_similarity: f32,
+    answer_similarity: f32,
+    policy: &ScoringPolicy,
+) -> Option<AnswerConflict> {
+    let (q1, q2) = (&questions[i], &questions[j]);
+
+    // Độ tương đồng phần dẫn sau khi trừ khác biệt phủ định, số liệu, token mã: "Câu nào KHÔNG phải..."
+    // và "Câu nào là..." có đáp án khác nhau là đúng, không phải mâu thuẫn. Chỉ so phần dẫn vì đáp án
+    // khác nhau chính là điều đang kiểm tra. Khác biệt chỉ làm giảm điểm nên chỉ tách từ khi điểm gốc đạt ngưỡng
+    if raw_similarity <= policy.thresholds.in_file {
+        return None;
+    }
+    let contrast = compare_contrast(&q1.text, "", &q2.text, "", &policy.contrast);
+    let stem_similarity = apply_contrast(raw_similarity, contrast.as_ref());
+    if stem_similarity <= policy.thresholds.in_file {
+        return None;
+    }
+
+    let (answer, other_answer) = (correct_answer_text(q1), correct_answer_text(q2));
+    if !answers_differ(&answer, &other_answer, answer_similarity, policy) {
+        return None;
+    }
+
+    Some(AnswerConflict {
+        position: i + 1,
+        id: q1.id.clone(),
+        other_position: Some(j + 1),
+        other_id: q2.id.clone(),
+        source: "file".to_string(),
+        severity: "high".to_string(),
+        stem_similarity,
+        answer_similarity,
+        answer,
+        other_answer,
+        message: format!(
+            "{} và {} trong file trùng phần dẫn nhưng khác đáp án",
+            label(&q1.id, Some(i + 1)),
+            label(&q2.id, Some(j + 1))
+        ),
+    })
+}
+
+// Cặp "cùng câu hỏi, khác đáp án" từ so khớp tập lựa chọn, kể cả khi nội dung đáp án gần giống nhau;
+// `answer_similarity` là cosine đáp án của cặp trong ma trận điểm
+pub fn conflict_from_item_match(
+    i: usize,
+    j: usize,
+    questions: &[Question],
+    item_match: &ItemMatch,
+    answer_similarity: f32,
+) -> AnswerConflict {
+    let (q1, q2) = (&questions[i], &questions[j]);
+
+    AnswerConflict {
+        position: i + 1,
+        id: q1.id.clone(),
+        other_position: Some(j + 1),
+        other_id: q2.id.clone(),
+        source: "file".to_string(),
+        severity: "high".to_string(),
+        stem_similarity: item_match.stem_similarity,
+        answer_similarity,
+        answer: format!("{} ({})", item_match.keys.join(", "), correct_answer_text(q1)),
+        other_answer: format!("{} ({})", item_match.other_keys.join(", "), correct_answer_text(q2)),
+        message: format!(
+            "{} và {} có cùng câu hỏi và tập lựa chọn nhưng khác đáp án",
+            label(&q1.id, Some(i + 1)),
+            label(&q2.id, Some(j + 1))
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::bank_matches::BankRows;
+    use crate::service::querydb::BankQuestion;
+    use crate::service::settings::Settings;
+
+    fn question(id: &str, text: &str, answer: &str) -> Question {
+        Question {
+            id: id.to_string(),
+            position: 1,
+            text: text.to_string(),
+            answers: vec![format!("a. {}", answer)],
+            correct_answers: vec![answer.to_string()],
+            correct_answer_keys: vec!["A".to_string()],
+            question_embedding: Vec::new(),
+            answer_embedding: Vec::new(),
+            option_embeddings: Vec::new(),
+            stem_chunks: 1,
+            truncated: false,
+        }
+    }
+
+    fn policy() -> ScoringPolicy {
+        ScoringPolicy::new(&Settings::default(), None)
+    }
+
+    fn bank(rows: &[(&str, &str, &str)], policy: &ScoringPolicy) -> BankIndex {
+        let rows = rows
+            .iter()
+            .enumerate()
+            .map(|(i, (id, text, answer))| BankQuestion {
+                rowid: i as i64 + 1,
+                model: "hashing".to_string(),
+                question_id: id.to_string(),
+                question_text: text.to_string(),
+                answer_text: answer.to_string(),
+                question_embedding: Vec::new(),
+                answer_embedding: Vec::new(),
+            })
+            .collect();
+        BankIndex::new(BankRows::Full(rows), Vec::new(), policy).unwrap()
+    }
+
+    const STEM: &str = "Số nào sau đây là số nguyên tố?";
+    const NEGATED: &str = "Số nào sau đây KHÔNG là số nguyên tố?";
+
+    #[test]
+    fn same_stem_with_different_answer_is_a_conflict() {
+        let policy = policy();
+        let questions = [question("Q1", STEM, "7"), question("Q2", STEM, "9")];
+
+        let conflict = find_file_conflict(0, 1, &questions, 0.98, 0.2, &policy).unwrap();
+        assert_eq!((conflict.position, conflict.other_position), (1, Some(2)));
+        assert_eq!((conflict.answer.as_str(), conflict.other_answer.as_str()), ("7", "9"));
+        assert!((conflict.stem_similarity - 0.98).abs() < 1e-6);
+        assert!(conflict.message.contains("QN=Q1") && conflict.message.contains("QN=Q2"));
+
+        let bank = bank(&[("B1", STEM, "9"), ("B2", "Đơn vị đo điện trở là gì?", "Ohm")], &policy);
+        let features = ContrastFeatures::new(STEM, "");
+        let conflicts = find_bank_conflicts(0, &questions[0], &features, &[(0, 0.98, 0.2), (1, 0.3, 0.1)], &bank, &policy);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].other_id.as_str(), conflicts[0].source.as_str()), ("B1", "database"));
+    }
+
+    #[test]
+    fn negated_stem_below_threshold_is_not_a_conflict() {
+        let policy = policy();
+        let questions = [question("Q1", STEM, "7"), question("Q2", NEGATED, "9")];
+        let raw = 0.85;
+        // Điểm gốc đạt ngưỡng nhưng "KHÔNG" làm điểm phần dẫn tụt xuống dưới ngưỡng
+        assert!(raw > policy.thresholds.in_file && raw * (1.0 - policy.contrast.negation) <= policy.thresholds.in_file);
+
+        assert!(find_file_conflict(0, 1, &questions, raw, 0.2, &policy).is_none());
+
+        let bank = bank(&[("B1", NEGATED, "9")], &policy);
+        let features = ContrastFeatures::new(STEM, "");
+        assert!(find_bank_conflicts(0, &questions[0], &features, &[(0, raw, 0.2)], &bank, &policy).is_empty());
+    }
+
+    #[test]
+    fn same_answer_text_with_low_embedding_score_is_not_a_conflict() {
+        let policy = policy();
+        // Cùng nội dung đáp án (khác hoa thường và khoảng trắng) nhưng embedding đáp án cho điểm thấp
+        let questions = [question("Q1", STEM, "Số  7"), question("Q2", STEM, "số 7")];
+        assert!(answers_differ("Số 7", "Số 9", 0.2, &policy));
+        assert!(!answers_differ("Số  7", "số 7", 0.2, &policy));
+        assert!(!answers_differ("Số 7", "Số 9", 0.9, &policy));
+
+        assert!(find_file_conflict(0, 1, &questions, 0.98, 0.2, &policy).is_none());
+
+        let bank = bank(&[("B1", STEM, "số 7")], &policy);
+        let features = ContrastFeatures::new(STEM, "");
+        assert!(find_bank_conflicts(0, &questions[0], &features, &[(0, 0.98, 0.2)], &bank, &policy).is_empty());
+    }
+}
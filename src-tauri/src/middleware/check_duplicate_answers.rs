@@ -1,144 +1,370 @@
+use crate::functions::contrast_tokens::{apply_contrast, compare_contrast, ContrastFinding};
 use crate::functions::cosine_similarity::calculate_cosine_similarity;
-use crate::middleware::fill_format::EMBEDDING_MODEL;
+use crate::middleware::cluster_duplicates::cluster_pairs;
 use crate::middleware::fill_format::Question;
-use crate::functions::load_accurancy::load_similarity_threshold;
+use crate::functions::plot_similarity::ScoringPolicy;
 use std::collections::HashMap;
 
-pub fn check_duplicate_answers(answers: &Vec<String>) -> Option<(String, String, f32)> {
-    let threshold = match load_similarity_threshold() {
-        Ok(t) => t,
-        Err(_) => return None,
-    };
-    
-    if answers.len() < 2 {
-        return None;
-    }
-        
-    let mut embeddings = Vec::new();
-    for ans in answers {
-        if ans.len() <= 3 {
+// Đáp án ngắn hơn mức này (ví dụ "A", "12", "True") chỉ so khớp chính xác, embedding của chúng không có nghĩa
+const SHORT_ANSWER_CHARS: usize = 3;
+
+// Một câu trong file khi so đáp án: mã QN, đáp án đúng và embedding đáp án đã tạo khi đọc file
+// (qua cache embedding và chia đoạn văn bản dài)
+pub struct AnswerEntry<'a> {
+    pub id: &'a str,
+    pub answer: &'a str,
+    pub embedding: &'a [f32],
+}
+
+// Một câu trong nhóm dùng chung đáp án
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnswerOverlapMember {
+    // Vị trí câu hỏi trong file, bắt đầu từ 1
+    pub position: usize,
+    pub id: String,
+    pub answer: String,
+}
+
+// Nhóm câu hỏi trong file có đáp án đúng trùng nhau: cùng đáp án sau khi bỏ hoa thường và khoảng trắng thừa,
+// hoặc các đáp án đủ dài giống nhau theo embedding
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnswerOverlap {
+    pub group_id: usize,
+    // Theo vị trí trong file
+    pub members: Vec<AnswerOverlapMember>,
+    // Các đáp án khác nhau trong nhóm, đáp án nhiều câu dùng nhất đứng đầu
+    pub answers: Vec<String>,
+    // Điểm thấp nhất giữa hai đáp án được nối vào nhóm, 1.0 khi mọi câu dùng cùng một đáp án
+    pub similarity: f32,
+    // Mọi câu trong nhóm dùng cùng một đáp án
+    pub exact: bool,
+}
+
+fn normalize_answer(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Các nhóm câu hỏi có đáp án đúng trùng nhau, xếp theo vị trí câu đầu tiên của nhóm.
+// `answers` là từng câu theo thứ tự trong file
+pub fn check_duplicate_answers(answers: &[AnswerEntry], policy: &ScoringPolicy) -> Vec<AnswerOverlap> {
+    let threshold = policy.thresholds.cross_answers;
+
+    // Gom các câu dùng cùng đáp án (đã chuẩn hóa), theo thứ tự xuất hiện
+    let mut distinct: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut slot_of: HashMap<String, usize> = HashMap::new();
+    for (i, entry) in answers.iter().enumerate() {
+        let normalized = normalize_answer(entry.answer);
+        if normalized.is_empty() {
             continue;
         }
-        
-        match EMBEDDING_MODEL.embed(vec![ans], None) {
-            Ok(mut emb) => embeddings.push((ans, emb.remove(0))),
-            Err(_) => continue,
-        }
+        let slot = *slot_of.entry(normalized.clone()).or_insert_with(|| {
+            distinct.push((normalized, Vec::new()));
+            distinct.len() - 1
+        });
+        distinct[slot].1.push(i);
     }
 
-    for i in 0..embeddings.len() {
-        for j in (i + 1)..embeddings.len() {
-            let similarity = calculate_cosine_similarity(&embeddings[i].1, &embeddings[j].1);
-            
+    // Nối các đáp án khác nhau nhưng giống nhau theo embedding; đáp án ngắn chỉ so khớp chính xác
+    let representative = |slot: usize| &answers[distinct[slot].1[0]];
+    let comparable = |slot: usize| {
+        distinct[slot].0.chars().count() > SHORT_ANSWER_CHARS && !representative(slot).embedding.is_empty()
+    };
+    let mut links: Vec<(usize, usize, f32)> = Vec::new();
+    for a in (0..distinct.len()).filter(|&a| comparable(a)) {
+        for b in ((a + 1)..distinct.len()).filter(|&b| comparable(b)) {
+            let (first, second) = (representative(a), representative(b));
+            let raw_similarity = calculate_cosine_similarity(first.embedding, second.embedding);
+            if raw_similarity <= threshold {
+                continue;
+            }
+            // Đáp án chỉ khác số hoặc toán tử ("O(n)" và "O(n^2)") bị giảm điểm
+            let contrast = compare_contrast("", first.answer, "", second.answer, &policy.contrast);
+            let similarity = apply_contrast(raw_similarity, contrast.as_ref());
             if similarity > threshold {
-                return Some((
-                    embeddings[i].0.clone(),
-                    embeddings[j].0.clone(),
-                    similarity
-                ));
+                links.push((a, b, similarity));
             }
         }
     }
-    
-    None
+
+    let linked = cluster_pairs(distinct.len(), &links);
+    let mut in_cluster = vec![false; distinct.len()];
+    let mut groups: Vec<Vec<usize>> = linked
+        .iter()
+        .map(|cluster| {
+            cluster.members.iter().for_each(|&slot| in_cluster[slot] = true);
+            cluster.members.clone()
+        })
+        .collect();
+    groups.extend(
+        (0..distinct.len())
+            .filter(|&slot| !in_cluster[slot] && distinct[slot].1.len() > 1)
+            .map(|slot| vec![slot]),
+    );
+    groups.sort_by_key(|slots| slots.iter().map(|&slot| distinct[slot].1[0]).min());
+
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(group_id, mut slots)| {
+            let similarity = links
+                .iter()
+                .filter(|(a, b, _)| slots.contains(a) && slots.contains(b))
+                .map(|(_, _, similarity)| *similarity)
+                .fold(1.0f32, f32::min);
+            let mut members: Vec<usize> = slots.iter().flat_map(|&slot| distinct[slot].1.iter().copied()).collect();
+            members.sort_unstable();
+            slots.sort_by_key(|&slot| std::cmp::Reverse(distinct[slot].1.len()));
+
+            AnswerOverlap {
+                group_id: group_id + 1,
+                members: members
+                    .into_iter()
+                    .map(|i| AnswerOverlapMember {
+                        position: i + 1,
+                        id: answers[i].id.to_string(),
+                        answer: answers[i].answer.to_string(),
+                    })
+                    .collect(),
+                answers: slots.iter().map(|&slot| representative(slot).answer.to_string()).collect(),
+                similarity,
+                exact: slots.len() == 1,
+            }
+        })
+        .collect()
 }
 
-// Hàm mới: Kiểm tra đáp án trùng lặp trong cùng một câu hỏi
-pub fn check_duplicates_within_question(question: &Question) -> Option<(String, String, f32)> {
+// Một cặp lựa chọn trùng nhau trong cùng câu hỏi, giữ nguyên dạng "a. nội dung"
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OptionDuplicate {
+    pub option: String,
+    pub other_option: String,
+    // Điểm sau khi giảm theo khác biệt; raw_similarity là cosine trước khi giảm
+    pub similarity: f32,
+    pub raw_similarity: f32,
+    // Hai lựa chọn giống hệt nhau về nội dung (không phân biệt hoa thường)
+    pub exact: bool,
+    pub contrast: Option<ContrastFinding>,
+}
+
+// Tất cả các cặp lựa chọn trùng trong cùng một câu hỏi, cặp giống nhất đứng đầu
+pub fn check_duplicates_within_question(question: &Question, policy: &ScoringPolicy) -> Vec<OptionDuplicate> {
+    let mut duplicates = Vec::new();
+
     // Tách nội dung các đáp án (bỏ qua phần a., b., c.,...)
-    let mut answer_contents: HashMap<String, String> = HashMap::new();
-    
-    for ans in &question.answers {
-        if let Some(pos) = ans.find('.') {
-            let key = ans[0..pos].trim().to_string();
-            let content = ans[pos+1..].trim().to_string();
-            
-            if !content.is_empty() {
-                for (existing_content, existing_key) in &answer_contents {
-                    if content.eq_ignore_ascii_case(existing_content) {
-                        return Some((
-                            format!("{}.{}", existing_key, existing_content),
-                            ans.clone(),
-                            1.0
-                        ));
-                    }
-                }
-                
-                answer_contents.insert(content, key);
+    let contents: Vec<Option<&str>> = question.answers.iter()
+        .map(|ans| ans.find('.').map(|pos| ans[pos + 1..].trim()).filter(|content| !content.is_empty()))
+        .collect();
+    let same_content = |i: usize, j: usize| match (contents[i], contents[j]) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
+    };
+
+    for i in 0..question.answers.len() {
+        for j in (i + 1)..question.answers.len() {
+            if same_content(i, j) {
+                duplicates.push(OptionDuplicate {
+                    option: question.answers[i].clone(),
+                    other_option: question.answers[j].clone(),
+                    similarity: 1.0,
+                    raw_similarity: 1.0,
+                    exact: true,
+                    contrast: None,
+                });
             }
         }
     }
     
-    let threshold = load_similarity_threshold().unwrap_or(0.6);
+    let threshold = policy.thresholds.within_question;
     
-    let mut embeddings = Vec::new();
-    for ans in &question.answers {
-        // Tách nội dung đáp án
-        if let Some(pos) = ans.find('.') {
-            let content = ans[pos+1..].trim();
-            if content.len() > 3 {
-                match EMBEDDING_MODEL.embed(vec![content], None) {
-                    Ok(mut emb) => embeddings.push((ans.clone(), emb.remove(0))),
-                    Err(_) => continue,
-                }
-            }
-        }
-    }
+    // Embedding các lựa chọn đã được tạo theo batch khi đọc file
+    let embeddings: Vec<(usize, &Vec<f32>)> = question.option_embeddings.iter()
+        .enumerate()
+        .take(question.answers.len())
+        .filter(|(_, emb)| !emb.is_empty())
+        .collect();
     
-    // So sánh từng cặp embedding
-    for i in 0..embeddings.len() {
-        for j in (i + 1)..embeddings.len() {
-            let similarity = calculate_cosine_similarity(&embeddings[i].1, &embeddings[j].1);
-            
-            if similarity > threshold {
-                return Some((
-                    embeddings[i].0.clone(),
-                    embeddings[j].0.clone(),
-                    similarity
-                ));
+    // So sánh từng cặp embedding; lựa chọn chỉ khác số hoặc toán tử ("O(n)" và "O(n^2)") bị giảm điểm
+    for a in 0..embeddings.len() {
+        for b in (a + 1)..embeddings.len() {
+            let ((i, emb1), (j, emb2)) = (embeddings[a], embeddings[b]);
+            if same_content(i, j) {
+                continue;
             }
-        }
-    }
-    
-    None
-}
 
-// Hàm mới: Kiểm tra câu hỏi trùng lặp
-#[allow(dead_code)]
-pub fn check_duplicate_questions(questions: &[Question]) -> Vec<(usize, usize, f32)> {
-    let threshold = load_similarity_threshold().unwrap_or(0.6);
-    let mut duplicates = Vec::new();
-    let mut question_text_map: HashMap<String, usize> = HashMap::new();
-    
-    // Kiểm tra trùng text trước
-    for (i, q) in questions.iter().enumerate() {
-        let normalized_text = q.text.to_lowercase();
-        
-        if let Some(prev_idx) = question_text_map.get(&normalized_text) {
-            duplicates.push((*prev_idx, i, 1.0)); // Trùng hoàn toàn
-        } else {
-            question_text_map.insert(normalized_text, i);
-        }
-    }
-    
-    // Sau đó kiểm tra similarity
-    for i in 0..questions.len() {
-        for j in (i + 1)..questions.len() {
-            // Bỏ qua nếu đã xác định là trùng
-            if duplicates.iter().any(|(a, b, _)| (*a == i && *b == j) || (*a == j && *b == i)) {
+            let raw_similarity = calculate_cosine_similarity(emb1, emb2);
+            if raw_similarity <= threshold {
                 continue;
             }
-            
-            let similarity = calculate_cosine_similarity(
-                &questions[i].question_embedding,
-                &questions[j].question_embedding
-            );
+            let contrast = compare_contrast("", &question.answers[i], "", &question.answers[j], &policy.contrast);
+            let similarity = apply_contrast(raw_similarity, contrast.as_ref());
             
             if similarity > threshold {
-                duplicates.push((i, j, similarity));
+                duplicates.push(OptionDuplicate {
+                    option: question.answers[i].clone(),
+                    other_option: question.answers[j].clone(),
+                    similarity,
+                    raw_similarity,
+                    exact: false,
+                    contrast,
+                });
             }
         }
     }
-    
+
+    duplicates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
     duplicates
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::embedder::{Embedder, HashingEmbedder};
+    use crate::middleware::fill_format::embeddable_option;
+    use crate::service::settings::Settings;
+
+    fn policy() -> ScoringPolicy {
+        ScoringPolicy::new(&Settings::default(), None)
+    }
+
+    fn entry<'a>(id: &'a str, answer: &'a str, embedding: &'a [f32]) -> AnswerEntry<'a> {
+        AnswerEntry { id, answer, embedding }
+    }
+
+    #[test]
+    fn same_normalized_answer_forms_exact_group() {
+        let answers = [
+            entry("Q1", "Central Processing Unit", &[1.0, 0.0]),
+            entry("Q2", "Photosynthesis", &[0.0, 1.0]),
+            entry("Q3", "central  processing unit", &[1.0, 0.0]),
+        ];
+        let groups = check_duplicate_answers(&answers, &policy());
+
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].exact);
+        assert_eq!(groups[0].similarity, 1.0);
+        assert_eq!(groups[0].members.iter().map(|m| m.position).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(groups[0].answers, vec!["Central Processing Unit".to_string()]);
+    }
+
+    #[test]
+    fn similar_long_answers_are_linked_by_embedding() {
+        let answers = [
+            entry("Q1", "Central Processing Unit", &[1.0, 0.0]),
+            entry("Q2", "The Central Processing Unit", &[0.95, 0.05]),
+            entry("Q3", "The central processing unit", &[0.95, 0.05]),
+            entry("Q4", "Photosynthesis", &[0.0, 1.0]),
+        ];
+        let groups = check_duplicate_answers(&answers, &policy());
+
+        assert_eq!(groups.len(), 1);
+        assert!(!groups[0].exact);
+        assert!(groups[0].similarity > 0.9 && groups[0].similarity < 1.0);
+        assert_eq!(groups[0].members.len(), 3);
+        // Đáp án nhiều câu dùng nhất đứng đầu
+        assert_eq!(groups[0].answers[0], "The Central Processing Unit");
+    }
+
+    #[test]
+    fn short_answers_only_match_exactly() {
+        let answers = [
+            entry("Q1", "12", &[1.0, 0.0]),
+            entry("Q2", "13", &[1.0, 0.0]),
+            entry("Q3", "", &[1.0, 0.0]),
+            entry("Q4", "", &[1.0, 0.0]),
+        ];
+        assert!(check_duplicate_answers(&answers, &policy()).is_empty());
+    }
+
+    #[test]
+    fn answers_differing_in_numbers_are_penalized() {
+        let answers = [
+            entry("Q1", "Complexity O(n)", &[1.0, 0.0]),
+            entry("Q2", "Complexity O(n^2)", &[0.99, 0.01]),
+        ];
+        assert!(check_duplicate_answers(&answers, &policy()).is_empty());
+    }
+
+    // Câu hỏi có các lựa chọn "a.", "b.", ...; chỉ lựa chọn đủ dài mới có embedding, giống khi đọc file
+    fn question(options: &[&str]) -> Question {
+        let embedder = HashingEmbedder::new(256);
+        let answers: Vec<String> = options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("{}. {}", (b'a' + i as u8) as char, option))
+            .collect();
+        let option_embeddings = answers
+            .iter()
+            .map(|answer| match embeddable_option(answer) {
+                Some(content) => embedder.embed(&[content]).unwrap().remove(0),
+                None => Vec::new(),
+            })
+            .collect();
+
+        Question {
+            id: "Q1".to_string(),
+            position: 1,
+            text: "Câu hỏi".to_string(),
+            answers,
+            correct_answers: Vec::new(),
+            correct_answer_keys: Vec::new(),
+            question_embedding: Vec::new(),
+            answer_embedding: Vec::new(),
+            option_embeddings,
+            stem_chunks: 1,
+            truncated: false,
+        }
+    }
+
+    fn pairs(duplicates: &[OptionDuplicate]) -> Vec<(&str, &str, bool)> {
+        let mut pairs: Vec<(&str, &str, bool)> = duplicates
+            .iter()
+            .map(|d| (d.option.as_str(), d.other_option.as_str(), d.exact))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn every_duplicate_option_pair_is_reported() {
+        let question = question(&["Paris", "paris", "London", "PARIS"]);
+        let duplicates = check_duplicates_within_question(&question, &policy());
+
+        assert_eq!(
+            pairs(&duplicates),
+            vec![("a. Paris", "b. paris", true), ("a. Paris", "d. PARIS", true), ("b. paris", "d. PARIS", true)]
+        );
+        assert!(duplicates.iter().all(|d| d.similarity == 1.0 && d.contrast.is_none()));
+    }
+
+    #[test]
+    fn exact_and_semantic_duplicates_are_told_apart() {
+        let question = question(&[
+            "Central Processing Unit",
+            "central processing unit",
+            "The Central Processing Unit",
+            "Photosynthesis",
+        ]);
+        let duplicates = check_duplicates_within_question(&question, &policy());
+
+        assert_eq!(
+            pairs(&duplicates),
+            vec![
+                ("a. Central Processing Unit", "b. central processing unit", true),
+                ("a. Central Processing Unit", "c. The Central Processing Unit", false),
+                ("b. central processing unit", "c. The Central Processing Unit", false),
+            ]
+        );
+        // Cặp giống hệt đứng đầu, cặp theo embedding có điểm dưới 1
+        assert!(duplicates[0].exact);
+        assert!(duplicates[1..].iter().all(|d| d.similarity < 1.0 && d.similarity > policy().thresholds.within_question));
+    }
+
+    #[test]
+    fn short_options_are_only_compared_exactly() {
+        let question = question(&["12", "13", "12", "Mười hai"]);
+        assert!(question.option_embeddings[0].is_empty());
+
+        let duplicates = check_duplicates_within_question(&question, &policy());
+        assert_eq!(pairs(&duplicates), vec![("a. 12", "c. 12", true)]);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::middleware::fill_format::{extract_cell_text, set_cell_text};
+use docx_rust::document::{BodyContent, Table};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, serde::Serialize)]
+pub struct IdConflict {
+    pub id: String,
+    pub conflict_type: String,
+    // Thứ tự của bảng câu hỏi trong file, bắt đầu từ 1
+    pub positions: Vec<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct IdChange {
+    pub position: usize,
+    pub old_id: String,
+    pub new_id: String,
+}
+
+// Quy tắc sinh mã QN mới, mục "IdScheme" trong settings.json
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct IdScheme {
+    pub prefix: String,
+    pub start: u64,
+    pub padding: usize,
+}
+
+impl Default for IdScheme {
+    fn default() -> Self {
+        IdScheme {
+            prefix: String::new(),
+            start: 1,
+            padding: 0,
+        }
+    }
+}
+
+impl IdScheme {
+    pub fn format(&self, number: u64) -> String {
+        format!("{}{:0width$}", self.prefix, number, width = self.padding)
+    }
+}
+
+pub fn table_question_id(table: &Table) -> Option<String> {
+    let first_row = table.rows.first()?;
+    let cell = first_row.cells.first()?;
+    let cell_text = extract_cell_text(cell).trim().to_string();
+    cell_text.strip_prefix("QN=").map(|id| id.trim().to_string())
+}
+
+// Mã QN của từng bảng theo thứ tự trong file, bảng không có QN= cho chuỗi rỗng
+pub fn collect_table_ids(content: &[BodyContent]) -> Vec<String> {
+    content
+        .iter()
+        .filter_map(|element| match element {
+            BodyContent::Table(table) => Some(table_question_id(table).unwrap_or_default()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn find_id_conflicts(ids: &[String], bank_ids: &HashSet<String>) -> Vec<IdConflict> {
+    let mut order: Vec<&String> = Vec::new();
+    let mut positions: HashMap<&String, Vec<usize>> = HashMap::new();
+
+    for (i, id) in ids.iter().enumerate() {
+        if id.is_empty() {
+            continue;
+        }
+        let entry = positions.entry(id).or_default();
+        if entry.is_empty() {
+            order.push(id);
+        }
+        entry.push(i + 1);
+    }
+
+    let mut conflicts = Vec::new();
+
+    for id in order {
+        let id_positions = &positions[id];
+
+        if id_positions.len() > 1 {
+            conflicts.push(IdConflict {
+                id: id.clone(),
+                conflict_type: "file".to_string(),
+                positions: id_positions.clone(),
+                message: format!("QN={} xuất hiện {} lần trong file", id, id_positions.len()),
+            });
+        }
+
+        if bank_ids.contains(id) {
+            conflicts.push(IdConflict {
+                id: id.clone(),
+                conflict_type: "database".to_string(),
+                positions: id_positions.clone(),
+                message: format!("QN={} đã tồn tại trong ngân hàng câu hỏi", id),
+            });
+        }
+    }
+
+    conflicts
+}
+
+// Giữ mã của lần xuất hiện đầu tiên, đổi mã cho các lần lặp lại và các mã trùng với ngân hàng
+pub fn plan_renumbering(ids: &[String], bank_ids: &HashSet<String>, scheme: &IdScheme) -> Vec<IdChange> {
+    let mut used: HashSet<String> = bank_ids.clone();
+    used.extend(ids.iter().filter(|id| !id.is_empty()).cloned());
+
+    let mut seen: HashSet<&String> = HashSet::new();
+    let mut next_number = scheme.start;
+    let mut changes = Vec::new();
+
+    for (i, id) in ids.iter().enumerate() {
+        if id.is_empty() {
+            continue;
+        }
+
+        if bank_ids.contains(id) || seen.contains(id) {
+            let mut new_id = scheme.format(next_number);
+            while used.contains(&new_id) {
+                next_number += 1;
+                new_id = scheme.format(next_number);
+            }
+            used.insert(new_id.clone());
+
+            changes.push(IdChange {
+                position: i + 1,
+                old_id: id.clone(),
+                new_id,
+            });
+        }
+
+        seen.insert(id);
+    }
+
+    changes
+}
+
+pub fn apply_renumbering(content: &mut [BodyContent], changes: &[IdChange]) {
+    let mut position = 0;
+
+    for element in content.iter_mut() {
+        if let BodyContent::Table(table) = element {
+            position += 1;
+
+            if let Some(change) = changes.iter().find(|c| c.position == position) {
+                if let Some(cell) = table.rows.first_mut().and_then(|row| row.cells.first_mut()) {
+                    set_cell_text(cell, &format!("QN={}", change.new_id));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn conflicts_report_file_and_bank_duplicates() {
+        let bank: HashSet<String> = ids(&["B1"]).into_iter().collect();
+        let conflicts = find_id_conflicts(&ids(&["A1", "B1", "", "A1", "C1"]), &bank);
+
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].id, "A1");
+        assert_eq!(conflicts[0].conflict_type, "file");
+        assert_eq!(conflicts[0].positions, vec![1, 4]);
+        assert_eq!(conflicts[1].id, "B1");
+        assert_eq!(conflicts[1].conflict_type, "database");
+        assert_eq!(conflicts[1].positions, vec![2]);
+    }
+
+    #[test]
+    fn empty_ids_are_not_conflicts() {
+        assert!(find_id_conflicts(&ids(&["", ""]), &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn renumbering_keeps_first_occurrence_and_skips_used_ids() {
+        let bank: HashSet<String> = ids(&["Q1", "Q3"]).into_iter().collect();
+        let scheme = IdScheme {
+            prefix: "Q".to_string(),
+            start: 1,
+            padding: 0,
+        };
+        let changes = plan_renumbering(&ids(&["Q2", "Q2", "Q3", ""]), &bank, &scheme);
+
+        let summary: Vec<(usize, &str, &str)> = changes
+            .iter()
+            .map(|change| (change.position, change.old_id.as_str(), change.new_id.as_str()))
+            .collect();
+        // Q1, Q2, Q3 đều đã được dùng nên mã mới bắt đầu từ Q4
+        assert_eq!(summary, vec![(2, "Q2", "Q4"), (3, "Q3", "Q5")]);
+    }
+
+    #[test]
+    fn scheme_pads_numbers() {
+        let scheme = IdScheme {
+            prefix: "MATH-".to_string(),
+            start: 7,
+            padding: 4,
+        };
+        assert_eq!(scheme.format(7), "MATH-0007");
+        assert!(plan_renumbering(&ids(&["A", "B"]), &HashSet::new(), &scheme).is_empty());
+    }
+}
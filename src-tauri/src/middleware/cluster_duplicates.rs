@@ -0,0 +1,107 @@
+// Nhóm các câu trùng nhau trong file thành cụm (thành phần liên thông của đồ thị trùng lặp)
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DuplicateCluster {
+    pub cluster_id: usize,
+    // Vị trí câu hỏi trong file (bắt đầu từ 0), theo thứ tự xuất hiện
+    pub members: Vec<usize>,
+    // Câu đề xuất giữ lại: câu có tổng điểm trùng với các câu còn lại trong cụm cao nhất
+    pub canonical: usize,
+}
+
+fn find(parent: &mut [usize], x: usize) -> usize {
+    let mut root = x;
+    while parent[root] != root {
+        root = parent[root];
+    }
+
+    // Nén đường đi để các lần tìm sau nhanh hơn
+    let mut current = x;
+    while parent[current] != root {
+        let next = parent[current];
+        parent[current] = root;
+        current = next;
+    }
+    root
+}
+
+// `pairs` là các cặp (i, j, điểm) đã được kết luận trùng; chỉ trả về các cụm có từ 2 câu trở lên
+pub fn cluster_pairs(count: usize, pairs: &[(usize, usize, f32)]) -> Vec<DuplicateCluster> {
+    let mut parent: Vec<usize> = (0..count).collect();
+    let mut connection_score = vec![0.0f32; count];
+
+    for &(i, j, score) in pairs {
+        if i >= count || j >= count || i == j {
+            continue;
+        }
+        connection_score[i] += score;
+        connection_score[j] += score;
+
+        let (root_i, root_j) = (find(&mut parent, i), find(&mut parent, j));
+        if root_i != root_j {
+            // Giữ gốc là câu xuất hiện trước để thứ tự cụm ổn định
+            let (keep, merge) = if root_i < root_j { (root_i, root_j) } else { (root_j, root_i) };
+            parent[merge] = keep;
+        }
+    }
+
+    let mut members_by_root: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for index in 0..count {
+        let root = find(&mut parent, index);
+        members_by_root[root].push(index);
+    }
+
+    members_by_root
+        .into_iter()
+        .filter(|members| members.len() > 1)
+        .enumerate()
+        .map(|(cluster_index, members)| {
+            let canonical = members
+                .iter()
+                .copied()
+                .fold(members[0], |best, index| {
+                    if connection_score[index] > connection_score[best] { index } else { best }
+                });
+
+            DuplicateCluster {
+                cluster_id: cluster_index + 1,
+                members,
+                canonical,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transitive_pairs_form_one_cluster() {
+        let clusters = cluster_pairs(5, &[(0, 2, 0.9), (2, 4, 0.95)]);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].cluster_id, 1);
+        assert_eq!(clusters[0].members, vec![0, 2, 4]);
+        // Câu 2 nối với cả hai câu còn lại nên có tổng điểm cao nhất
+        assert_eq!(clusters[0].canonical, 2);
+    }
+
+    #[test]
+    fn separate_components_are_numbered_in_order() {
+        let clusters = cluster_pairs(6, &[(4, 5, 0.9), (0, 1, 0.9)]);
+        let members: Vec<Vec<usize>> = clusters.iter().map(|c| c.members.clone()).collect();
+        assert_eq!(members, vec![vec![0, 1], vec![4, 5]]);
+        assert_eq!(clusters.iter().map(|c| c.cluster_id).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn invalid_pairs_and_singletons_are_ignored() {
+        assert!(cluster_pairs(3, &[(1, 1, 1.0), (0, 7, 1.0)]).is_empty());
+        assert!(cluster_pairs(0, &[]).is_empty());
+    }
+
+    #[test]
+    fn ties_keep_the_first_member_as_canonical() {
+        let clusters = cluster_pairs(2, &[(0, 1, 0.8)]);
+        assert_eq!(clusters[0].canonical, 0);
+    }
+}
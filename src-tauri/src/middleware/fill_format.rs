@@ -1,19 +1,11 @@
 use docx_rust::DocxFile;
-use docx_rust::document::{BodyContent, ParagraphContent, RunContent, TableRowContent, TableCellContent};
+use docx_rust::document::{BodyContent, Paragraph, ParagraphContent, Run, RunContent, Text, TableRowContent, TableCellContent};
 use std::path::Path;
-use std::sync::LazyLock;
-use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
-use std::path::PathBuf;
-
-pub static EMBEDDING_MODEL: LazyLock<TextEmbedding> = LazyLock::new(|| {
-    let mut options = InitOptions::default();
-    options.model_name = EmbeddingModel::AllMiniLML6V2;
-    options.show_download_progress = true;
-    options.cache_dir = PathBuf::from("FUC-mini");
-    
-    TextEmbedding::try_new(options)
-        .expect("Failed to init embedding model")
-});
+use crate::functions::embedding_cache::{embed_texts, normalize_text};
+use crate::functions::embedding_model::{load_model_for, SelectedModel};
+use crate::functions::long_text::chunk_report;
+use crate::service::progress::JobContext;
+use crate::service::settings::Settings;
 
 pub fn extract_cell_text(cell: &TableRowContent) -> String {
     match cell {
@@ -42,37 +34,110 @@ pub fn extract_cell_text(cell: &TableRowContent) -> String {
     }
 }
 
+// Ghi đè nội dung một ô: giữ định dạng của đoạn text đầu tiên, xóa các đoạn text còn lại
+pub fn set_cell_text(cell: &mut TableRowContent, new_text: &str) {
+    if let TableRowContent::TableCell(cell_data) = cell {
+        let mut written = false;
+
+        for content in cell_data.content.iter_mut() {
+            let TableCellContent::Paragraph(p) = content;
+            for run in p.content.iter_mut() {
+                if let ParagraphContent::Run(r) = run {
+                    for text in r.content.iter_mut() {
+                        if let RunContent::Text(t) = text {
+                            t.text = if written { "".into() } else { new_text.to_string().into() };
+                            written = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !written {
+            cell_data.content.push(TableCellContent::Paragraph(Paragraph {
+                content: vec![
+                    ParagraphContent::Run(Run {
+                        content: vec![
+                            RunContent::Text(Text {
+                                text: new_text.to_string().into(),
+                                space: None,
+                            })
+                        ],
+                        ..Default::default()
+                    })
+                ],
+                ..Default::default()
+            }));
+        }
+    }
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct Question {
     pub id: String,
+    // Thứ tự của bảng câu hỏi trong file, bắt đầu từ 1, tính cả các bảng bị bỏ qua
+    pub position: usize,
     pub text: String,
     pub answers: Vec<String>,
     pub correct_answers: Vec<String>,
     pub correct_answer_keys: Vec<String>,
     pub question_embedding: Vec<f32>,
     pub answer_embedding: Vec<f32>,
+    // Embedding nội dung từng lựa chọn, cùng thứ tự với `answers`; rỗng nếu nội dung quá ngắn
+    pub option_embeddings: Vec<Vec<f32>>,
+    // Số đoạn phần dẫn được chia khi embed, 1 nếu nằm gọn trong cửa sổ token của model
+    pub stem_chunks: usize,
+    // Phần dẫn (hoặc một đoạn của nó) vẫn dài hơn cửa sổ token: model chỉ thấy phần đầu
+    pub truncated: bool,
 }
 
-pub fn read_docx_content(file_path: &str) -> Result<Vec<Question>, Box<dyn std::error::Error>> {
+// Nội dung lựa chọn sau phần "a.", "b.", ...
+pub fn option_content(answer: &str) -> &str {
+    match answer.find('.') {
+        Some(pos) => answer[pos + 1..].trim(),
+        None => "",
+    }
+}
+
+// Nội dung lựa chọn đủ dài để embed; lựa chọn quá ngắn không có embedding
+pub fn embeddable_option(answer: &str) -> Option<&str> {
+    Some(option_content(answer)).filter(|content| content.len() > 3)
+}
+
+pub fn read_docx_content(
+    file_path: &str,
+    subject: Option<&str>,
+    settings: &Settings,
+    job: &JobContext,
+) -> Result<(Vec<Question>, SelectedModel), Box<dyn std::error::Error>> {
     if !Path::new(file_path).exists() {
         return Err("File không tồn tại!".into());
     }
 
+    job.report("parsing", 0, 1);
+
     let doc_file = DocxFile::from_file(file_path)?;
     let docx = doc_file.parse()?;
+    let mut parsed_questions = Vec::new();
     let mut questions = Vec::new();
     let mut skipped_count = 0;
+    let mut table_position = 0;
 
     for element in &docx.document.body.content {
         if let BodyContent::Table(table) = element {
+            table_position += 1;
             let mut question = Question {
                 id: String::new(),
+                position: table_position,
                 text: String::new(),
                 answers: Vec::new(),
                 correct_answers: Vec::new(),
                 correct_answer_keys: Vec::new(),
                 question_embedding: Vec::new(),
                 answer_embedding: Vec::new(),
+                option_embeddings: Vec::new(),
+                stem_chunks: 1,
+                truncated: false,
             };
 
             // Thu thập câu hỏi từ hàng đầu tiên
@@ -146,32 +211,57 @@ pub fn read_docx_content(file_path: &str) -> Result<Vec<Question>, Box<dyn std::
                 continue;
             }
 
-            // Tạo embedding
-            match EMBEDDING_MODEL.embed(vec![&question.text], None) {
-                Ok(mut embeddings) => question.question_embedding = embeddings.remove(0),
-                Err(e) => {
-                    println!("Lỗi khi tạo embedding cho câu hỏi {}: {}", question.id, e);
-                    skipped_count += 1;
-                    continue;
-                }
-            }
+            parsed_questions.push(question);
+        }
+    }
 
-            let combined_answers = question.correct_answers.join(" ");
-            match EMBEDDING_MODEL.embed(vec![&combined_answers], None) {
-                Ok(mut embeddings) => question.answer_embedding = embeddings.remove(0),
-                Err(e) => {
-                    println!("Lỗi khi tạo embedding cho đáp án của câu {}: {}", question.id, e);
-                    skipped_count += 1;
-                    continue;
-                }
+    job.report("parsing", 1, 1);
+    job.check_cancelled()?;
+
+    // Chọn model theo môn học hoặc ngôn ngữ của file
+    let sample_text = parsed_questions.iter()
+        .map(|q| q.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let model = load_model_for(settings, subject, &sample_text)?;
+
+    // Gom câu hỏi, đáp án đúng và nội dung các lựa chọn của cả file để embed theo batch
+    let mut texts: Vec<String> = Vec::new();
+    for question in &parsed_questions {
+        texts.push(question.text.clone());
+        texts.push(question.correct_answers.join(" "));
+        for ans in &question.answers {
+            if let Some(content) = embeddable_option(ans) {
+                texts.push(content.to_string());
             }
+        }
+    }
 
-            questions.push(question);
+    let text_refs: Vec<&str> = texts.iter().map(|text| text.as_str()).collect();
+    let mut embeddings = embed_texts(&model, &text_refs, settings, job)?.into_iter();
+    let mut next_embedding = || embeddings.next().ok_or("Thiếu embedding sau khi xử lý batch");
+
+    let chunking = &settings.long_text;
+    for mut question in parsed_questions {
+        let report = chunk_report(&model, &normalize_text(&question.text), chunking);
+        question.stem_chunks = report.chunks;
+        question.truncated = report.truncated;
+        question.question_embedding = next_embedding()?;
+        question.answer_embedding = next_embedding()?;
+        for i in 0..question.answers.len() {
+            let embedding = if embeddable_option(&question.answers[i]).is_some() {
+                next_embedding()?
+            } else {
+                Vec::new()
+            };
+            question.option_embeddings.push(embedding);
         }
+
+        questions.push(question);
     }
     
     println!("Đã bỏ qua {} câu hỏi không hợp lệ", skipped_count);
-    Ok(questions)
+    Ok((questions, model))
 }
 
 // Thêm hàm main để chạy trực tiếp fill_format.rs
@@ -1,3 +1,8 @@
 pub mod check_duplicate_answers;
+pub mod check_duplicate_ids;
 pub mod fill_format;
-
+pub mod repair_docx;
+pub mod validate_metadata;
+pub mod cluster_duplicates;
+pub mod option_set_match;
+pub mod answer_conflicts;
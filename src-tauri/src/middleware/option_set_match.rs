@@ -0,0 +1,227 @@
+use crate::functions::contrast_tokens::{apply_contrast, compare_contrast};
+use crate::functions::cosine_similarity::calculate_cosine_similarity;
+use crate::functions::plot_similarity::ScoringPolicy;
+use crate::middleware::fill_format::{option_content, Question};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemMatchKind {
+    // Cùng câu hỏi, cùng thứ tự lựa chọn và cùng đáp án
+    Identical,
+    // Cùng câu hỏi và tập lựa chọn, chỉ đảo thứ tự; đáp án vẫn trỏ cùng nội dung
+    ReorderedChoices,
+    // Cùng câu hỏi và tập lựa chọn nhưng đáp án đúng trỏ tới nội dung khác
+    DifferentKey,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ItemMatch {
+    pub kind: ItemMatchKind,
+    pub stem_similarity: f32,
+    pub option_set_similarity: f32,
+    // Cặp lựa chọn tương ứng (key câu này, key câu kia), ví dụ ("A", "C")
+    pub option_mapping: Vec<(String, String)>,
+    pub keys: Vec<String>,
+    pub other_keys: Vec<String>,
+}
+
+impl ItemMatch {
+    // Cùng kết quả nhìn từ phía câu còn lại
+    pub fn reversed(&self) -> ItemMatch {
+        ItemMatch {
+            kind: self.kind,
+            stem_similarity: self.stem_similarity,
+            option_set_similarity: self.option_set_similarity,
+            option_mapping: self.option_mapping.iter().map(|(a, b)| (b.clone(), a.clone())).collect(),
+            keys: self.other_keys.clone(),
+            other_keys: self.keys.clone(),
+        }
+    }
+}
+
+fn option_key(answer: &str) -> String {
+    answer.chars().next().map(|c| c.to_uppercase().to_string()).unwrap_or_default()
+}
+
+fn normalized_option(answer: &str) -> String {
+    option_content(answer)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Nội dung giống hệt (bỏ qua hoa thường, khoảng trắng) cho 1.0; lựa chọn quá ngắn không có embedding thì chỉ so chữ
+fn option_similarity(q1: &Question, i: usize, q2: &Question, j: usize) -> f32 {
+    if normalized_option(&q1.answers[i]) == normalized_option(&q2.answers[j]) {
+        return 1.0;
+    }
+
+    match (q1.option_embeddings.get(i), q2.option_embeddings.get(j)) {
+        (Some(a), Some(b)) if !a.is_empty() && !b.is_empty() => calculate_cosine_similarity(a, b),
+        _ => 0.0,
+    }
+}
+
+// So khớp hai câu có cùng phần dẫn theo toàn bộ tập lựa chọn, không phụ thuộc thứ tự lựa chọn;
+// `raw_similarity` là cosine phần dẫn của cặp lấy từ ma trận điểm đã tính trên embedding chuẩn hóa.
+// Trả về None nếu phần dẫn khác nhau hoặc không ghép được đủ các lựa chọn
+pub fn match_option_sets(q1: &Question, q2: &Question, raw_similarity: f32, policy: &ScoringPolicy) -> Option<ItemMatch> {
+    let count = q1.answers.len();
+    if count == 0 || count != q2.answers.len() {
+        return None;
+    }
+
+    if raw_similarity <= policy.thresholds.in_file {
+        return None;
+    }
+    // Phần dẫn khác nhau ở phủ định, số liệu hoặc token mã thì không còn là cùng một câu hỏi
+    let contrast = compare_contrast(&q1.text, "", &q2.text, "", &policy.contrast);
+    let stem_similarity = apply_contrast(raw_similarity, contrast.as_ref());
+    if stem_similarity <= policy.thresholds.in_file {
+        return None;
+    }
+
+    // Ghép tham lam theo điểm giảm dần: đủ chính xác với 4-6 lựa chọn
+    let mut candidates: Vec<(usize, usize, f32)> = (0..count)
+        .flat_map(|i| (0..count).map(move |j| (i, j)))
+        .map(|(i, j)| (i, j, option_similarity(q1, i, q2, j)))
+        .collect();
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let mut assigned = vec![None; count];
+    let mut used = vec![false; count];
+    for (i, j, similarity) in candidates {
+        if assigned[i].is_none() && !used[j] && similarity > policy.thresholds.in_file {
+            assigned[i] = Some((j, similarity));
+            used[j] = true;
+        }
+    }
+
+    let mapping: Vec<(usize, usize, f32)> = assigned
+        .iter()
+        .enumerate()
+        .map(|(i, pair)| pair.map(|(j, similarity)| (i, j, similarity)))
+        .collect::<Option<_>>()?;
+
+    let key_map: Vec<(String, String)> = mapping
+        .iter()
+        .map(|(i, j, _)| (option_key(&q1.answers[*i]), option_key(&q2.answers[*j])))
+        .collect();
+
+    let mapped_keys: HashSet<&String> = q1
+        .correct_answer_keys
+        .iter()
+        .filter_map(|key| key_map.iter().find(|(from, _)| from == key).map(|(_, to)| to))
+        .collect();
+    let other_keys: HashSet<&String> = q2.correct_answer_keys.iter().collect();
+
+    let kind = if mapped_keys != other_keys {
+        ItemMatchKind::DifferentKey
+    } else if mapping.iter().all(|(i, j, _)| i == j) {
+        ItemMatchKind::Identical
+    } else {
+        ItemMatchKind::ReorderedChoices
+    };
+
+    Some(ItemMatch {
+        kind,
+        stem_similarity,
+        option_set_similarity: mapping.iter().map(|(_, _, similarity)| similarity).sum::<f32>() / count as f32,
+        option_mapping: key_map,
+        keys: q1.correct_answer_keys.clone(),
+        other_keys: q2.correct_answer_keys.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::embedder::{Embedder, HashingEmbedder};
+    use crate::service::settings::Settings;
+
+    fn question(text: &str, options: &[&str], keys: &[&str]) -> Question {
+        let embedder = HashingEmbedder::new(384);
+        let mut texts = vec![text];
+        texts.extend_from_slice(options);
+        let mut embeddings = embedder.embed(&texts).unwrap().into_iter();
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+
+        Question {
+            id: String::new(),
+            position: 1,
+            text: text.to_string(),
+            answers: options
+                .iter()
+                .enumerate()
+                .map(|(i, option)| format!("{}. {}", (b'a' + i as u8) as char, option))
+                .collect(),
+            correct_answers: keys
+                .iter()
+                .map(|key| options[(key.as_bytes()[0] - b'A') as usize].to_string())
+                .collect(),
+            correct_answer_keys: keys,
+            question_embedding: embeddings.next().unwrap(),
+            answer_embedding: Vec::new(),
+            option_embeddings: embeddings.collect(),
+            stem_chunks: 1,
+            truncated: false,
+        }
+    }
+
+    fn policy() -> ScoringPolicy {
+        ScoringPolicy::new(&Settings::default(), None)
+    }
+
+    fn matched(q1: &Question, q2: &Question) -> Option<ItemMatch> {
+        let stem_similarity = calculate_cosine_similarity(&q1.question_embedding, &q2.question_embedding);
+        match_option_sets(q1, q2, stem_similarity, &policy())
+    }
+
+    const STEM: &str = "Thủ đô của nước Pháp là thành phố nào?";
+
+    #[test]
+    fn same_order_is_identical() {
+        let q1 = question(STEM, &["Paris", "London", "Berlin", "Madrid"], &["A"]);
+        let q2 = question(STEM, &["paris", "London", "Berlin", "Madrid"], &["A"]);
+        let matched = matched(&q1, &q2).unwrap();
+
+        assert_eq!(matched.kind, ItemMatchKind::Identical);
+        assert!((matched.option_set_similarity - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shuffled_choices_with_same_answer_are_reordered() {
+        let q1 = question(STEM, &["Paris", "London", "Berlin", "Madrid"], &["A"]);
+        let q2 = question(STEM, &["Madrid", "Berlin", "Paris", "London"], &["C"]);
+        let matched = matched(&q1, &q2).unwrap();
+
+        assert_eq!(matched.kind, ItemMatchKind::ReorderedChoices);
+        assert!(matched.option_mapping.contains(&("A".to_string(), "C".to_string())));
+        assert!(matched.option_mapping.contains(&("D".to_string(), "A".to_string())));
+
+        let reversed = matched.reversed();
+        assert_eq!(reversed.keys, vec!["C".to_string()]);
+        assert!(reversed.option_mapping.contains(&("C".to_string(), "A".to_string())));
+    }
+
+    #[test]
+    fn same_choices_with_other_key_is_different_key() {
+        let q1 = question(STEM, &["Paris", "London", "Berlin", "Madrid"], &["A"]);
+        let q2 = question(STEM, &["Madrid", "Berlin", "Paris", "London"], &["D"]);
+        let matched = matched(&q1, &q2).unwrap();
+        assert_eq!(matched.kind, ItemMatchKind::DifferentKey);
+    }
+
+    #[test]
+    fn different_stems_or_option_counts_do_not_match() {
+        let q1 = question(STEM, &["Paris", "London", "Berlin", "Madrid"], &["A"]);
+        let mut q2 = question(STEM, &["Paris", "London", "Berlin", "Madrid"], &["A"]);
+        q2.question_embedding = q1.question_embedding.iter().map(|value| -value).collect();
+        assert!(matched(&q1, &q2).is_none());
+
+        let q3 = question(STEM, &["Paris", "London", "Berlin"], &["A"]);
+        assert!(matched(&q1, &q3).is_none());
+    }
+}
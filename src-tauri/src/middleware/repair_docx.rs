@@ -0,0 +1,357 @@
+use crate::middleware::check_duplicate_ids::table_question_id;
+use crate::middleware::fill_format::{extract_cell_text, set_cell_text};
+use docx_rust::document::BodyContent;
+
+#[derive(Debug, serde::Serialize)]
+pub struct AppliedFix {
+    // Thứ tự bảng và hàng trong file gốc, bắt đầu từ 1
+    pub table: usize,
+    pub row: usize,
+    pub id: String,
+    pub before: String,
+    pub after: String,
+    pub description: String,
+}
+
+// Nhãn chuẩn của các hàng metadata, so khớp sau khi bỏ dấu ":" và khoảng trắng thừa
+const METADATA_LABELS: [(&str, &str); 8] = [
+    ("ANSWER", "ANSWER:"),
+    ("MARK", "MARK:"),
+    ("UNIT", "UNIT:"),
+    ("LO", "LO:"),
+    ("MIX CHOICES", "MIX CHOICES:"),
+    ("CREATOR-REVIEWER", "CREATOR-REVIEWER:"),
+    ("EDITOR", "EDITOR:"),
+    ("REFERENCE", "REFERENCE:"),
+];
+
+fn normalize_option_label(text: &str) -> Option<String> {
+    // Chấp nhận "a", "a.", "a)", "A:", "a. a", "a. a." ... và đưa về "a."
+    let mut parts = text.split_whitespace();
+    let first = parts.next()?;
+    let second = parts.next();
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let mut chars = first.chars();
+    let letter = chars.next()?;
+    let rest: String = chars.collect();
+    if !letter.is_ascii_alphabetic() || !matches!(rest.as_str(), "" | "." | ")" | ":") {
+        return None;
+    }
+
+    if let Some(second) = second {
+        let repeated = second.trim_end_matches('.');
+        if !repeated.eq_ignore_ascii_case(&letter.to_string()) {
+            return None;
+        }
+    }
+
+    Some(format!("{}.", letter.to_ascii_lowercase()))
+}
+
+// Chữ cái của nhãn lựa chọn đã chuẩn hóa "a." ... "z."
+fn option_letter(label: &str) -> Option<char> {
+    let mut chars = label.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(letter), Some('.'), None) if letter.is_ascii_lowercase() => Some(letter),
+        _ => None,
+    }
+}
+
+fn normalize_metadata_label(text: &str) -> Option<&'static str> {
+    let key = text
+        .trim()
+        .trim_end_matches(':')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+        .replace(" - ", "-");
+
+    let key = match key.as_str() {
+        "MIX CHOICE" | "MIXCHOICES" => "MIX CHOICES".to_string(),
+        "CREATOR REVIEWER" | "CREATOR/REVIEWER" => "CREATOR-REVIEWER".to_string(),
+        "REFERENCES" => "REFERENCE".to_string(),
+        _ => key,
+    };
+
+    METADATA_LABELS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, label)| *label)
+}
+
+fn normalize_qn_label(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    if !trimmed.get(..2)?.eq_ignore_ascii_case("QN") {
+        return None;
+    }
+
+    let id = trimmed[2..].trim_start().strip_prefix('=')?;
+    Some(format!("QN={}", id.trim()))
+}
+
+// Đưa đáp án về dạng chữ in hoa, ngăn cách bằng ", " (ví dụ "a;b" -> "A, B").
+// Trả về None nếu ô ANSWER không chỉ gồm các chữ cái đáp án
+pub fn normalize_answer_keys(text: &str) -> Option<String> {
+    let keys: Vec<String> = text
+        .split(|c: char| c == ',' || c == ';' || c == '/' || c == '&' || c.is_whitespace())
+        .map(|key| key.trim().trim_end_matches(['.', ')']).to_uppercase())
+        .filter(|key| !key.is_empty())
+        .collect();
+
+    let is_key = |key: &String| key.len() == 1 && key.chars().all(|c| c.is_ascii_alphabetic());
+    if keys.is_empty() || !keys.iter().all(is_key) {
+        return None;
+    }
+
+    Some(keys.join(", "))
+}
+
+// Nội dung lựa chọn còn để trống hoặc giữ chỗ như trong file mẫu: "", "…", "...", "___", hay chỉ lặp lại chữ cái "c."
+pub fn is_placeholder_option(letter: char, content: &str) -> bool {
+    let content = content.trim();
+    content.chars().all(|c| matches!(c, '.' | '…' | '_' | '-') || c.is_whitespace())
+        || content.trim_end_matches('.').eq_ignore_ascii_case(&letter.to_string())
+}
+
+pub fn normalize_first_cell(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    normalize_qn_label(trimmed)
+        .or_else(|| normalize_option_label(trimmed))
+        .or_else(|| normalize_metadata_label(trimmed).map(|label| label.to_string()))
+}
+
+// Sửa nhãn các hàng, đáp án ANSWER, xóa hàng trống và các lựa chọn trống ở cuối bảng mà ANSWER không dùng;
+// trả về danh sách các chỗ đã sửa
+pub fn repair_tables(content: &mut [BodyContent]) -> Vec<AppliedFix> {
+    let mut fixes = Vec::new();
+    let mut table_index = 0;
+
+    for element in content.iter_mut() {
+        let BodyContent::Table(table) = element else {
+            continue;
+        };
+        table_index += 1;
+        let id = table_question_id(table).unwrap_or_default();
+
+        let mut keep_rows = Vec::with_capacity(table.rows.len());
+        // Hàng lựa chọn theo thứ tự: (hàng, chữ cái, nội dung có phải giữ chỗ không, nội dung gốc)
+        let mut options: Vec<(usize, char, bool, String)> = Vec::new();
+        let mut answer_keys: Vec<char> = Vec::new();
+
+        for (row_index, row) in table.rows.iter_mut().enumerate() {
+            let texts: Vec<String> = row.cells.iter().map(extract_cell_text).collect();
+
+            // Hàng không có nội dung ở bất kỳ ô nào
+            if row_index > 0 && texts.iter().all(|text| text.trim().is_empty()) {
+                keep_rows.push(false);
+                fixes.push(AppliedFix {
+                    table: table_index,
+                    row: row_index + 1,
+                    id: id.clone(),
+                    before: String::new(),
+                    after: String::new(),
+                    description: "Xóa hàng trống".to_string(),
+                });
+                continue;
+            }
+            keep_rows.push(true);
+
+            let label = texts.first().cloned().unwrap_or_default();
+            let normalized_label = normalize_first_cell(&label);
+
+            if let Some(new_label) = &normalized_label {
+                if *new_label != label {
+                    set_cell_text(&mut row.cells[0], new_label);
+                    fixes.push(AppliedFix {
+                        table: table_index,
+                        row: row_index + 1,
+                        id: id.clone(),
+                        before: label.clone(),
+                        after: new_label.clone(),
+                        description: "Chuẩn hóa nhãn".to_string(),
+                    });
+                }
+            }
+
+            if let Some(letter) = normalized_label.as_deref().and_then(option_letter) {
+                let content = texts.get(1).cloned().unwrap_or_default();
+                options.push((row_index, letter, is_placeholder_option(letter, &content), content));
+            }
+
+            if normalized_label.as_deref() == Some("ANSWER:") && row.cells.len() > 1 {
+                let answer = texts[1].clone();
+                let new_answer = normalize_answer_keys(&answer).unwrap_or_else(|| answer.clone());
+                answer_keys = new_answer.split(", ")
+                    .filter_map(|key| key.chars().next())
+                    .map(|key| key.to_ascii_lowercase())
+                    .collect();
+                if new_answer != answer {
+                    set_cell_text(&mut row.cells[1], &new_answer);
+                    fixes.push(AppliedFix {
+                        table: table_index,
+                        row: row_index + 1,
+                        id: id.clone(),
+                        before: answer,
+                        after: new_answer,
+                        description: "Chuẩn hóa đáp án ANSWER".to_string(),
+                    });
+                }
+            }
+        }
+
+        // Lựa chọn giữ chỗ ở cuối bảng là hàng mẫu chưa dùng tới (câu 3 lựa chọn để trống "d.");
+        // lựa chọn trống ở giữa hoặc là đáp án đúng thì để người soạn tự sửa
+        while let Some((row_index, letter, true, content)) = options.last().cloned() {
+            if answer_keys.contains(&letter) {
+                break;
+            }
+            options.pop();
+            keep_rows[row_index] = false;
+            fixes.push(AppliedFix {
+                table: table_index,
+                row: row_index + 1,
+                id: id.clone(),
+                before: format!("{}. {}", letter, content).trim_end().to_string(),
+                after: String::new(),
+                description: "Xóa lựa chọn trống".to_string(),
+            });
+        }
+
+        let mut keep = keep_rows.into_iter();
+        table.rows.retain(|_| keep.next().unwrap_or(true));
+    }
+
+    fixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::validate_metadata::{validate_question_metadata, validation_rules};
+    use crate::service::export_docx::extract_questions_from_content;
+    use crate::service::settings::Settings;
+    use crate::service::template_docx::question_template_table;
+
+    // Bảng mẫu với nhãn và nội dung của từng hàng được ghi đè
+    fn table(rows: &[(&str, &str)]) -> Vec<BodyContent<'static>> {
+        let mut table = question_template_table("");
+        for (row, (label, value)) in table.rows.iter_mut().zip(rows) {
+            set_cell_text(&mut row.cells[0], label);
+            set_cell_text(&mut row.cells[1], value);
+        }
+        vec![BodyContent::Table(table)]
+    }
+
+    fn first_cells(content: &[BodyContent]) -> Vec<String> {
+        match &content[0] {
+            BodyContent::Table(table) => table.rows.iter().map(|row| extract_cell_text(&row.cells[0])).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    const METADATA: [(&str, &str); 7] = [
+        ("mark", "1"),
+        ("UNIT", "1"),
+        ("LO:", "LO1"),
+        ("Mix choice", "Yes"),
+        ("Creator reviewer", "Creator - Reviewer"),
+        ("", ""),
+        ("REFERENCE:", ""),
+    ];
+
+    #[test]
+    fn broken_table_is_repaired_and_passes_validation() {
+        let mut rows = vec![
+            ("qn = 1", "Thủ đô của Việt Nam là gì?"),
+            ("A)", "Hà Nội"),
+            ("b", "Huế"),
+            ("c. c", "Đà Nẵng"),
+            ("d.", "…"),
+            ("Answer", "a;"),
+        ];
+        rows.extend(METADATA);
+        let mut content = table(&rows);
+
+        let fixes = repair_tables(&mut content);
+        let descriptions: Vec<&str> = fixes.iter().map(|fix| fix.description.as_str()).collect();
+        assert!(descriptions.contains(&"Xóa hàng trống"));
+        assert!(descriptions.contains(&"Chuẩn hóa đáp án ANSWER"));
+        let removed = fixes.iter().find(|fix| fix.description == "Xóa lựa chọn trống").unwrap();
+        assert_eq!((removed.row, removed.before.as_str()), (5, "d. …"));
+
+        assert_eq!(first_cells(&content), vec![
+            "QN=1", "a.", "b.", "c.", "ANSWER:", "MARK:", "UNIT:", "LO:", "MIX CHOICES:", "CREATOR-REVIEWER:", "REFERENCE:",
+        ]);
+
+        let questions = extract_questions_from_content(&content);
+        assert_eq!(questions[0].qn, "1");
+        assert_eq!(questions[0].options, vec!["Hà Nội", "Huế", "Đà Nẵng"]);
+        assert_eq!(questions[0].answer, "A");
+        let rules = validation_rules(&Settings::default(), None);
+        assert!(validate_question_metadata(1, &questions[0], &rules).is_empty());
+
+        // Bảng đã sửa không còn gì để sửa
+        assert!(repair_tables(&mut content).is_empty());
+    }
+
+    #[test]
+    fn placeholders_used_by_answer_or_between_options_are_kept() {
+        let mut rows = vec![
+            ("QN=2", "Chọn đáp án đúng"),
+            ("a.", "Hà Nội"),
+            ("b.", "..."),
+            ("c.", "Huế"),
+            ("d.", ""),
+            ("ANSWER:", "D"),
+        ];
+        rows.extend(METADATA);
+        let mut content = table(&rows);
+
+        let fixes = repair_tables(&mut content);
+        assert!(fixes.iter().all(|fix| fix.description != "Xóa lựa chọn trống"));
+        assert_eq!(&first_cells(&content)[..6], &["QN=2", "a.", "b.", "c.", "d.", "ANSWER:"]);
+    }
+
+    #[test]
+    fn several_trailing_placeholders_are_removed() {
+        let mut rows = vec![
+            ("QN=3", "Đúng hay sai?"),
+            ("a.", "Đúng"),
+            ("b.", "Sai"),
+            ("c.", "c."),
+            ("d.", "___"),
+            ("ANSWER:", "B"),
+        ];
+        rows.extend(METADATA);
+        let mut content = table(&rows);
+
+        let removed: Vec<usize> = repair_tables(&mut content).iter()
+            .filter(|fix| fix.description == "Xóa lựa chọn trống")
+            .map(|fix| fix.row)
+            .collect();
+        assert_eq!(removed, vec![5, 4]);
+        assert_eq!(extract_questions_from_content(&content)[0].options, vec!["Đúng", "Sai"]);
+    }
+
+    #[test]
+    fn labels_answers_and_placeholders_are_normalized() {
+        assert_eq!(normalize_first_cell(" a) ").as_deref(), Some("a."));
+        assert_eq!(normalize_first_cell("B. b.").as_deref(), Some("b."));
+        assert_eq!(normalize_first_cell("QN =  MAE-01").as_deref(), Some("QN=MAE-01"));
+        assert_eq!(normalize_first_cell("creator/reviewer").as_deref(), Some("CREATOR-REVIEWER:"));
+        assert_eq!(normalize_first_cell("Quiz"), None);
+
+        assert_eq!(normalize_answer_keys("a; c/d").as_deref(), Some("A, C, D"));
+        assert_eq!(normalize_answer_keys("Hà Nội"), None);
+
+        assert!(is_placeholder_option('c', "  "));
+        assert!(is_placeholder_option('c', "…"));
+        assert!(is_placeholder_option('c', "C."));
+        assert!(!is_placeholder_option('c', "CPU"));
+        assert!(!is_placeholder_option('a', "0.5"));
+    }
+}
@@ -0,0 +1,241 @@
+use crate::service::export_docx::QuestionData;
+use crate::service::settings::Settings;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MetadataIssue {
+    pub id: String,
+    // Thứ tự của bảng câu hỏi trong file, bắt đầu từ 1; phân biệt được các câu trùng mã QN
+    pub position: usize,
+    pub field: String,
+    // "error" chặn việc import, "warning" chỉ để người duyệt xem lại
+    pub severity: String,
+    pub message: String,
+}
+
+// Khoảng điểm MARK hợp lệ (tính cả hai đầu), mục "Validation" trong settings.json
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct ValidationSettings {
+    pub mark_min: f64,
+    pub mark_max: f64,
+}
+
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        ValidationSettings {
+            mark_min: 0.0,
+            mark_max: 10.0,
+        }
+    }
+}
+
+// Mã UNIT, LO hợp lệ của một môn học, mục "Syllabus" trong settings.json
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct SyllabusSettings {
+    pub units: Vec<String>,
+    #[serde(rename = "LOs")]
+    pub los: Vec<String>,
+}
+
+pub struct ValidationRules {
+    pub mark_min: f64,
+    pub mark_max: f64,
+    pub units: Vec<String>,
+    pub los: Vec<String>,
+}
+
+// Mã UNIT/LO lấy theo môn học, môn chưa khai báo thì không kiểm tra mã
+pub fn validation_rules(settings: &Settings, subject: Option<&str>) -> ValidationRules {
+    let trimmed = |values: &[String]| -> Vec<String> { values.iter().map(|value| value.trim().to_string()).collect() };
+    let syllabus = subject.and_then(|subject| settings.syllabus.get(subject.trim()));
+
+    ValidationRules {
+        mark_min: settings.validation.mark_min,
+        mark_max: settings.validation.mark_max,
+        units: syllabus.map(|syllabus| trimmed(&syllabus.units)).unwrap_or_default(),
+        los: syllabus.map(|syllabus| trimmed(&syllabus.los)).unwrap_or_default(),
+    }
+}
+
+fn issue(position: usize, question: &QuestionData, field: &str, severity: &str, message: String) -> MetadataIssue {
+    MetadataIssue {
+        id: question.qn.clone(),
+        position,
+        field: field.to_string(),
+        severity: severity.to_string(),
+        message,
+    }
+}
+
+fn check_code(
+    position: usize,
+    question: &QuestionData,
+    field: &str,
+    value: &str,
+    allowed: &[String],
+    issues: &mut Vec<MetadataIssue>,
+) {
+    if value.is_empty() {
+        issues.push(issue(position, question, field, "warning", format!("Thiếu {}", field)));
+    } else if !allowed.is_empty() && !allowed.iter().any(|code| code.eq_ignore_ascii_case(value)) {
+        issues.push(issue(
+            position,
+            question,
+            field,
+            "error",
+            format!("{} '{}' không có trong đề cương môn học", field, value),
+        ));
+    }
+}
+
+// `position` là thứ tự của bảng câu hỏi trong file, bắt đầu từ 1
+pub fn validate_question_metadata(position: usize, question: &QuestionData, rules: &ValidationRules) -> Vec<MetadataIssue> {
+    let mut issues = Vec::new();
+
+    // MARK phải là số nằm trong khoảng cho phép
+    let mark = question.mark.trim();
+    if mark.is_empty() {
+        issues.push(issue(position, question, "MARK", "error", "Thiếu MARK".to_string()));
+    } else {
+        match mark.replace(',', ".").parse::<f64>() {
+            Ok(value) if value >= rules.mark_min && value <= rules.mark_max => {}
+            Ok(value) => issues.push(issue(
+                position,
+                question,
+                "MARK",
+                "error",
+                format!("MARK {} nằm ngoài khoảng [{}, {}]", value, rules.mark_min, rules.mark_max),
+            )),
+            Err(_) => issues.push(issue(
+                position,
+                question,
+                "MARK",
+                "error",
+                format!("MARK '{}' không phải là số", mark),
+            )),
+        }
+    }
+
+    check_code(position, question, "UNIT", question.unit.trim(), &rules.units, &mut issues);
+    check_code(position, question, "LO", question.lo.trim(), &rules.los, &mut issues);
+
+    let mix_choices = question.mix_choices.trim();
+    if mix_choices.is_empty() {
+        issues.push(issue(position, question, "MIX CHOICES", "warning", "Thiếu MIX CHOICES".to_string()));
+    } else if !mix_choices.eq_ignore_ascii_case("yes") && !mix_choices.eq_ignore_ascii_case("no") {
+        issues.push(issue(
+            position,
+            question,
+            "MIX CHOICES",
+            "error",
+            format!("MIX CHOICES phải là Yes hoặc No, nhận được '{}'", mix_choices),
+        ));
+    }
+
+    if question.creator_reviewer.trim().is_empty() {
+        issues.push(issue(
+            position,
+            question,
+            "CREATOR-REVIEWER",
+            "error",
+            "Thiếu CREATOR-REVIEWER".to_string(),
+        ));
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(qn: &str, mark: &str) -> QuestionData {
+        QuestionData {
+            qn: qn.to_string(),
+            question: "Thủ đô của Việt Nam là gì?".to_string(),
+            options: vec!["Hà Nội".to_string(), "Huế".to_string()],
+            answer: "A".to_string(),
+            mark: mark.to_string(),
+            unit: "1".to_string(),
+            lo: "LO1".to_string(),
+            mix_choices: "Yes".to_string(),
+            creator_reviewer: "Creator - Reviewer".to_string(),
+            editor: String::new(),
+            reference: String::new(),
+        }
+    }
+
+    fn rules() -> ValidationRules {
+        validation_rules(&Settings::default(), None)
+    }
+
+    fn fields(issues: &[MetadataIssue]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.field.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_question_has_no_issues() {
+        assert!(validate_question_metadata(1, &question("Q1", "1"), &rules()).is_empty());
+    }
+
+    #[test]
+    fn mark_range_includes_both_bounds() {
+        assert!(validate_question_metadata(1, &question("Q1", "0"), &rules()).is_empty());
+        assert!(validate_question_metadata(1, &question("Q1", "10"), &rules()).is_empty());
+        assert!(validate_question_metadata(1, &question("Q1", "0,5"), &rules()).is_empty());
+
+        let issues = validate_question_metadata(1, &question("Q1", "10.5"), &rules());
+        assert_eq!(fields(&issues), vec!["MARK"]);
+        assert_eq!(issues[0].severity, "error");
+        assert_eq!(fields(&validate_question_metadata(1, &question("Q1", "-1"), &rules())), vec!["MARK"]);
+        assert_eq!(fields(&validate_question_metadata(1, &question("Q1", "một"), &rules())), vec!["MARK"]);
+        assert_eq!(fields(&validate_question_metadata(1, &question("Q1", " "), &rules())), vec!["MARK"]);
+    }
+
+    #[test]
+    fn codes_are_checked_against_the_subject_syllabus() {
+        let mut settings = Settings::default();
+        settings.syllabus.insert("MAE101".to_string(), SyllabusSettings {
+            units: vec![" 1 ".to_string(), "2".to_string()],
+            los: vec!["lo1".to_string()],
+        });
+        let rules = validation_rules(&settings, Some("MAE101"));
+        assert!(validate_question_metadata(1, &question("Q1", "1"), &rules).is_empty());
+
+        let mut unknown = question("Q1", "1");
+        unknown.unit = "3".to_string();
+        unknown.lo = String::new();
+        let issues = validate_question_metadata(1, &unknown, &rules);
+        assert_eq!(fields(&issues), vec!["UNIT", "LO"]);
+        assert_eq!(issues[0].severity, "error");
+        assert_eq!(issues[1].severity, "warning");
+
+        // Môn chưa khai báo đề cương thì mã nào cũng hợp lệ
+        assert!(validate_question_metadata(1, &unknown, &validation_rules(&settings, Some("PRF192")))
+            .iter()
+            .all(|issue| issue.field == "LO"));
+    }
+
+    #[test]
+    fn mix_choices_and_creator_reviewer_are_required() {
+        let mut bad = question("Q1", "1");
+        bad.mix_choices = "maybe".to_string();
+        bad.creator_reviewer = "  ".to_string();
+        let issues = validate_question_metadata(1, &bad, &rules());
+        assert_eq!(fields(&issues), vec!["MIX CHOICES", "CREATOR-REVIEWER"]);
+        assert!(issues.iter().all(|issue| issue.severity == "error"));
+
+        bad.mix_choices = String::new();
+        assert_eq!(validate_question_metadata(1, &bad, &rules())[0].severity, "warning");
+    }
+
+    #[test]
+    fn issues_keep_the_table_position_of_duplicate_ids() {
+        let first = validate_question_metadata(1, &question("Q1", "1"), &rules());
+        let second = validate_question_metadata(2, &question("Q1", "20"), &rules());
+        assert!(first.is_empty());
+        assert_eq!(second[0].id, "Q1");
+        assert_eq!(second[0].position, 2);
+    }
+}
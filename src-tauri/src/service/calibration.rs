@@ -0,0 +1,630 @@
+use crate::functions::contrast_tokens::{apply_contrast, compare_contrast, compare_features, ContrastFeatures};
+use crate::functions::cosine_similarity::calculate_cosine_similarity;
+use crate::functions::embedding_cache::embed_texts;
+use crate::functions::embedding_model::{get_model, select_model_name};
+use crate::functions::lexical_similarity::estimate_jaccard;
+use crate::functions::plot_similarity::{
+    calculate_similarity_score, file_pair_components, file_pair_score, option_set_similarity, PairSide, ScoringPolicy,
+};
+use crate::functions::reranker::{get_reranker, rerank_candidates, rerank_text};
+use crate::middleware::fill_format::embeddable_option;
+use crate::service::progress::JobContext;
+use crate::service::settings::{load_settings, save_settings, Settings, ThresholdSettings};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// Tên các loại kiểm tra, trùng với tên ngưỡng trong settings.json
+const CHECKS: [&str; 4] = ["in_file", "database", "within_question", "cross_answers"];
+// Cặp trong file khi bật Reranker được quyết định bằng điểm cross-encoder so với Reranker.Threshold
+const RERANK_CHECK: &str = "rerank";
+// Cột mới được thêm vào cuối để file log cũ vẫn đọc đúng
+const HEADER: [&str; 11] = [
+    "check",
+    "subject",
+    "question_a",
+    "answer_a",
+    "question_b",
+    "answer_b",
+    "is_duplicate",
+    "score",
+    "model",
+    "options_a",
+    "options_b",
+];
+// Quét ngưỡng từ 0.30 đến 0.99, bước 0.01
+const SWEEP: std::ops::RangeInclusive<u32> = 30..=99;
+
+// Một quyết định của người duyệt trên một cặp bị (hoặc không bị) đánh dấu trùng.
+// `score`, `model` là điểm và model lúc kiểm tra; điểm chỉ được dùng lại khi model vẫn như cũ.
+// `options_a`, `options_b` là các lựa chọn của cặp trong file, mỗi lựa chọn một dòng trong CSV
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ReviewDecision {
+    pub check: String,
+    pub subject: Option<String>,
+    pub question_a: String,
+    pub answer_a: String,
+    pub question_b: String,
+    pub answer_b: String,
+    pub is_duplicate: bool,
+    pub score: Option<f32>,
+    pub model: Option<String>,
+    #[serde(default)]
+    pub options_a: Vec<String>,
+    #[serde(default)]
+    pub options_b: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ThresholdMetrics {
+    pub threshold: f32,
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CheckCalibration {
+    pub subject: Option<String>,
+    pub check: String,
+    pub pairs: usize,
+    pub duplicates: usize,
+    pub current_threshold: f32,
+    pub current: ThresholdMetrics,
+    // Ngưỡng có F1 cao nhất; None khi dữ liệu chỉ có một loại nhãn
+    pub recommended: Option<ThresholdMetrics>,
+    pub curve: Vec<ThresholdMetrics>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CalibrationReport {
+    pub source: String,
+    pub checks: Vec<CheckCalibration>,
+    pub skipped_rows: Vec<String>,
+    pub saved: bool,
+}
+
+// Trường CSV có dấu phẩy, dấu ngoặc kép hoặc xuống dòng được đặt trong ngoặc kép
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.into_iter()
+        .filter(|row| row.iter().any(|field| !field.trim().is_empty()))
+        .collect()
+}
+
+fn parse_label(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "duplicate" | "trùng" | "có" => Some(true),
+        "0" | "false" | "no" | "not_duplicate" | "không trùng" | "không" => Some(false),
+        _ => None,
+    }
+}
+
+fn normalize_check(value: &str) -> Option<&'static str> {
+    let value = value.trim().to_lowercase().replace(['-', ' '], "_");
+    CHECKS.iter().copied().find(|check| *check == value)
+}
+
+// Ghi thêm một quyết định vào cuối file log, tạo file kèm dòng tiêu đề nếu chưa có
+pub fn record_decision(decision: &ReviewDecision) -> Result<String, String> {
+    let check = normalize_check(&decision.check)
+        .ok_or_else(|| format!("Loại kiểm tra không hợp lệ: {}", decision.check))?;
+    let path = load_settings()?.paths.review_log;
+    let is_new = !Path::new(&path).exists();
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Không thể mở file {}: {}", path, e))?;
+
+    let mut content = String::new();
+    if is_new {
+        content.push_str(&HEADER.join(","));
+        content.push('\n');
+    }
+    let row = [
+        check.to_string(),
+        decision.subject.clone().unwrap_or_default(),
+        decision.question_a.clone(),
+        decision.answer_a.clone(),
+        decision.question_b.clone(),
+        decision.answer_b.clone(),
+        if decision.is_duplicate { "1" } else { "0" }.to_string(),
+        decision.score.map(|score| score.to_string()).unwrap_or_default(),
+        decision.model.clone().unwrap_or_default(),
+        decision.options_a.join("\n"),
+        decision.options_b.join("\n"),
+    ];
+    content.push_str(&row.iter().map(|value| csv_field(value)).collect::<Vec<_>>().join(","));
+    content.push('\n');
+
+    file.write_all(content.as_bytes())
+        .map_err(|e| format!("Không thể ghi file {}: {}", path, e))?;
+    Ok(path)
+}
+
+// Đọc các cặp đã gán nhãn; dòng lỗi được bỏ qua và liệt kê trong `skipped`
+fn read_decisions(path: &str, skipped: &mut Vec<String>) -> Result<Vec<ReviewDecision>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Không thể đọc file {}: {}", path, e))?;
+    let mut rows = parse_csv(&content).into_iter();
+
+    let header: Vec<String> = rows
+        .next()
+        .ok_or_else(|| format!("File {} không có dữ liệu", path))?
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let required = ["check", "question_a", "question_b", "is_duplicate"];
+    if let Some(missing) = required.iter().find(|name| column(name).is_none()) {
+        return Err(format!("File {} thiếu cột {}", path, missing));
+    }
+
+    let mut decisions = Vec::new();
+    for (index, row) in rows.enumerate() {
+        // Dòng 1 là tiêu đề
+        let line = index + 2;
+        let field = |name: &str| {
+            column(name)
+                .and_then(|position| row.get(position))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let check = match normalize_check(&field("check")) {
+            Some(check) => check,
+            None => {
+                skipped.push(format!("Dòng {}: loại kiểm tra không hợp lệ \"{}\"", line, field("check")));
+                continue;
+            }
+        };
+        let is_duplicate = match parse_label(&field("is_duplicate")) {
+            Some(label) => label,
+            None => {
+                skipped.push(format!("Dòng {}: nhãn không hợp lệ \"{}\"", line, field("is_duplicate")));
+                continue;
+            }
+        };
+        let non_empty = |value: String| if value.is_empty() { None } else { Some(value) };
+        let options = |name: &str| {
+            field(name)
+                .lines()
+                .map(|option| option.trim().to_string())
+                .filter(|option| !option.is_empty())
+                .collect()
+        };
+
+        decisions.push(ReviewDecision {
+            check: check.to_string(),
+            subject: non_empty(field("subject")),
+            question_a: field("question_a"),
+            answer_a: field("answer_a"),
+            question_b: field("question_b"),
+            answer_b: field("answer_b"),
+            is_duplicate,
+            score: field("score").parse::<f32>().ok().filter(|score| score.is_finite()),
+            model: non_empty(field("model")),
+            options_a: options("options_a"),
+            options_b: options("options_b"),
+        });
+    }
+
+    Ok(decisions)
+}
+
+fn cosine_or_zero(v1: &[f32], v2: &[f32]) -> f32 {
+    let similarity = calculate_cosine_similarity(v1, v2);
+    if similarity.is_finite() { similarity } else { 0.0 }
+}
+
+// Model đã chấm cặp này khi kiểm tra và loại kiểm tra dùng để hiệu chỉnh: cặp trong file được re-rank
+// khi bật Reranker, các cặp khác dùng model embedding chọn theo môn học và ngôn ngữ giống lúc kiểm tra.
+// Dùng cho cả việc dùng lại điểm đã ghi lẫn việc chấm lại để hai nơi luôn chọn cùng một model
+fn scoring_model(decision: &ReviewDecision, subject: Option<&str>, settings: &Settings) -> (&'static str, String) {
+    match normalize_check(&decision.check) {
+        Some("in_file") if settings.reranker.enabled => (RERANK_CHECK, settings.reranker.model.clone()),
+        check => (
+            check.unwrap_or("in_file"),
+            select_model_name(&settings.model, subject, &decision.question_a),
+        ),
+    }
+}
+
+// Chấm lại bằng cross-encoder với đúng văn bản mà fill_format_check đưa vào reranker
+fn rerank_decisions(decisions: &[&ReviewDecision], settings: &Settings) -> Result<Vec<f32>, String> {
+    let reranker = get_reranker(&settings.reranker.model, &settings.paths.model_cache)?;
+    decisions
+        .iter()
+        .map(|d| {
+            let candidate = rerank_text(&d.question_b, &d.answer_b);
+            let scores = rerank_candidates(&reranker, &rerank_text(&d.question_a, &d.answer_a), &[candidate.as_str()])?;
+            Ok(scores.first().copied().unwrap_or(0.0))
+        })
+        .collect()
+}
+
+// Tính lại điểm theo đúng cách của từng loại kiểm tra bằng model `model_name`, cho các cặp không có điểm
+// hoặc có điểm từ model khác với model hiện tại
+fn rescore(
+    decisions: &[&ReviewDecision],
+    model_name: &str,
+    settings: &Settings,
+    policy: &ScoringPolicy,
+    job: &JobContext,
+) -> Result<Vec<f32>, String> {
+    let model = get_model(model_name, &settings.paths.model_cache)?;
+
+    // Lựa chọn được embed theo nội dung sau "a.", "b.", ... như khi đọc file
+    let option_text = |option: &str| embeddable_option(option).unwrap_or(option).to_string();
+    let option_texts: Vec<String> = decisions
+        .iter()
+        .flat_map(|d| {
+            let within_question = if d.check == "within_question" {
+                vec![option_text(&d.question_a), option_text(&d.question_b)]
+            } else {
+                Vec::new()
+            };
+            d.options_a
+                .iter()
+                .chain(&d.options_b)
+                .filter_map(|option| embeddable_option(option).map(|content| content.to_string()))
+                .chain(within_question)
+        })
+        .collect();
+    let texts: Vec<&str> = decisions
+        .iter()
+        .flat_map(|d| [d.question_a.as_str(), d.answer_a.as_str(), d.question_b.as_str(), d.answer_b.as_str()])
+        .chain(option_texts.iter().map(|text| text.as_str()))
+        .filter(|text| !text.trim().is_empty())
+        .collect();
+    let embeddings = embed_texts(&model, &texts, settings, job)?;
+    let by_text: HashMap<&str, &Vec<f32>> = texts.iter().copied().zip(embeddings.iter()).collect();
+    let empty = Vec::new();
+    let embedding = |text: &str| by_text.get(text).copied().unwrap_or(&empty);
+    // Lựa chọn quá ngắn không có embedding, giống Question::option_embeddings
+    let option_embeddings = |options: &[String]| -> Vec<Vec<f32>> {
+        options
+            .iter()
+            .map(|option| embeddable_option(option).map_or_else(Vec::new, |content| embedding(content).clone()))
+            .collect()
+    };
+
+    Ok(decisions
+        .iter()
+        .map(|d| {
+            let stem = cosine_or_zero(embedding(&d.question_a), embedding(&d.question_b));
+            let answer = cosine_or_zero(embedding(&d.answer_a), embedding(&d.answer_b));
+            let signatures = (
+                (policy.hybrid.signature(&d.question_a), policy.hybrid.signature(&d.answer_a)),
+                (policy.hybrid.signature(&d.question_b), policy.hybrid.signature(&d.answer_b)),
+            );
+            let features = (
+                ContrastFeatures::new(&d.question_a, &d.answer_a),
+                ContrastFeatures::new(&d.question_b, &d.answer_b),
+            );
+
+            match d.check.as_str() {
+                "in_file" => {
+                    let options = option_set_similarity(&option_embeddings(&d.options_a), &option_embeddings(&d.options_b));
+                    let (components, contrast, _) = file_pair_components(
+                        stem,
+                        answer,
+                        options,
+                        (
+                            PairSide { signatures: &signatures.0, features: &features.0 },
+                            PairSide { signatures: &signatures.1, features: &features.1 },
+                        ),
+                        policy,
+                    );
+                    file_pair_score(&components, contrast.as_ref(), policy).0
+                }
+                // Như nearest_bank_matches: bản ghi có văn bản thì trộn điểm Jaccard
+                "database" => {
+                    let lexical = Some(&d.question_b)
+                        .filter(|text| !text.trim().is_empty())
+                        .map(|_| {
+                            (
+                                estimate_jaccard(&signatures.0 .0, &signatures.1 .0),
+                                estimate_jaccard(&signatures.0 .1, &signatures.1 .1),
+                            )
+                        });
+                    let score = calculate_similarity_score(stem, answer, None, lexical, policy.thresholds.database, policy);
+                    apply_contrast(score, compare_features(&features.0, &features.1, &policy.contrast).as_ref())
+                }
+                // Hai lựa chọn trong cùng câu hỏi được ghi ở cột question_a, question_b
+                "within_question" => {
+                    let similarity = cosine_or_zero(
+                        embedding(&option_text(&d.question_a)),
+                        embedding(&option_text(&d.question_b)),
+                    );
+                    let contrast = compare_contrast("", &d.question_a, "", &d.question_b, &policy.contrast);
+                    apply_contrast(similarity, contrast.as_ref())
+                }
+                // Kiểm tra trùng đáp án giữa các câu không giảm điểm theo khác biệt
+                _ => answer,
+            }
+        })
+        .collect())
+}
+
+fn metrics_at(threshold: f32, scored: &[(f32, bool)]) -> ThresholdMetrics {
+    // Cùng quy ước với các bước kiểm tra: điểm lớn hơn ngưỡng mới bị coi là trùng
+    let count = |predicted: bool, actual: bool| {
+        scored
+            .iter()
+            .filter(|(score, label)| (*score > threshold) == predicted && *label == actual)
+            .count()
+    };
+    let (tp, fp, fn_) = (count(true, true), count(true, false), count(false, true));
+
+    let precision = if tp + fp > 0 { tp as f32 / (tp + fp) as f32 } else { 0.0 };
+    let recall = if tp + fn_ > 0 { tp as f32 / (tp + fn_) as f32 } else { 0.0 };
+    let f1 = if precision + recall > 0.0 { 2.0 * precision * recall / (precision + recall) } else { 0.0 };
+
+    ThresholdMetrics {
+        threshold,
+        precision,
+        recall,
+        f1,
+        true_positives: tp,
+        false_positives: fp,
+        false_negatives: fn_,
+    }
+}
+
+fn threshold_of(thresholds: &ThresholdSettings, check: &str) -> f32 {
+    match check {
+        "in_file" => thresholds.in_file,
+        "database" => thresholds.database,
+        "within_question" => thresholds.within_question,
+        _ => thresholds.cross_answers,
+    }
+}
+
+fn set_threshold(thresholds: &mut ThresholdSettings, check: &str, value: f32) {
+    match check {
+        "in_file" => thresholds.in_file = value,
+        "database" => thresholds.database = value,
+        "within_question" => thresholds.within_question = value,
+        _ => thresholds.cross_answers = value,
+    }
+}
+
+fn calibrate_check(
+    subject: Option<String>,
+    check: &str,
+    scored: &[(f32, bool)],
+    current_threshold: f32,
+) -> CheckCalibration {
+    let duplicates = scored.iter().filter(|(_, label)| *label).count();
+    let curve: Vec<ThresholdMetrics> = SWEEP
+        .map(|step| metrics_at(step as f32 / 100.0, scored))
+        .collect();
+
+    // F1 bằng nhau thì chọn ngưỡng cao hơn để ít báo nhầm hơn
+    let (recommended, message) = if duplicates == 0 || duplicates == scored.len() {
+        (None, Some("Cần có cả cặp trùng và cặp không trùng để đề xuất ngưỡng".to_string()))
+    } else {
+        let best = curve
+            .iter()
+            .fold(None::<&ThresholdMetrics>, |best, metrics| match best {
+                Some(best) if best.f1 > metrics.f1 => Some(best),
+                _ => Some(metrics),
+            })
+            .cloned();
+        (best, None)
+    };
+
+    CheckCalibration {
+        subject,
+        check: check.to_string(),
+        pairs: scored.len(),
+        duplicates,
+        current_threshold,
+        current: metrics_at(current_threshold, scored),
+        recommended,
+        curve,
+        message,
+    }
+}
+
+// Hiệu chỉnh ngưỡng từ file CSV (mặc định là file log quyết định của người duyệt).
+// Mỗi môn học và mỗi loại kiểm tra được hiệu chỉnh riêng; `save` ghi ngưỡng đề xuất vào settings.json
+pub fn calibrate_thresholds(csv_path: Option<&str>, save: bool) -> Result<CalibrationReport, String> {
+    let mut settings = load_settings()?;
+    let source = csv_path
+        .map(|path| path.to_string())
+        .unwrap_or_else(|| settings.paths.review_log.clone());
+
+    let mut skipped_rows = Vec::new();
+    let decisions = read_decisions(&source, &mut skipped_rows)?;
+    if decisions.is_empty() {
+        return Err(format!("File {} chưa có cặp câu nào được gán nhãn", source));
+    }
+
+    let mut by_subject: BTreeMap<Option<String>, Vec<&ReviewDecision>> = BTreeMap::new();
+    for decision in &decisions {
+        by_subject.entry(decision.subject.clone()).or_default().push(decision);
+    }
+
+    let job = JobContext::new(None, None);
+    let mut checks = Vec::new();
+
+    for (subject, decisions) in by_subject {
+        let policy = ScoringPolicy::new(&settings, subject.as_deref());
+
+        // Điểm đã ghi chỉ dùng lại khi được tính bằng model sẽ được chọn bây giờ
+        let scorers: Vec<(&str, String)> = decisions
+            .iter()
+            .map(|d| scoring_model(d, subject.as_deref(), &settings))
+            .collect();
+        let mut scores: Vec<Option<f32>> = decisions
+            .iter()
+            .zip(&scorers)
+            .map(|(d, (_, model))| d.score.filter(|_| d.model.as_deref() == Some(model.as_str())))
+            .collect();
+
+        let mut missing: BTreeMap<&(&str, String), Vec<usize>> = BTreeMap::new();
+        for (i, scorer) in scorers.iter().enumerate().filter(|(i, _)| scores[*i].is_none()) {
+            missing.entry(scorer).or_default().push(i);
+        }
+        for ((check, model), indices) in missing {
+            let to_rescore: Vec<&ReviewDecision> = indices.iter().map(|&i| decisions[i]).collect();
+            let rescored = if *check == RERANK_CHECK {
+                rerank_decisions(&to_rescore, &settings)?
+            } else {
+                rescore(&to_rescore, model, &settings, &policy, &job)?
+            };
+            for (i, score) in indices.into_iter().zip(rescored) {
+                scores[i] = Some(score);
+            }
+        }
+
+        for check in CHECKS.iter().copied().chain([RERANK_CHECK]) {
+            let scored: Vec<(f32, bool)> = decisions
+                .iter()
+                .zip(&scorers)
+                .zip(&scores)
+                .filter(|((_, (scorer_check, _)), _)| *scorer_check == check)
+                .filter_map(|((d, _), score)| Some(((*score)?, d.is_duplicate)))
+                .collect();
+            if scored.is_empty() {
+                continue;
+            }
+
+            let current_threshold = if check == RERANK_CHECK {
+                settings.reranker.threshold
+            } else {
+                threshold_of(&policy.thresholds, check)
+            };
+            let mut calibration = calibrate_check(subject.clone(), check, &scored, current_threshold);
+            if check == RERANK_CHECK && subject.is_some() && calibration.message.is_none() {
+                calibration.message = Some("Ngưỡng re-rank dùng chung cho mọi môn học, không được lưu theo môn".to_string());
+            }
+            checks.push(calibration);
+        }
+    }
+
+    if save {
+        for calibration in &checks {
+            let recommended = match &calibration.recommended {
+                Some(recommended) => recommended,
+                None => continue,
+            };
+            let thresholds = match &calibration.subject {
+                _ if calibration.check == RERANK_CHECK => {
+                    if calibration.subject.is_none() {
+                        settings.reranker.threshold = recommended.threshold;
+                    }
+                    continue;
+                }
+                Some(subject) => {
+                    let base = settings.thresholds_for(Some(subject));
+                    settings.subject_thresholds.entry(subject.clone()).or_insert(base)
+                }
+                None => &mut settings.thresholds,
+            };
+            set_threshold(thresholds, &calibration.check, recommended.threshold);
+        }
+        save_settings(&settings)?;
+    }
+
+    Ok(CalibrationReport {
+        source,
+        checks,
+        skipped_rows,
+        saved: save,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_csv_handles_quotes_bom_and_blank_lines() {
+        let content = "\u{feff}a,b,c\r\n\"x, y\",\"say \"\"hi\"\"\",\"line1\nline2\"\n\n,,\nlast,row";
+        let rows = parse_csv(content);
+        assert_eq!(rows, vec![
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            vec!["x, y".to_string(), "say \"hi\"".to_string(), "line1\nline2".to_string()],
+            vec!["last".to_string(), "row".to_string()],
+        ]);
+    }
+
+    #[test]
+    fn csv_field_round_trips_through_parse_csv() {
+        let values = ["plain", "có, dấu phẩy", "\"quoted\"", "two\nlines"];
+        let line = values.iter().map(|value| csv_field(value)).collect::<Vec<_>>().join(",");
+        assert_eq!(parse_csv(&line), vec![values.iter().map(|value| value.to_string()).collect::<Vec<_>>()]);
+    }
+
+    #[test]
+    fn metrics_count_scores_strictly_above_threshold() {
+        let scored = [(0.9, true), (0.7, true), (0.7, false), (0.4, true), (0.2, false)];
+        let metrics = metrics_at(0.7, &scored);
+
+        assert_eq!(metrics.true_positives, 1);
+        assert_eq!(metrics.false_positives, 0);
+        assert_eq!(metrics.false_negatives, 2);
+        assert_eq!(metrics.precision, 1.0);
+        assert!((metrics.recall - 1.0 / 3.0).abs() < 1e-6);
+        assert!((metrics.f1 - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn metrics_without_predictions_are_zero() {
+        let metrics = metrics_at(0.95, &[(0.5, true), (0.1, false)]);
+        assert_eq!(metrics.precision, 0.0);
+        assert_eq!(metrics.recall, 0.0);
+        assert_eq!(metrics.f1, 0.0);
+    }
+
+    #[test]
+    fn labels_accept_english_and_vietnamese() {
+        assert_eq!(parse_label(" Trùng "), Some(true));
+        assert_eq!(parse_label("không trùng"), Some(false));
+        assert_eq!(parse_label("maybe"), None);
+    }
+}
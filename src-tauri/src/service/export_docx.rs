@@ -21,11 +21,16 @@ pub fn extract_questions_from_docx(file_path: &str) -> Result<Vec<QuestionData>,
     // Đọc và parse document
     let doc_file = DocxFile::from_file(file_path)?;
     let doc = doc_file.parse()?;
-    
+
+    Ok(extract_questions_from_content(&doc.document.body.content))
+}
+
+// Mỗi bảng trong body là một câu hỏi, theo thứ tự trong file
+pub fn extract_questions_from_content(content: &[BodyContent]) -> Vec<QuestionData> {
     let mut questions = Vec::new();
     
     // Lặp qua các phần tử trong body để tìm các bảng
-    for element in &doc.document.body.content {
+    for element in content {
         if let BodyContent::Table(table) = element {
             let mut current_question = QuestionData {
                 qn: String::new(),
@@ -51,9 +56,14 @@ pub fn extract_questions_from_docx(file_path: &str) -> Result<Vec<QuestionData>,
                 let key = extract_text_from_row_cell(&row.cells[0]).trim().to_string();
                 let value = extract_text_from_row_cell(&row.cells[1]).trim().to_string();
 
+                // Hàng đầu tiên có dạng "QN=<mã>" | nội dung câu hỏi
+                if let Some(qn) = key.strip_prefix("QN=") {
+                    current_question.qn = qn.trim().to_string();
+                    current_question.question = value;
+                    continue;
+                }
+
                 match key.as_str() {
-                    "QN=" => current_question.qn = value,
-                    "Which of the following best describes an array?" => current_question.question = value,
                     "a." | "b." | "c." | "d." => {
                         if !value.is_empty() {
                             current_question.options.push(value);
@@ -75,10 +85,9 @@ pub fn extract_questions_from_docx(file_path: &str) -> Result<Vec<QuestionData>,
         }
     }
 
-    Ok(questions)
+    questions
 }
 
-#[allow(dead_code)]
 fn extract_text_from_row_cell(cell: &TableRowContent) -> String {
     match cell {
         TableRowContent::TableCell(cell_data) => {
@@ -1,3 +1,6 @@
 pub mod querydb;
 pub mod export_docx;
-
+pub mod progress;
+pub mod template_docx;
+pub mod settings;
+pub mod calibration;
\ No newline at end of file
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+use tauri::Window;
+
+pub const PROGRESS_EVENT: &str = "check-progress";
+pub const CANCELLED_MESSAGE: &str = "Tác vụ đã bị hủy";
+
+// Cờ hủy của các tác vụ đang chạy, key là job_id
+static RUNNING_JOBS: LazyLock<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Số thứ tự tác vụ trong phiên chạy, giữ cho job_id tự sinh không trùng kể cả khi cùng mili giây
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Clone, serde::Serialize)]
+pub struct ProgressPayload {
+    pub job_id: String,
+    pub stage: String,
+    pub current: usize,
+    pub total: usize,
+}
+
+pub struct JobContext {
+    pub job_id: String,
+    window: Option<Window>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobContext {
+    pub fn new(job_id: Option<String>, window: Option<Window>) -> Self {
+        let job_id = job_id.unwrap_or_else(|| {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            format!("job_{}_{}", timestamp, NEXT_JOB.fetch_add(1, Ordering::Relaxed))
+        });
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Ok(mut jobs) = RUNNING_JOBS.lock() {
+            jobs.insert(job_id.clone(), cancelled.clone());
+        }
+
+        JobContext { job_id, window, cancelled }
+    }
+
+    // Gửi sự kiện tiến độ lên giao diện (parsing, embedding, comparing, writing)
+    pub fn report(&self, stage: &str, current: usize, total: usize) {
+        if let Some(window) = &self.window {
+            let payload = ProgressPayload {
+                job_id: self.job_id.clone(),
+                stage: stage.to_string(),
+                current,
+                total,
+            };
+            if let Err(e) = window.emit(PROGRESS_EVENT, payload) {
+                println!("Không thể gửi tiến độ: {}", e);
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED_MESSAGE.to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for JobContext {
+    fn drop(&mut self) {
+        // Chỉ gỡ cờ của chính tác vụ này, không gỡ cờ của tác vụ khác đã đăng ký lại cùng job_id
+        if let Ok(mut jobs) = RUNNING_JOBS.lock() {
+            if jobs.get(&self.job_id).is_some_and(|cancelled| Arc::ptr_eq(cancelled, &self.cancelled)) {
+                jobs.remove(&self.job_id);
+            }
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub millis: u64,
+}
+
+// Đo thời gian từng bước của một tác vụ: mỗi lần `finish` ghi lại thời gian kể từ bước trước
+pub struct StageTimer {
+    last: Instant,
+    timings: Vec<StageTiming>,
+}
+
+impl StageTimer {
+    pub fn start() -> Self {
+        StageTimer {
+            last: Instant::now(),
+            timings: Vec::new(),
+        }
+    }
+
+    pub fn finish(&mut self, stage: &str) {
+        let now = Instant::now();
+        let millis = now.duration_since(self.last).as_millis() as u64;
+        println!("Bước {}: {} ms", stage, millis);
+        self.timings.push(StageTiming {
+            stage: stage.to_string(),
+            millis,
+        });
+        self.last = now;
+    }
+
+    pub fn timings(&self) -> &[StageTiming] {
+        &self.timings
+    }
+}
+
+pub fn cancel_job(job_id: &str) -> bool {
+    match RUNNING_JOBS.lock() {
+        Ok(jobs) => match jobs.get(job_id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_job_ids_are_unique() {
+        let first = JobContext::new(None, None);
+        let second = JobContext::new(None, None);
+        assert_ne!(first.job_id, second.job_id);
+        assert!(cancel_job(&first.job_id));
+        assert!(first.is_cancelled() && !second.is_cancelled());
+    }
+
+    #[test]
+    fn dropping_a_job_keeps_a_newer_job_with_the_same_id() {
+        let old = JobContext::new(Some("same_job".to_string()), None);
+        let new = JobContext::new(Some("same_job".to_string()), None);
+        drop(old);
+
+        assert!(cancel_job("same_job"));
+        assert!(new.check_cancelled().is_err());
+        drop(new);
+        assert!(!cancel_job("same_job"));
+    }
+}
@@ -1,39 +1,181 @@
-use duckdb::{Connection, Result};
+use duckdb::{Connection, Result, Statement};
 use serde_json;
+use crate::database::createdb::ensure_schema;
+use crate::functions::quantization::{quantize, QuantizationMode, QuantizedCode};
 
-pub fn query_db() -> Result<Vec<(Vec<f32>, Vec<f32>)>> {
-    let conn = Connection::open("data.duckdb")?;
+// Một câu hỏi trong ngân hàng; dữ liệu cũ chưa có mã và nội dung thì để chuỗi rỗng
+#[derive(Debug, Clone)]
+pub struct BankQuestion {
+    pub rowid: i64,
+    // Model đã tạo embedding của dòng này
+    pub model: String,
+    pub question_id: String,
+    pub question_text: String,
+    pub answer_text: String,
+    pub question_embedding: Vec<f32>,
+    pub answer_embedding: Vec<f32>,
+}
+
+// Bản lượng tử hóa (phần dẫn, đáp án) của một câu trong ngân hàng, đọc cho lượt lọc nhanh
+#[derive(Debug, Clone)]
+pub struct BankCode {
+    pub rowid: i64,
+    pub model: String,
+    pub question_code: QuantizedCode,
+    pub answer_code: QuantizedCode,
+}
+
+const BANK_COLUMNS: &str = "
+    rowid,
+    CAST(question_embedding AS JSON),
+    CAST(answer_embedding AS JSON),
+    COALESCE(question_id, ''),
+    COALESCE(question_text, ''),
+    COALESCE(answer_text, ''),
+    COALESCE(model, '')
+";
+
+// Đọc các dòng chọn theo BANK_COLUMNS, bỏ qua dòng hỏng thay vì dừng chương trình
+fn read_bank_rows(stmt: &mut Statement) -> Result<Vec<BankQuestion>> {
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, String>(5)?,
+            row.get::<_, String>(6)?,
+        ))
+    })?;
+
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|(rowid, q_json, a_json, question_id, question_text, answer_text, model)| {
+            Some(BankQuestion {
+                rowid,
+                model,
+                question_id,
+                question_text,
+                answer_text,
+                question_embedding: serde_json::from_str(&q_json).ok()?,
+                answer_embedding: serde_json::from_str(&a_json).ok()?,
+            })
+        })
+        .collect())
+}
+
+// Lấy câu hỏi của mọi model, các dòng cùng model xếp liền nhau; vector của hai model khác nhau không so được
+// với nhau nên nơi gọi phải so từng nhóm với embedding của file theo đúng model đó
+pub fn query_db(db_path: &str) -> Result<Vec<BankQuestion>> {
+    let conn = Connection::open(db_path)?;
+    ensure_schema(&conn)?;
+
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM data ORDER BY model, rowid", BANK_COLUMNS))?;
+    read_bank_rows(&mut stmt)
+}
+
+// Chỉ các dòng `rowids` (ứng viên sau lượt lọc nhanh), cùng thứ tự với query_db
+pub fn query_bank_rows(db_path: &str, rowids: &[i64]) -> Result<Vec<BankQuestion>> {
+    let conn = Connection::open(db_path)?;
+    ensure_schema(&conn)?;
+
+    let mut questions = Vec::with_capacity(rowids.len());
+    for chunk in rowids.chunks(1000) {
+        let list = chunk.iter().map(|rowid| rowid.to_string()).collect::<Vec<_>>().join(", ");
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM data WHERE rowid IN ({})", BANK_COLUMNS, list))?;
+        questions.extend(read_bank_rows(&mut stmt)?);
+    }
+    questions.sort_by(|a, b| a.model.cmp(&b.model).then(a.rowid.cmp(&b.rowid)));
+    Ok(questions)
+}
+
+// Bản lượng tử hóa theo `mode` của mọi dòng, các dòng cùng model xếp liền nhau. Không đọc vector f32,
+// trừ các dòng chưa có bản mã theo `mode` (lưu trước khi bật lượng tử hóa): dòng đó được lượng tử hóa ngay khi đọc
+pub fn query_bank_codes(db_path: &str, mode: QuantizationMode) -> Result<Vec<BankCode>> {
+    let conn = Connection::open(db_path)?;
+    ensure_schema(&conn)?;
 
     let mut stmt = conn.prepare("
-        SELECT 
-            CAST(question_embedding AS JSON) as question_json,
-            CAST(answer_embedding AS JSON) as answer_json 
+        SELECT
+            rowid,
+            COALESCE(model, ''),
+            CASE WHEN code_mode = $1 THEN question_code END,
+            CASE WHEN code_mode = $1 THEN answer_code END,
+            CASE WHEN code_mode IS DISTINCT FROM $1 THEN CAST(question_embedding AS JSON) END,
+            CASE WHEN code_mode IS DISTINCT FROM $1 THEN CAST(answer_embedding AS JSON) END
         FROM data
+        ORDER BY model, rowid
     ")?;
 
-    let rows = stmt.query_map([], |row| {
-        let q_json: String = row.get(0)?;
-        let a_json: String = row.get(1)?;
-        
-        // Chuyển đổi từ JSON string sang Vec<f32>
-        let q_vec: Vec<f32> = serde_json::from_str(&q_json).unwrap();
-        let a_vec: Vec<f32> = serde_json::from_str(&a_json).unwrap();
-        
-        Ok((q_vec, a_vec))
+    let rows = stmt.query_map([mode.name()], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            (row.get::<_, Option<Vec<u8>>>(2)?, row.get::<_, Option<String>>(4)?),
+            (row.get::<_, Option<Vec<u8>>>(3)?, row.get::<_, Option<String>>(5)?),
+        ))
     })?;
 
-    let embeddings = rows.filter_map(Result::ok).collect();
-    Ok(embeddings)
+    let code = |(stored, json): (Option<Vec<u8>>, Option<String>)| match stored {
+        Some(bytes) => QuantizedCode::from_bytes(&bytes, mode),
+        None => serde_json::from_str::<Vec<f32>>(&json?).ok().map(|vector| quantize(&vector, mode)),
+    };
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|(rowid, model, question, answer)| {
+            Some(BankCode {
+                rowid,
+                model,
+                question_code: code(question)?,
+                answer_code: code(answer)?,
+            })
+        })
+        .collect())
+}
+
+// Câu có nội dung được embed với cách chia đoạn khác `chunking`, cần kiểm tra lại độ dài: (rowid, model,
+// phần dẫn, đáp án). NULL được coi như "off" vì dữ liệu cũ embed nguyên văn
+pub fn query_chunking_pending(db_path: &str, chunking: &str) -> Result<Vec<(i64, String, String, String)>> {
+    let conn = Connection::open(db_path)?;
+    ensure_schema(&conn)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT rowid, COALESCE(model, ''), question_text, COALESCE(answer_text, '') FROM data
+         WHERE question_text IS NOT NULL AND COALESCE(chunking, 'off') <> ?
+         ORDER BY model, rowid",
+    )?;
+    let rows = stmt.query_map([chunking], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+    Ok(rows.filter_map(Result::ok).collect())
+}
+
+// Lấy toàn bộ mã QN đã có trong ngân hàng câu hỏi (không phụ thuộc model)
+pub fn query_question_ids(db_path: &str) -> Result<Vec<String>> {
+    let conn = Connection::open(db_path)?;
+    ensure_schema(&conn)?;
+
+    let mut stmt = conn.prepare("
+        SELECT DISTINCT question_id
+        FROM data
+        WHERE question_id IS NOT NULL AND question_id <> ''
+    ")?;
+
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+    let ids = rows.filter_map(Result::ok).collect();
+    Ok(ids)
 }
 
 #[allow(dead_code)]
 fn main() -> Result<()> {
-    let embeddings = query_db()?;
+    let db_path = crate::service::settings::PathSettings::default().database;
+    let embeddings = query_db(&db_path)?;
     
     println!("Tổng số cặp embedding: {}", embeddings.len());
     
     // In ra 2 cặp đầu tiên để kiểm tra
-    for (i, (question, answer)) in embeddings.iter().take(2).enumerate() {
+    for (i, bank_question) in embeddings.iter().take(2).enumerate() {
+        let (question, answer) = (&bank_question.question_embedding, &bank_question.answer_embedding);
         println!("\n=== Cặp embedding thứ {} ===", i + 1);
         println!("\nQuestion embedding ({} chiều):", question.len());
         for (j, value) in question.iter().enumerate() {
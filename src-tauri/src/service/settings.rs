@@ -0,0 +1,556 @@
+use crate::functions::contrast_tokens::ContrastPenalties;
+use crate::functions::embedding_cache::EmbeddingCacheSettings;
+use crate::functions::embedding_model::DEFAULT_MODEL_NAME;
+use crate::functions::load_accurancy::threshold_from_legacy_value;
+use crate::functions::long_text::ChunkSettings;
+use crate::functions::plot_similarity::{HybridWeights, ScoringSettings};
+use crate::functions::quantization::QuantizationSettings;
+use crate::functions::reranker::{parse_reranker_name, RerankSettings};
+use crate::middleware::check_duplicate_ids::IdScheme;
+use crate::middleware::validate_metadata::{SyllabusSettings, ValidationSettings};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const SETTINGS_PATH: &str = "settings.json";
+pub const SETTINGS_VERSION: u32 = 1;
+const LEGACY_CONFIG_PATH: &str = "configs.json";
+
+// Ngưỡng kết luận trùng của từng loại kiểm tra, trong khoảng 0..1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct ThresholdSettings {
+    pub in_file: f32,
+    pub database: f32,
+    pub within_question: f32,
+    pub cross_answers: f32,
+}
+
+impl Default for ThresholdSettings {
+    fn default() -> Self {
+        ThresholdSettings {
+            in_file: 0.6,
+            database: 0.6,
+            within_question: 0.6,
+            cross_answers: 0.6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct ModelSettings {
+    pub default: String,
+    pub vietnamese: Option<String>,
+    // Model riêng cho từng môn học, ưu tiên hơn model theo ngôn ngữ
+    pub subjects: HashMap<String, String>,
+}
+
+impl Default for ModelSettings {
+    fn default() -> Self {
+        ModelSettings {
+            default: DEFAULT_MODEL_NAME.to_string(),
+            vietnamese: None,
+            subjects: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct PathSettings {
+    pub database: String,
+    pub backup_database: String,
+    pub embedding_cache: String,
+    pub model_cache: String,
+    // File CSV ghi lại quyết định trùng/không trùng của người duyệt, dùng để hiệu chỉnh ngưỡng
+    pub review_log: String,
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        PathSettings {
+            database: "data.duckdb".to_string(),
+            backup_database: "new_data.duckdb".to_string(),
+            embedding_cache: "embedding_cache.duckdb".to_string(),
+            model_cache: "FUC-mini".to_string(),
+            review_log: "review_decisions.csv".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct BackupSettings {
+    // Sao lưu database trước mỗi lần nhập câu hỏi mới
+    pub before_import: bool,
+    pub directory: String,
+    // Số bản sao lưu tự động được giữ lại, bản cũ hơn bị xóa
+    pub keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            before_import: true,
+            directory: "backups".to_string(),
+            keep: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default, deny_unknown_fields)]
+pub struct Settings {
+    pub version: u32,
+    pub thresholds: ThresholdSettings,
+    // Ngưỡng riêng cho từng môn học (thường lấy từ bước hiệu chỉnh), ưu tiên hơn Thresholds
+    pub subject_thresholds: HashMap<String, ThresholdSettings>,
+    pub model: ModelSettings,
+    pub paths: PathSettings,
+    pub backup: BackupSettings,
+    // Các mục dưới đây trước đây được đọc thẳng từ configs.json
+    pub scoring_policy: ScoringSettings,
+    pub hybrid_scoring: HybridWeights,
+    pub contrast_penalty: ContrastPenalties,
+    pub reranker: RerankSettings,
+    pub id_scheme: IdScheme,
+    pub validation: ValidationSettings,
+    // Mã UNIT, LO hợp lệ của từng môn học
+    pub syllabus: HashMap<String, SyllabusSettings>,
+    pub embedding_cache: EmbeddingCacheSettings,
+    pub quantization: QuantizationSettings,
+    pub long_text: ChunkSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            thresholds: ThresholdSettings::default(),
+            subject_thresholds: HashMap::new(),
+            model: ModelSettings::default(),
+            paths: PathSettings::default(),
+            backup: BackupSettings::default(),
+            scoring_policy: ScoringSettings::default(),
+            hybrid_scoring: HybridWeights::default(),
+            contrast_penalty: ContrastPenalties::default(),
+            reranker: RerankSettings::default(),
+            id_scheme: IdScheme::default(),
+            validation: ValidationSettings::default(),
+            syllabus: HashMap::new(),
+            embedding_cache: EmbeddingCacheSettings::default(),
+            quantization: QuantizationSettings::default(),
+            long_text: ChunkSettings::default(),
+        }
+    }
+}
+
+impl ThresholdSettings {
+    fn validate(&self, prefix: &str) -> Result<(), String> {
+        let thresholds = [
+            ("InFile", self.in_file),
+            ("Database", self.database),
+            ("WithinQuestion", self.within_question),
+            ("CrossAnswers", self.cross_answers),
+        ];
+        for (name, value) in thresholds {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{}.{} phải nằm trong khoảng 0 đến 1, hiện là {}", prefix, name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Settings {
+    // Ngưỡng của môn học nếu đã được hiệu chỉnh riêng, ngược lại dùng ngưỡng chung
+    pub fn thresholds_for(&self, subject: Option<&str>) -> ThresholdSettings {
+        subject
+            .and_then(|subject| self.subject_thresholds.get(subject.trim()))
+            .unwrap_or(&self.thresholds)
+            .clone()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version > SETTINGS_VERSION {
+            return Err(format!(
+                "{} có phiên bản {} mới hơn phiên bản ứng dụng hỗ trợ ({})",
+                SETTINGS_PATH, self.version, SETTINGS_VERSION
+            ));
+        }
+
+        self.thresholds.validate("Thresholds")?;
+        for (subject, thresholds) in &self.subject_thresholds {
+            thresholds.validate(&format!("SubjectThresholds.{}", subject))?;
+        }
+
+        if self.model.default.trim().is_empty() {
+            return Err("Model.Default không được để trống".to_string());
+        }
+        if let Some((subject, _)) = self.model.subjects.iter().find(|(_, name)| name.trim().is_empty()) {
+            return Err(format!("Model.Subjects: model của môn {} không được để trống", subject));
+        }
+
+        let paths = [
+            ("Paths.Database", &self.paths.database),
+            ("Paths.BackupDatabase", &self.paths.backup_database),
+            ("Paths.EmbeddingCache", &self.paths.embedding_cache),
+            ("Paths.ModelCache", &self.paths.model_cache),
+            ("Paths.ReviewLog", &self.paths.review_log),
+            ("Backup.Directory", &self.backup.directory),
+        ];
+        for (name, value) in paths {
+            if value.trim().is_empty() {
+                return Err(format!("{} không được để trống", name));
+            }
+        }
+        if self.paths.database == self.paths.backup_database {
+            return Err("Paths.BackupDatabase phải khác Paths.Database".to_string());
+        }
+
+        if self.backup.keep == 0 {
+            return Err("Backup.Keep phải lớn hơn 0".to_string());
+        }
+
+        self.validate_scoring()
+    }
+
+    fn validate_scoring(&self) -> Result<(), String> {
+        let scoring = &self.scoring_policy;
+        let weights = [
+            ("ScoringPolicy.StemWeight", scoring.stem_weight),
+            ("ScoringPolicy.AnswerWeight", scoring.answer_weight),
+            ("ScoringPolicy.OptionWeight", scoring.option_weight),
+            ("HybridScoring.SemanticWeight", self.hybrid_scoring.semantic),
+            ("HybridScoring.LexicalWeight", self.hybrid_scoring.lexical),
+        ];
+        for (name, value) in weights {
+            if value.is_nan() || value < 0.0 {
+                return Err(format!("{} không được âm, hiện là {}", name, value));
+            }
+        }
+        if scoring.stem_weight + scoring.answer_weight + scoring.option_weight <= 0.0 {
+            return Err("ScoringPolicy: tổng trọng số phải lớn hơn 0".to_string());
+        }
+
+        let mut fractions = vec![
+            ("ContrastPenalty.NegationPenalty", self.contrast_penalty.negation),
+            ("ContrastPenalty.NumericPenalty", self.contrast_penalty.numeric),
+            ("ContrastPenalty.CodeTokenPenalty", self.contrast_penalty.code_token),
+            ("Reranker.Threshold", self.reranker.threshold),
+        ];
+        if let Some(gate) = scoring.gate {
+            fractions.push(("ScoringPolicy.Gate", gate));
+        }
+        for (name, value) in fractions {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{} phải nằm trong khoảng 0 đến 1, hiện là {}", name, value));
+            }
+        }
+
+        let counts = [
+            ("ScoringPolicy.BankTopK", scoring.bank_top_k),
+            ("HybridScoring.ShingleSize", self.hybrid_scoring.shingle_size),
+            ("HybridScoring.NumHashes", self.hybrid_scoring.num_hashes),
+            ("Reranker.TopK", self.reranker.top_k),
+            ("EmbeddingCache.BatchSize", self.embedding_cache.batch_size),
+            ("Quantization.Shortlist", self.quantization.shortlist),
+            ("LongText.MaxTokens", self.long_text.max_tokens),
+        ];
+        for (name, value) in counts {
+            if value == 0 {
+                return Err(format!("{} phải lớn hơn 0", name));
+            }
+        }
+        if self.long_text.overlap_tokens >= self.long_text.max_tokens {
+            return Err("LongText.OverlapTokens phải nhỏ hơn LongText.MaxTokens".to_string());
+        }
+
+        if parse_reranker_name(&self.reranker.model).is_none() {
+            return Err(format!("Reranker.Model: model reranker không được hỗ trợ: {}", self.reranker.model));
+        }
+        if self.validation.mark_min > self.validation.mark_max {
+            return Err("Validation.MarkMin phải nhỏ hơn hoặc bằng Validation.MarkMax".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+// None khi không có configs.json; file có nhưng không đọc được hoặc sai JSON là lỗi, không âm thầm dùng mặc định
+fn read_legacy_config(path: &str) -> Result<Option<serde_json::Value>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Lỗi đọc file {}: {}", path, e)),
+    };
+
+    serde_json::from_str(&content).map(Some).map_err(|e| {
+        format!(
+            "File {} không hợp lệ (dòng {}, cột {}): {}",
+            path,
+            e.line(),
+            e.column(),
+            e
+        )
+    })
+}
+
+fn legacy_section<T: DeserializeOwned>(config: &serde_json::Value, key: &str) -> Result<Option<T>, String> {
+    match config.get(key) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|e| format!("Mục {} trong {} không hợp lệ: {}", key, LEGACY_CONFIG_PATH, e)),
+    }
+}
+
+// Lần đầu chạy phiên bản mới: lấy ngưỡng "Value", model và các mục cấu hình khác từ configs.json cũ
+fn migrate_from_legacy_config() -> Result<Settings, String> {
+    match read_legacy_config(LEGACY_CONFIG_PATH)? {
+        Some(config) => settings_from_legacy(&config),
+        None => Ok(Settings::default()),
+    }
+}
+
+fn settings_from_legacy(config: &serde_json::Value) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+
+    if let Some(value) = config["Value"].as_f64() {
+        let threshold = threshold_from_legacy_value(value).clamp(0.0, 1.0);
+        settings.thresholds = ThresholdSettings {
+            in_file: threshold,
+            database: threshold,
+            within_question: threshold,
+            cross_answers: threshold,
+        };
+    }
+
+    if let Some(name) = config["Model"].as_str() {
+        settings.model.default = name.to_string();
+    }
+    settings.model.vietnamese = config["VietnameseModel"].as_str().map(|name| name.to_string());
+    if let Some(subjects) = config["SubjectModels"].as_object() {
+        settings.model.subjects = subjects
+            .iter()
+            .filter_map(|(subject, name)| Some((subject.clone(), name.as_str()?.to_string())))
+            .collect();
+    }
+
+    // Các mục chấm điểm, re-rank, mã QN, kiểm tra metadata, cache embedding, lượng tử hóa và chia đoạn văn bản dài
+    let mut config = config.clone();
+    // Rule và Mode trước đây không phân biệt hoa thường; Mode "none" nghĩa là tắt lượng tử hóa
+    if let Some(rule) = config["ScoringPolicy"]["Rule"].as_str().map(|rule| rule.trim().to_lowercase()) {
+        config["ScoringPolicy"]["Rule"] = rule.into();
+    }
+    if let Some(mode) = config["Quantization"]["Mode"].as_str().map(|mode| mode.trim().to_lowercase()) {
+        config["Quantization"]["Mode"] = if mode == "none" { serde_json::Value::Null } else { mode.into() };
+    }
+
+    if let Some(scoring) = legacy_section(&config, "ScoringPolicy")? {
+        settings.scoring_policy = scoring;
+    }
+    if let Some(hybrid) = legacy_section(&config, "HybridScoring")? {
+        settings.hybrid_scoring = hybrid;
+    }
+    if let Some(penalties) = legacy_section(&config, "ContrastPenalty")? {
+        settings.contrast_penalty = penalties;
+    }
+    if let Some(reranker) = legacy_section(&config, "Reranker")? {
+        settings.reranker = reranker;
+    }
+    if let Some(scheme) = legacy_section(&config, "IdScheme")? {
+        settings.id_scheme = scheme;
+    }
+    if let Some(validation) = legacy_section(&config, "Validation")? {
+        settings.validation = validation;
+    }
+    if let Some(syllabus) = legacy_section(&config, "Syllabus")? {
+        settings.syllabus = syllabus;
+    }
+    if let Some(quantization) = legacy_section(&config, "Quantization")? {
+        settings.quantization = quantization;
+    }
+    if let Some(long_text) = legacy_section(&config, "LongText")? {
+        settings.long_text = long_text;
+    }
+
+    // Cache embedding từng là hai khóa riêng ở cấp ngoài cùng
+    if let Some(enabled) = config["EmbeddingCache"].as_bool() {
+        settings.embedding_cache.enabled = enabled;
+    }
+    if let Some(batch_size) = config["EmbeddingBatchSize"].as_u64() {
+        settings.embedding_cache.batch_size = batch_size as usize;
+    }
+
+    Ok(settings)
+}
+
+// Đọc lại file mỗi lần gọi; mỗi tác vụ (kiểm tra, nhập câu hỏi, hiệu chỉnh) chỉ gọi một lần rồi truyền xuống.
+// Chỉ ghi file khi chuyển từ configs.json lần đầu, còn lại chỉ set_settings mới ghi
+pub fn load_settings() -> Result<Settings, String> {
+    if !Path::new(SETTINGS_PATH).exists() {
+        let settings = migrate_from_legacy_config()?;
+        save_settings(&settings)?;
+        return Ok(settings);
+    }
+
+    let content = fs::read_to_string(SETTINGS_PATH)
+        .map_err(|e| format!("Lỗi đọc file {}: {}", SETTINGS_PATH, e))?;
+
+    let settings: Settings = serde_json::from_str(&content).map_err(|e| {
+        format!(
+            "File {} không hợp lệ (dòng {}, cột {}): {}",
+            SETTINGS_PATH,
+            e.line(),
+            e.column(),
+            e
+        )
+    })?;
+
+    settings.validate()?;
+    Ok(settings)
+}
+
+pub fn save_settings(settings: &Settings) -> Result<(), String> {
+    settings.validate()?;
+
+    let mut settings = settings.clone();
+    settings.version = SETTINGS_VERSION;
+
+    let content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Lỗi khi chuyển cấu hình sang JSON: {}", e))?;
+    fs::write(SETTINGS_PATH, content)
+        .map_err(|e| format!("Lỗi ghi file {}: {}", SETTINGS_PATH, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::plot_similarity::CombinationRule;
+    use crate::functions::quantization::QuantizationMode;
+    use serde_json::json;
+
+    #[test]
+    fn default_settings_are_valid() {
+        assert!(Settings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values() {
+        let mut settings = Settings::default();
+        settings.thresholds.database = 1.2;
+        assert!(settings.validate().unwrap_err().contains("Thresholds.Database"));
+
+        let mut settings = Settings::default();
+        settings.subject_thresholds.insert("MAE101".to_string(), ThresholdSettings {
+            in_file: -0.1,
+            ..ThresholdSettings::default()
+        });
+        assert!(settings.validate().unwrap_err().contains("SubjectThresholds.MAE101.InFile"));
+
+        let mut settings = Settings::default();
+        settings.paths.backup_database = settings.paths.database.clone();
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.long_text.overlap_tokens = settings.long_text.max_tokens;
+        assert!(settings.validate().unwrap_err().contains("LongText.OverlapTokens"));
+
+        let settings = Settings {
+            version: SETTINGS_VERSION + 1,
+            ..Settings::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let result = serde_json::from_value::<Settings>(json!({ "Thresholds": { "Infile": 0.5 } }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_legacy_config_gives_defaults() {
+        let settings = settings_from_legacy(&json!({})).unwrap();
+        assert_eq!(settings.model.default, DEFAULT_MODEL_NAME);
+        assert_eq!(settings.version, SETTINGS_VERSION);
+    }
+
+    #[test]
+    fn missing_legacy_config_is_none_and_malformed_one_is_an_error() {
+        let dir = std::env::temp_dir();
+        let missing = dir.join(format!("missing_configs_{}.json", std::process::id()));
+        assert_eq!(read_legacy_config(&missing.to_string_lossy()), Ok(None));
+
+        let malformed = dir.join(format!("malformed_configs_{}.json", std::process::id()));
+        fs::write(&malformed, "{ \"Value\": 70,").unwrap();
+        let path = malformed.to_string_lossy().to_string();
+        let error = read_legacy_config(&path).unwrap_err();
+        fs::remove_file(&malformed).unwrap();
+        assert!(error.contains(&path), "{}", error);
+
+        let valid = dir.join(format!("valid_configs_{}.json", std::process::id()));
+        fs::write(&valid, "{ \"Value\": 70 }").unwrap();
+        let config = read_legacy_config(&valid.to_string_lossy()).unwrap();
+        fs::remove_file(&valid).unwrap();
+        assert_eq!(config, Some(json!({ "Value": 70 })));
+    }
+
+    #[test]
+    fn legacy_config_is_migrated() {
+        let config = json!({
+            "Value": -(2.0 / 35.0) * 70.0,
+            "Model": "multilingual-e5-small",
+            "VietnameseModel": "multilingual-e5-base",
+            "SubjectModels": { "PRF192": "all-minilm-l6-v2", "Broken": 3 },
+            "ScoringPolicy": { "Rule": "Min", "StemWeight": 0.7 },
+            "Quantization": { "Mode": "NONE", "Shortlist": 50 },
+            "EmbeddingCache": false,
+            "EmbeddingBatchSize": 16
+        });
+        let settings = settings_from_legacy(&config).unwrap();
+
+        assert!((settings.thresholds.in_file - 0.7).abs() < 1e-4);
+        assert!((settings.thresholds.cross_answers - 0.7).abs() < 1e-4);
+        assert_eq!(settings.model.default, "multilingual-e5-small");
+        assert_eq!(settings.model.vietnamese.as_deref(), Some("multilingual-e5-base"));
+        assert_eq!(settings.model.subjects.len(), 1);
+        assert_eq!(settings.scoring_policy.rule, CombinationRule::Min);
+        assert_eq!(settings.scoring_policy.stem_weight, 0.7);
+        assert_eq!(settings.quantization.mode, None);
+        assert_eq!(settings.quantization.shortlist, 50);
+        assert!(!settings.embedding_cache.enabled);
+        assert_eq!(settings.embedding_cache.batch_size, 16);
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn legacy_quantization_mode_ignores_case() {
+        let settings = settings_from_legacy(&json!({ "Quantization": { "Mode": " Binary " } })).unwrap();
+        assert_eq!(settings.quantization.mode, Some(QuantizationMode::Binary));
+    }
+
+    #[test]
+    fn invalid_legacy_section_is_an_error() {
+        let result = settings_from_legacy(&json!({ "IdScheme": { "Start": "one" } }));
+        assert!(result.unwrap_err().contains("IdScheme"));
+    }
+
+    #[test]
+    fn subject_thresholds_ignore_surrounding_whitespace() {
+        let mut settings = Settings::default();
+        let calibrated = ThresholdSettings {
+            in_file: 0.8,
+            ..ThresholdSettings::default()
+        };
+        settings.subject_thresholds.insert("MAE101".to_string(), calibrated);
+
+        assert_eq!(settings.thresholds_for(Some(" MAE101 ")).in_file, 0.8);
+        assert_eq!(settings.thresholds_for(Some("PRF192")).in_file, settings.thresholds.in_file);
+        assert_eq!(settings.thresholds_for(None).in_file, settings.thresholds.in_file);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::middleware::check_duplicate_ids::IdScheme;
+use docx_rust::document::{
+    BodyContent, Paragraph, ParagraphContent, Run, RunContent, Table, TableCell, TableCellContent,
+    TableRow, TableRowContent, Text,
+};
+use docx_rust::Docx;
+
+// Nhãn cột đầu tiên theo đúng thứ tự mà fill_format.rs và process_docx.rs đọc
+pub const TEMPLATE_LABELS: [&str; 12] = [
+    "a.",
+    "b.",
+    "c.",
+    "d.",
+    "ANSWER:",
+    "MARK:",
+    "UNIT:",
+    "LO:",
+    "MIX CHOICES:",
+    "CREATOR-REVIEWER:",
+    "EDITOR:",
+    "REFERENCE:",
+];
+
+fn text_paragraph(text: String) -> Paragraph<'static> {
+    Paragraph {
+        content: vec![
+            ParagraphContent::Run(Run {
+                content: vec![
+                    RunContent::Text(Text {
+                        text: text.into(),
+                        space: None,
+                    })
+                ],
+                ..Default::default()
+            })
+        ],
+        ..Default::default()
+    }
+}
+
+fn template_row(label: String, value: String) -> TableRow<'static> {
+    let cell = |text: String| {
+        TableRowContent::TableCell(TableCell {
+            content: vec![TableCellContent::Paragraph(text_paragraph(text))],
+            ..Default::default()
+        })
+    };
+
+    TableRow {
+        cells: vec![cell(label), cell(value)],
+        ..Default::default()
+    }
+}
+
+pub fn question_template_table(question_id: &str) -> Table<'static> {
+    let mut rows = vec![template_row(format!("QN={}", question_id), String::new())];
+    rows.extend(
+        TEMPLATE_LABELS
+            .iter()
+            .map(|label| template_row(label.to_string(), String::new())),
+    );
+
+    Table {
+        rows,
+        ..Default::default()
+    }
+}
+
+// Tạo file mẫu gồm `count` bảng câu hỏi trống, mã QN đánh số từ `start_id` nếu có
+pub fn build_question_template(
+    count: usize,
+    subject: Option<&str>,
+    start_id: Option<u64>,
+    scheme: &IdScheme,
+) -> Docx<'static> {
+    let mut docx = Docx::default();
+    let content = &mut docx.document.body.content;
+
+    if let Some(subject) = subject {
+        content.push(BodyContent::Paragraph(text_paragraph(format!("SUBJECT: {}", subject.trim()))));
+        content.push(BodyContent::Paragraph(text_paragraph(String::new())));
+    }
+
+    for i in 0..count {
+        let question_id = start_id
+            .map(|start| scheme.format(start + i as u64))
+            .unwrap_or_default();
+
+        content.push(BodyContent::Table(question_template_table(&question_id)));
+        // Đoạn trống giữa hai bảng để Word không gộp chúng lại
+        content.push(BodyContent::Paragraph(text_paragraph(String::new())));
+    }
+
+    docx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::process_docx::parse_docx_questions;
+    use crate::middleware::check_duplicate_ids::{collect_table_ids, find_id_conflicts};
+    use crate::middleware::fill_format::set_cell_text;
+    use crate::middleware::validate_metadata::{validate_question_metadata, validation_rules};
+    use crate::service::export_docx::extract_questions_from_docx;
+    use crate::service::settings::Settings;
+    use docx_rust::DocxFile;
+    use std::collections::HashSet;
+
+    // Điền vào ô thứ hai của từng hàng như người soạn câu hỏi
+    fn fill_question(table: &mut Table, question: &str) {
+        let values = [
+            question, "Hà Nội", "Huế", "Đà Nẵng", "Sài Gòn", "A", "1", "1", "LO1", "Yes", "Creator - Reviewer", "", "",
+        ];
+        for (row, value) in table.rows.iter_mut().zip(values) {
+            set_cell_text(&mut row.cells[1], value);
+        }
+    }
+
+    fn write_template(mut docx: Docx<'static>, name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.docx", name, std::process::id()));
+        docx.write_file(&path).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn blank_template_has_numbered_tables_in_the_expected_layout() {
+        let docx = build_question_template(3, Some("MAE101"), Some(7), &IdScheme::default());
+        let path = write_template(docx, "blank_template");
+        let bytes = std::fs::read(&path).unwrap();
+
+        let questions = parse_docx_questions(&bytes).unwrap();
+        let ids: Vec<&str> = questions.iter().map(|q| q.id.as_str()).collect();
+        assert_eq!(ids, vec!["7", "8", "9"]);
+        assert!(questions.iter().all(|q| q.text.is_empty()));
+
+        let doc_file = DocxFile::from_file(&path).unwrap();
+        let parsed = doc_file.parse().unwrap();
+        let tables: Vec<&Table> = parsed.document.body.content.iter()
+            .filter_map(|element| match element {
+                BodyContent::Table(table) => Some(table),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tables.len(), 3);
+        assert_eq!(tables[0].rows.len(), TEMPLATE_LABELS.len() + 1);
+
+        // Mẫu trống chưa có MARK nên bị báo thiếu, không phải lỗi định dạng bảng
+        let rules = validation_rules(&Settings::default(), None);
+        let metadata = extract_questions_from_docx(&path).unwrap();
+        assert!(validate_question_metadata(1, &metadata[0], &rules).iter().any(|issue| issue.field == "MARK"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn filled_template_passes_the_format_checks() {
+        let mut docx = build_question_template(2, None, Some(1), &IdScheme::default());
+        let mut index = 0;
+        for element in docx.document.body.content.iter_mut() {
+            if let BodyContent::Table(table) = element {
+                index += 1;
+                fill_question(table, &format!("Câu hỏi số {}?", index));
+            }
+        }
+        let path = write_template(docx, "filled_template");
+        let bytes = std::fs::read(&path).unwrap();
+
+        let questions = parse_docx_questions(&bytes).unwrap();
+        assert_eq!(questions.len(), 2);
+        assert_eq!(questions[1].id, "2");
+        assert_eq!(questions[1].text, "Câu hỏi số 2?");
+        assert_eq!(questions[0].correct_answer_text, "Hà Nội");
+
+        let doc_file = DocxFile::from_file(&path).unwrap();
+        let parsed = doc_file.parse().unwrap();
+        assert!(find_id_conflicts(&collect_table_ids(&parsed.document.body.content), &HashSet::new()).is_empty());
+
+        let rules = validation_rules(&Settings::default(), None);
+        let metadata = extract_questions_from_docx(&path).unwrap();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata[0].options.len(), 4);
+        for (i, question) in metadata.iter().enumerate() {
+            assert!(validate_question_metadata(i + 1, question, &rules).is_empty());
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}